@@ -0,0 +1,247 @@
+//! Ingress scaling benchmark
+//!
+//! Starts the `rog` binary Cargo builds for the benchmark and measures:
+//! - how the server's thread count and memory grow with idle connections
+//! - message throughput with several producers, with and without many idle
+//!   connections open at the same time
+//!
+//! Usage: `cargo bench --bench ingress_bench -- [--server <path>] [--workers <n>]
+//!         [--connections 100,1000,4000] [--producers 1,4,16] [--messages <per producer>]`
+//!
+//! Thread and memory figures are read from `/proc` and are only shown on Linux.
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Result, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Port the benchmarked server listens on
+const BENCH_PORT: u16 = 49252;
+
+struct Options {
+    server: PathBuf,
+    workers: Option<usize>,
+    connections: Vec<usize>,
+    producers: Vec<usize>,
+    messages: usize,
+}
+
+impl Options {
+    fn parse() -> std::result::Result<Self, String> {
+        let mut options = Self {
+            server: default_server_path(),
+            workers: None,
+            connections: vec![100, 1000, 4000],
+            producers: vec![1, 4, 16],
+            messages: 20_000,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(flag) = args.next() {
+            // Passed by `cargo bench`
+            if flag == "--bench" {
+                continue;
+            }
+            let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+            match flag.as_str() {
+                "--server" => options.server = PathBuf::from(value),
+                "--workers" => options.workers = Some(parse_number(&flag, &value)?),
+                "--connections" => options.connections = parse_list(&flag, &value)?,
+                "--producers" => options.producers = parse_list(&flag, &value)?,
+                "--messages" => options.messages = parse_number(&flag, &value)?,
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> std::result::Result<usize, String> {
+    value.parse().map_err(|_| format!("{}: '{}' is not a number", flag, value))
+}
+
+fn parse_list(flag: &str, value: &str) -> std::result::Result<Vec<usize>, String> {
+    value.split(',').map(|item| parse_number(flag, item.trim())).collect()
+}
+
+fn default_server_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_rog"))
+}
+
+/// Server process under test, killed when dropped
+struct BenchServer {
+    child: Child,
+    address: SocketAddr,
+}
+
+impl BenchServer {
+    fn start(options: &Options, max_connections: usize) -> Result<Self> {
+        let address: SocketAddr = ([127, 0, 0, 1], BENCH_PORT).into();
+        let workers = options.workers
+            .map(|workers| format!(", \"worker_threads\": {}", workers))
+            .unwrap_or_default();
+        let config = format!(
+            r#"{{
+                "listeners": {{ "ingress": {{ "address": "{}" }}, "registration": {{ "enabled": false }} }},
+                "limits": {{ "max_connections": {}{} }},
+                "logging": {{ "log_messages": false }}
+            }}"#,
+            address, max_connections, workers,
+        );
+        let config_path = env::temp_dir().join(format!("ingress_bench_{}.json", std::process::id()));
+        fs::write(&config_path, config)?;
+
+        let child = Command::new(&options.server)
+            .args(["--headless", "--config"])
+            .arg(&config_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let server = Self { child, address };
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(address).is_err() {
+            if Instant::now() > deadline {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "server did not start"));
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(server)
+    }
+
+    /// Connect and wait until the server serves the connection
+    fn connect(&self) -> Result<(TcpStream, BufReader<TcpStream>)> {
+        let mut stream = TcpStream::connect(self.address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        stream.write_all(b"ACKMODE PARSED\n")?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("OK") {
+            return Err(std::io::Error::other(format!("unexpected reply: {}", line.trim())));
+        }
+        Ok((stream, reader))
+    }
+
+    /// Server thread count and resident memory in KiB, when `/proc` has them
+    fn usage(&self) -> Option<(usize, usize)> {
+        let status = fs::read_to_string(format!("/proc/{}/status", self.child.id())).ok()?;
+        let field = |name: &str| -> Option<usize> {
+            status.lines()
+                .find_map(|line| line.strip_prefix(name))?
+                .split_whitespace()
+                .next()?
+                .parse()
+                .ok()
+        };
+        Some((field("Threads:")?, field("VmRSS:")?))
+    }
+}
+
+impl Drop for BenchServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Send `messages` messages on each of `producers` connections and wait for every ACK
+fn throughput(server: &BenchServer, producers: usize, messages: usize) -> Result<f64> {
+    let connections = (0..producers).map(|_| server.connect()).collect::<Result<Vec<_>>>()?;
+    let started = Instant::now();
+
+    let handles: Vec<_> = connections.into_iter().enumerate().map(|(producer, (mut stream, mut reader))| {
+        thread::spawn(move || -> Result<()> {
+            let sender = thread::spawn(move || -> Result<()> {
+                let mut batch = Vec::new();
+                for sequence in 0..messages {
+                    writeln!(batch, "bench:{}-{}", producer, sequence)?;
+                    if batch.len() >= 64 * 1024 {
+                        stream.write_all(&batch)?;
+                        batch.clear();
+                    }
+                }
+                stream.write_all(&batch)
+            });
+
+            let mut line = String::new();
+            for _ in 0..messages {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 {
+                    return Err(std::io::Error::other("connection closed before every ACK arrived"));
+                }
+            }
+            sender.join().map_err(|_| std::io::Error::other("sender panicked"))?
+        })
+    }).collect();
+
+    for handle in handles {
+        handle.join().map_err(|_| std::io::Error::other("producer panicked"))??;
+    }
+    Ok((producers * messages) as f64 / started.elapsed().as_secs_f64())
+}
+
+fn format_usage(usage: Option<(usize, usize)>) -> (String, String) {
+    match usage {
+        Some((threads, rss)) => (threads.to_string(), format!("{:.1}", rss as f64 / 1024.0)),
+        None => ("n/a".to_string(), "n/a".to_string()),
+    }
+}
+
+fn run(options: &Options) -> Result<()> {
+    let max_idle = options.connections.iter().copied().max().unwrap_or(0);
+    let max_producers = options.producers.iter().copied().max().unwrap_or(0);
+    let server = BenchServer::start(options, max_idle + max_producers + 16)?;
+
+    println!("Idle connections");
+    println!("{:>12} {:>10} {:>10} {:>12}", "connections", "threads", "rss (MiB)", "connect (s)");
+    let (threads, rss) = format_usage(server.usage());
+    println!("{:>12} {:>10} {:>10} {:>12}", 0, threads, rss, "-");
+
+    let mut idle = Vec::new();
+    for &target in &options.connections {
+        let started = Instant::now();
+        while idle.len() < target {
+            idle.push(server.connect()?);
+        }
+        let (threads, rss) = format_usage(server.usage());
+        println!("{:>12} {:>10} {:>10} {:>12.2}", target, threads, rss, started.elapsed().as_secs_f64());
+    }
+
+    println!();
+    println!("Throughput ({} messages per producer)", options.messages);
+    println!("{:>12} {:>10} {:>14}", "producers", "idle", "messages/s");
+    for &producers in &options.producers {
+        let rate = throughput(&server, producers, options.messages)?;
+        println!("{:>12} {:>10} {:>14.0}", producers, idle.len(), rate);
+    }
+
+    drop(idle);
+    // Let the server notice the closed connections before measuring without them
+    thread::sleep(Duration::from_millis(500));
+    for &producers in &options.producers {
+        let rate = throughput(&server, producers, options.messages)?;
+        println!("{:>12} {:>10} {:>14.0}", producers, 0, rate);
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    println!("Benchmarking {}", options.server.display());
+    if let Err(e) = run(&options) {
+        eprintln!("Benchmark failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,104 @@
+//! Routing benchmark
+//!
+//! Routes messages from several threads across many events with many
+//! subscribers each, once with a thread subscribing and unsubscribing a
+//! producer in a loop and once without it. Subscribers discard what they
+//! get, so the figures show the cost of routing and fan-out handoff alone.
+//!
+//! Usage: `cargo bench --bench routing`
+
+// The binaries already report lints in this code
+#[path = "../src/transport/mod.rs"]
+#[allow(dead_code, unused_imports, clippy::all)]
+mod transport;
+
+use std::io::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use transport::client_interface::Client;
+use transport::ProducerPool;
+
+const EVENTS: usize = 1_000;
+const PRODUCERS: usize = 500;
+const SUBSCRIPTIONS_PER_PRODUCER: usize = 100;
+const ROUTERS: usize = 4;
+const MESSAGES_PER_ROUTER: usize = 25_000;
+
+/// Client that discards every message
+struct NullClient;
+
+impl Client for NullClient {
+    fn send(&self, _message: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn protocol_name(&self) -> &str {
+        "NULL"
+    }
+
+    fn address(&self) -> &str {
+        "null://bench"
+    }
+}
+
+fn main() {
+    let pool = Arc::new(ProducerPool::new());
+    for producer in 0..PRODUCERS {
+        let events = (0..SUBSCRIPTIONS_PER_PRODUCER)
+            .map(|n| format!("event-{}", (producer * 7 + n * 13) % EVENTS))
+            .collect::<Vec<_>>();
+        let events = events.iter().map(String::as_str).collect::<Vec<_>>();
+        pool.add_producer_with_events(format!("producer-{}", producer), Arc::new(NullClient), &events)
+            .expect("producer ids are unique");
+    }
+
+    println!(
+        "{} events, {} producers with {} subscriptions each, {} routing threads",
+        EVENTS, PRODUCERS, SUBSCRIPTIONS_PER_PRODUCER, ROUTERS,
+    );
+    for churn in [false, true] {
+        let stop = Arc::new(AtomicBool::new(false));
+        let churner = churn.then(|| {
+            let pool = Arc::clone(&pool);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut changes = 0usize;
+                while !stop.load(Ordering::Relaxed) {
+                    let event = format!("event-{}", changes % EVENTS);
+                    let _ = pool.subscribe_producer_to_event("producer-0", &event);
+                    let _ = pool.unsubscribe_producer_from_event("producer-0", &event);
+                    changes += 2;
+                }
+                changes
+            })
+        });
+
+        let started = Instant::now();
+        let routers = (0..ROUTERS).map(|router| {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                let mut deliveries = 0;
+                for n in 0..MESSAGES_PER_ROUTER {
+                    let event = format!("event-{}", (router + n) % EVENTS);
+                    deliveries += pool.forward_to_event(&event, "payload").len();
+                }
+                deliveries
+            })
+        }).collect::<Vec<_>>();
+        let deliveries: usize = routers.into_iter().map(|router| router.join().unwrap()).sum();
+        let elapsed = started.elapsed().as_secs_f64();
+
+        stop.store(true, Ordering::Relaxed);
+        let changes = churner.map(|churner| churner.join().unwrap()).unwrap_or(0);
+        println!(
+            "churn {:5}: {:>10.0} messages/s, {:>12.0} deliveries/s, {} subscription changes",
+            churn,
+            (ROUTERS * MESSAGES_PER_ROUTER) as f64 / elapsed,
+            deliveries as f64 / elapsed,
+            changes,
+        );
+    }
+}
@@ -0,0 +1,809 @@
+//! Server configuration file support
+//!
+//! The whole server can be described by one JSON file passed with
+//! `--config <path>` or the `TRANSPORT_CONFIG` environment variable.
+//! Environment variables override values from the file, and the result
+//! is validated as a whole so every problem is reported at once.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::reload::DEFAULT_RELOAD_INTERVAL;
+
+use crate::transport::server_config::{ListenerConfig, ProtocolType, PRIMARY_LISTENER_NAME};
+use crate::transport::push;
+use crate::transport::shutdown::DEFAULT_SHUTDOWN_TIMEOUT;
+use crate::transport::{
+    ClientFactory,
+    DedupRule,
+    DestinationPolicy,
+    ProducerUri,
+    RateLimit,
+    RateLimitAction,
+    RegistrationAuth,
+    ServerConfig,
+    TokenScope,
+};
+
+/// Environment variable holding the configuration file path
+pub const CONFIG_ENV: &str = "TRANSPORT_CONFIG";
+
+/// Prefix of variables declaring producers: `PRODUCER_<ID>=<uri>`
+pub const PRODUCER_ENV_PREFIX: &str = "PRODUCER_";
+
+/// Configuration file layout
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub listeners: ListenersSection,
+    pub producers: Vec<ProducerSection>,
+    /// Event name -> ids of producers subscribed to it
+    pub routes: BTreeMap<String, Vec<String>>,
+    pub limits: LimitsSection,
+    /// Event name -> duplicate suppression settings
+    pub dedup: BTreeMap<String, DedupSection>,
+    pub rate_limits: RateLimitsSection,
+    pub logging: LoggingSection,
+    pub security: SecuritySection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenersSection {
+    pub ingress: IngressSection,
+    /// Further ingress listeners feeding the same routing pipeline
+    pub additional_ingress: Vec<AdditionalIngressSection>,
+    pub registration: RegistrationSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngressSection {
+    pub protocol: Option<String>,
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdditionalIngressSection {
+    pub name: String,
+    #[serde(default)]
+    pub protocol: Option<String>,
+    pub address: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistrationSection {
+    pub enabled: Option<bool>,
+    pub address: Option<String>,
+    pub loopback_only: Option<bool>,
+    pub shared_with_ingress: Option<bool>,
+}
+
+/// Static producer declared in the configuration file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProducerSection {
+    pub id: String,
+    pub uri: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSection {
+    /// Default send timeout for producers without their own `timeout_ms`
+    pub producer_timeout_ms: Option<u64>,
+    pub lease_reaper_interval_ms: Option<u64>,
+    /// How often the configuration file is checked for changes
+    pub config_reload_interval_ms: Option<u64>,
+    /// How long shutdown waits for in-flight messages
+    pub shutdown_timeout_ms: Option<u64>,
+    /// How long a request waits for its reply
+    pub request_timeout_ms: Option<u64>,
+    /// Requests that may wait for their reply at once
+    pub max_pending_requests: Option<usize>,
+    /// Event name -> TTL of messages that don't set their own
+    pub event_ttl_ms: BTreeMap<String, u64>,
+    /// Concurrent connections accepted per listener
+    pub max_connections: Option<usize>,
+    /// How long an ingress connection may wait between messages
+    pub idle_timeout_ms: Option<u64>,
+    /// How long one message may take to arrive once it has started
+    pub read_timeout_ms: Option<u64>,
+    /// Longest ingress message, in bytes including the newline
+    pub max_message_bytes: Option<usize>,
+    /// Threads reading from all connections
+    pub worker_threads: Option<usize>,
+    /// Threads routing received messages
+    pub message_threads: Option<usize>,
+    /// Threads delivering messages to event subscribers
+    pub fanout_threads: Option<usize>,
+    /// How long delivering a message to all subscribers of its event may take
+    pub fanout_deadline_ms: Option<u64>,
+    /// Messages that may wait for their delivery time at once
+    pub max_scheduled_messages: Option<usize>,
+    /// Longest delay a message may be scheduled for
+    pub max_schedule_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DedupSection {
+    /// How long a message key is remembered
+    pub window_ms: u64,
+    /// Header holding the key instead of the message id
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitsSection {
+    /// What happens to messages over a limit: `nack`, `drop` or `delay`
+    pub action: Option<String>,
+    /// Longest wait the `delay` action holds a message for
+    pub max_delay_ms: Option<u64>,
+    pub per_peer: Option<RateLimitSection>,
+    pub per_identity: Option<RateLimitSection>,
+    /// Event name (or `*` for the rest) -> limit shared by all publishers
+    pub per_event: BTreeMap<String, RateLimitSection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSection {
+    pub per_second: f64,
+    /// Messages allowed at once; defaults to one second's worth
+    #[serde(default)]
+    pub burst: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSection {
+    /// Log every received and forwarded message
+    pub log_messages: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecuritySection {
+    /// Registration token -> producer-id prefixes it may manage
+    pub registration_tokens: BTreeMap<String, Vec<String>>,
+    pub destination_allow: Vec<String>,
+    pub destination_deny: Vec<String>,
+}
+
+/// Static producer after validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerSpec {
+    pub id: String,
+    pub uri: String,
+    pub events: Vec<String>,
+    pub timeout: Option<Duration>,
+}
+
+/// Fully resolved server configuration
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub producers: Vec<ProducerSpec>,
+    pub destination_policy: DestinationPolicy,
+    pub reload_interval: Duration,
+    pub shutdown_timeout: Duration,
+}
+
+/// Configuration errors collected while loading and validating
+#[derive(Debug)]
+pub struct ConfigError {
+    source: String,
+    errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration ({}):", self.source)?;
+        for error in &self.errors {
+            write!(f, "\n  • {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl FileConfig {
+    /// Read and parse a configuration file
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let source = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|e| ConfigError {
+            source: source.clone(),
+            errors: vec![format!("cannot read file: {}", e)],
+        })?;
+
+        Self::parse(&text).map_err(|e| ConfigError { source, errors: vec![e] })
+    }
+
+    /// Parse configuration from JSON text
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+impl AppConfig {
+    /// Validate a file configuration and apply environment overrides
+    ///
+    /// `vars` holds the environment variables; it is injected so the
+    /// override rules can be tested without touching the process environment.
+    pub fn resolve(
+        file: FileConfig,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<Self, Vec<String>> {
+        let env = |name: &str| vars.get(name).cloned();
+        let mut errors = Vec::new();
+        let mut server = ServerConfig::default();
+
+        // Ingress listener
+        if let Some(protocol) = &file.listeners.ingress.protocol {
+            match ProtocolType::from_str(protocol) {
+                Some(protocol) => server.protocol = protocol,
+                None => errors.push(format!(
+                    "listeners.ingress.protocol: unsupported protocol '{}'", protocol
+                )),
+            }
+        }
+        if let Some(address) = env("TRANSPORT_ADDRESS").or(file.listeners.ingress.address) {
+            check_address("listeners.ingress.address", &address, &mut errors);
+            server.address = address;
+        }
+
+        // Additional ingress listeners
+        let mut names = HashSet::from([PRIMARY_LISTENER_NAME.to_string()]);
+        for (index, section) in file.listeners.additional_ingress.iter().enumerate() {
+            let field = format!("listeners.additional_ingress[{}]", index);
+            if section.name.trim().is_empty() {
+                errors.push(format!("{}.name: must not be empty", field));
+            } else if !names.insert(section.name.clone()) {
+                errors.push(format!("{}.name: duplicate listener name '{}'", field, section.name));
+            }
+            check_address(&format!("{}.address", field), &section.address, &mut errors);
+
+            let protocol = match section.protocol.as_deref().map(ProtocolType::from_str) {
+                None => ProtocolType::default(),
+                Some(Some(protocol)) => protocol,
+                Some(None) => {
+                    errors.push(format!(
+                        "{}.protocol: unsupported protocol '{}'", field, section.protocol.as_deref().unwrap_or_default()
+                    ));
+                    continue;
+                }
+            };
+            server.add_listener(ListenerConfig::new(&section.name, protocol, section.address.clone()));
+        }
+
+        let mut addresses = HashSet::new();
+        for listener in server.listeners() {
+            if !addresses.insert(listener.address.clone()) {
+                errors.push(format!(
+                    "listeners: '{}' uses address {} which is already taken by another listener",
+                    listener.name, listener.address
+                ));
+            }
+        }
+
+        // Registration listener
+        let section = &file.listeners.registration;
+        let registration = &mut server.registration;
+        if let Some(address) = env("REGISTRATION_ADDRESS").or(section.address.clone()) {
+            check_address("listeners.registration.address", &address, &mut errors);
+            registration.address = address;
+        }
+        let flags = [
+            ("REGISTRATION_ENABLED", section.enabled, &mut registration.enabled),
+            ("REGISTRATION_LOOPBACK_ONLY", section.loopback_only, &mut registration.loopback_only),
+            ("REGISTRATION_SHARED_PORT", section.shared_with_ingress, &mut registration.shared_with_ingress),
+        ];
+        for (name, file_value, target) in flags {
+            if let Some(value) = env_flag(&env, name, &mut errors).or(file_value) {
+                *target = value;
+            }
+        }
+
+        // Registration tokens
+        match env(crate::transport::registration_auth::REGISTRATION_TOKENS_ENV) {
+            Some(spec) => match RegistrationAuth::parse(&spec) {
+                Ok(auth) => registration.auth = auth,
+                Err(e) => errors.push(format!("REGISTRATION_TOKENS: {}", e)),
+            },
+            None => {
+                let mut tokens = Vec::new();
+                for (token, prefixes) in &file.security.registration_tokens {
+                    if token.is_empty() || prefixes.is_empty() {
+                        errors.push(format!(
+                            "security.registration_tokens: token '{}' needs a non-empty token and at least one prefix",
+                            token
+                        ));
+                    }
+                    tokens.push((token.clone(), TokenScope::new(prefixes.clone())));
+                }
+                registration.auth = RegistrationAuth::with_tokens(tokens);
+            }
+        }
+
+        // Destination policy
+        let allow = env(crate::transport::destination_policy::DESTINATION_ALLOW_ENV).unwrap_or_else(|| file.security.destination_allow.join(","));
+        let deny = env(crate::transport::destination_policy::DESTINATION_DENY_ENV).unwrap_or_else(|| file.security.destination_deny.join(","));
+        let destination_policy = DestinationPolicy::parse(&allow, &deny).unwrap_or_else(|e| {
+            errors.push(format!("security.destination_allow/deny: {}", e));
+            DestinationPolicy::allow_all()
+        });
+
+        // Limits and logging
+        let producer_timeout = positive_millis(
+            "limits.producer_timeout_ms", file.limits.producer_timeout_ms, &mut errors,
+        );
+        if let Some(interval) = positive_millis(
+            "limits.lease_reaper_interval_ms", file.limits.lease_reaper_interval_ms, &mut errors,
+        ) {
+            server.lease_reaper_interval = interval;
+        }
+        if let Some(timeout) = positive_millis(
+            "limits.request_timeout_ms", file.limits.request_timeout_ms, &mut errors,
+        ) {
+            server.request_timeout = timeout;
+        }
+        match file.limits.max_pending_requests {
+            Some(0) => errors.push("limits.max_pending_requests: must be greater than 0".to_string()),
+            Some(max) => server.max_pending_requests = max,
+            None => {}
+        }
+        let connection_limits = &mut server.connection_limits;
+        connection_limits.idle_timeout = positive_millis(
+            "limits.idle_timeout_ms", file.limits.idle_timeout_ms, &mut errors,
+        );
+        connection_limits.read_timeout = positive_millis(
+            "limits.read_timeout_ms", file.limits.read_timeout_ms, &mut errors,
+        );
+        match file.limits.max_connections {
+            Some(0) => errors.push("limits.max_connections: must be greater than 0".to_string()),
+            Some(max) => connection_limits.max_connections = max,
+            None => {}
+        }
+        match file.limits.worker_threads {
+            Some(0) => errors.push("limits.worker_threads: must be greater than 0".to_string()),
+            Some(workers) => server.worker_threads = workers,
+            None => {}
+        }
+        match file.limits.message_threads {
+            Some(0) => errors.push("limits.message_threads: must be greater than 0".to_string()),
+            Some(threads) => server.message_threads = threads,
+            None => {}
+        }
+        match file.limits.fanout_threads {
+            Some(0) => errors.push("limits.fanout_threads: must be greater than 0".to_string()),
+            Some(threads) => server.fanout.threads = threads,
+            None => {}
+        }
+        if let Some(deadline) = positive_millis(
+            "limits.fanout_deadline_ms", file.limits.fanout_deadline_ms, &mut errors,
+        ) {
+            server.fanout.deadline = deadline;
+        }
+        match file.limits.max_scheduled_messages {
+            Some(0) => errors.push("limits.max_scheduled_messages: must be greater than 0".to_string()),
+            Some(max) => server.scheduler.max_messages = max,
+            None => {}
+        }
+        if let Some(max_delay) = positive_millis(
+            "limits.max_schedule_delay_ms", file.limits.max_schedule_delay_ms, &mut errors,
+        ) {
+            server.scheduler.max_delay = max_delay;
+        }
+        match file.limits.max_message_bytes {
+            Some(0) => errors.push("limits.max_message_bytes: must be greater than 0".to_string()),
+            Some(max) => connection_limits.max_frame_size = max,
+            None => {}
+        }
+        for (event, ttl_ms) in &file.limits.event_ttl_ms {
+            if let Some(ttl) = positive_millis(&format!("limits.event_ttl_ms.{}", event), Some(*ttl_ms), &mut errors) {
+                server.event_ttls.insert(event.clone(), ttl);
+            }
+        }
+        let reload_interval = positive_millis(
+            "limits.config_reload_interval_ms", file.limits.config_reload_interval_ms, &mut errors,
+        ).unwrap_or(DEFAULT_RELOAD_INTERVAL);
+        let shutdown_timeout = positive_millis(
+            "limits.shutdown_timeout_ms", file.limits.shutdown_timeout_ms, &mut errors,
+        ).unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        for (event, dedup) in &file.dedup {
+            let field = format!("dedup.{}", event);
+            let window = positive_millis(&format!("{}.window_ms", field), Some(dedup.window_ms), &mut errors);
+            if dedup.header.as_deref().is_some_and(|header| header.trim().is_empty()) {
+                errors.push(format!("{}.header: must not be empty", field));
+            }
+            if let Some(window) = window {
+                server.dedup.insert(event.clone(), DedupRule { window, header: dedup.header.clone() });
+            }
+        }
+        if let Some(action) = &file.rate_limits.action {
+            match RateLimitAction::from_str(action) {
+                Some(action) => server.rate_limits.action = action,
+                None => errors.push(format!("rate_limits.action: expected nack, drop or delay, got '{}'", action)),
+            }
+        }
+        if let Some(max_delay) = positive_millis(
+            "rate_limits.max_delay_ms", file.rate_limits.max_delay_ms, &mut errors,
+        ) {
+            server.rate_limits.max_delay = max_delay;
+        }
+        server.rate_limits.per_peer = file.rate_limits.per_peer.as_ref()
+            .and_then(|limit| rate_limit("rate_limits.per_peer", limit, &mut errors));
+        server.rate_limits.per_identity = file.rate_limits.per_identity.as_ref()
+            .and_then(|limit| rate_limit("rate_limits.per_identity", limit, &mut errors));
+        for (event, limit) in &file.rate_limits.per_event {
+            if let Some(limit) = rate_limit(&format!("rate_limits.per_event.{}", event), limit, &mut errors) {
+                server.rate_limits.per_event.insert(event.clone(), limit);
+            }
+        }
+        if let Some(log_messages) = file.logging.log_messages {
+            server.log_messages = log_messages;
+        }
+
+        // Static producers
+        let mut producers: Vec<ProducerSpec> = Vec::new();
+        let mut ids = HashSet::new();
+        for (index, producer) in file.producers.into_iter().enumerate() {
+            let field = format!("producers[{}]", index);
+            if producer.id.trim().is_empty() {
+                errors.push(format!("{}.id: must not be empty", field));
+            } else if !ids.insert(producer.id.clone()) {
+                errors.push(format!("{}.id: duplicate producer id '{}'", field, producer.id));
+            }
+            let timeout = positive_millis(&format!("{}.timeout_ms", field), producer.timeout_ms, &mut errors);
+
+            if let Some(spec) = producer_spec(&format!("{}.uri", field), producer.id, producer.uri, producer.events, timeout, &mut errors) {
+                producers.push(ProducerSpec { timeout: spec.timeout.or(producer_timeout), ..spec });
+            }
+        }
+
+        // PRODUCER_<ID> variables replace file producers with the same id
+        for (name, uri) in vars {
+            let Some(id) = name.strip_prefix(PRODUCER_ENV_PREFIX).map(producer_id_from_env) else {
+                continue;
+            };
+            if id.is_empty() {
+                errors.push(format!("{}: missing producer id after {}", name, PRODUCER_ENV_PREFIX));
+                continue;
+            }
+
+            if let Some(spec) = producer_spec(name, id, uri.clone(), Vec::new(), None, &mut errors) {
+                let spec = ProducerSpec { timeout: spec.timeout.or(producer_timeout), ..spec };
+                match producers.iter_mut().find(|producer| producer.id == spec.id) {
+                    Some(existing) => *existing = spec,
+                    None => producers.push(spec),
+                }
+            }
+        }
+
+        // Routes add subscriptions to the declared producers
+        for (event, producer_ids) in file.routes {
+            for id in producer_ids {
+                match producers.iter_mut().find(|producer| producer.id == id) {
+                    Some(producer) if !producer.events.contains(&event) => producer.events.push(event.clone()),
+                    Some(_) => {}
+                    None => errors.push(format!("routes.{}: unknown producer '{}'", event, id)),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Self {
+                server,
+                producers,
+                destination_policy,
+                reload_interval,
+                shutdown_timeout,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Get the configuration file path from `--config <path>` or `TRANSPORT_CONFIG`
+pub fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    std::env::var(CONFIG_ENV).ok().map(PathBuf::from)
+}
+
+/// Load the configuration file (if any), apply environment overrides and validate
+pub fn load(path: Option<&Path>) -> Result<AppConfig, ConfigError> {
+    let (file, source) = match path {
+        Some(path) => (FileConfig::load(path)?, path.display().to_string()),
+        None => (FileConfig::default(), "defaults + environment".to_string()),
+    };
+
+    AppConfig::resolve(file, &std::env::vars().collect())
+        .map_err(|errors| ConfigError { source, errors })
+}
+
+/// Derive a producer id from the part of a variable name after `PRODUCER_`
+///
+/// `PRODUCER_AUDIT_LOG` registers the producer `audit-log`.
+fn producer_id_from_env(suffix: &str) -> String {
+    suffix.to_lowercase().replace('_', "-")
+}
+
+/// Validate a producer URI and merge its `events`/`timeout` options
+///
+/// Explicit events come first; an explicit timeout wins over the URI's.
+fn producer_spec(
+    field: &str,
+    id: String,
+    uri: String,
+    mut events: Vec<String>,
+    timeout: Option<Duration>,
+    errors: &mut Vec<String>,
+) -> Option<ProducerSpec> {
+    if let Err(e) = push::check_not_reserved(&id) {
+        errors.push(format!("{}: {}", field, e));
+        return None;
+    }
+    let parsed = ProducerUri::parse(&uri)
+        .and_then(|parsed| ClientFactory::create_from_producer_uri(&parsed).map(|_| parsed));
+    let parsed = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            errors.push(format!("{}: {}", field, e));
+            return None;
+        }
+    };
+
+    for event in parsed.events {
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+
+    Some(ProducerSpec { id, uri, events, timeout: timeout.or(parsed.timeout) })
+}
+
+fn check_address(field: &str, address: &str, errors: &mut Vec<String>) {
+    let valid = address.rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if !valid {
+        errors.push(format!("{}: expected host:port, got '{}'", field, address));
+    }
+}
+
+fn env_flag(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    errors: &mut Vec<String>,
+) -> Option<bool> {
+    let value = env(name)?;
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => {
+            errors.push(format!("{}: expected true or false, got '{}'", name, value));
+            None
+        }
+    }
+}
+
+fn positive_millis(field: &str, value: Option<u64>, errors: &mut Vec<String>) -> Option<Duration> {
+    match value {
+        Some(0) => {
+            errors.push(format!("{}: must be greater than 0", field));
+            None
+        }
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None => None,
+    }
+}
+
+fn rate_limit(field: &str, section: &RateLimitSection, errors: &mut Vec<String>) -> Option<RateLimit> {
+    let burst = section.burst.unwrap_or(section.per_second.max(1.0));
+    if !section.per_second.is_finite() || section.per_second <= 0.0 {
+        errors.push(format!("{}.per_second: must be greater than 0", field));
+        return None;
+    }
+    if !burst.is_finite() || burst < 1.0 {
+        errors.push(format!("{}.burst: must be at least 1", field));
+        return None;
+    }
+    Some(RateLimit { per_second: section.per_second, burst })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(json: &str, env: &[(&str, &str)]) -> Result<AppConfig, Vec<String>> {
+        let env: BTreeMap<String, String> = env.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        AppConfig::resolve(FileConfig::parse(json).unwrap(), &env)
+    }
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = resolve("{}", &[]).unwrap();
+        assert_eq!(config.server.address, "0.0.0.0:49152");
+        assert!(config.server.registration.enabled);
+        assert!(config.producers.is_empty());
+        assert!(!config.destination_policy.is_restricted());
+    }
+
+    #[test]
+    fn test_full_config() {
+        let config = resolve(r#"{
+            "listeners": {
+                "ingress": { "protocol": "tcp", "address": "0.0.0.0:50000" },
+                "registration": { "address": "0.0.0.0:50001", "loopback_only": true }
+            },
+            "producers": [
+                { "id": "audit", "uri": "tcp://127.0.0.1:9000", "events": ["user_login"], "timeout_ms": 2000 },
+                { "id": "metrics", "uri": "tcp://127.0.0.1:9001" }
+            ],
+            "routes": { "user_login": ["metrics"], "system_log": ["audit", "metrics"] },
+            "limits": { "producer_timeout_ms": 3000, "lease_reaper_interval_ms": 500, "config_reload_interval_ms": 250,
+                        "shutdown_timeout_ms": 1500, "request_timeout_ms": 800, "max_pending_requests": 50,
+                        "event_ttl_ms": { "alerts": 30000 }, "max_connections": 64, "idle_timeout_ms": 60000,
+                        "read_timeout_ms": 5000, "max_message_bytes": 65536, "worker_threads": 4, "message_threads": 12,
+                        "fanout_threads": 8, "fanout_deadline_ms": 2500,
+                        "max_scheduled_messages": 500, "max_schedule_delay_ms": 3600000 },
+            "dedup": { "payments": { "window_ms": 60000 }, "orders": { "window_ms": 5000, "header": "idempotency-key" } },
+            "rate_limits": { "action": "delay", "max_delay_ms": 750, "per_peer": { "per_second": 100, "burst": 200 },
+                             "per_event": { "*": { "per_second": 0.5 } } },
+            "logging": { "log_messages": false },
+            "security": {
+                "registration_tokens": { "secret": ["team-a-"] },
+                "destination_deny": ["169.254.0.0/16"]
+            }
+        }"#, &[]).unwrap();
+
+        assert_eq!(config.server.address, "0.0.0.0:50000");
+        assert_eq!(config.server.registration.bind_address(), "127.0.0.1:50001");
+        assert!(config.server.registration.auth.is_enabled());
+        assert_eq!(config.server.lease_reaper_interval, Duration::from_millis(500));
+        assert_eq!(config.reload_interval, Duration::from_millis(250));
+        assert_eq!(config.shutdown_timeout, Duration::from_millis(1500));
+        assert_eq!(config.server.request_timeout, Duration::from_millis(800));
+        assert_eq!(config.server.max_pending_requests, 50);
+        assert_eq!(config.server.event_ttls["alerts"], Duration::from_secs(30));
+        assert_eq!(config.server.connection_limits, crate::transport::server_config::ConnectionLimits {
+            max_connections: 64,
+            idle_timeout: Some(Duration::from_secs(60)),
+            read_timeout: Some(Duration::from_secs(5)),
+            max_frame_size: 65536,
+        });
+        assert_eq!(config.server.worker_threads, 4);
+        assert_eq!(config.server.message_threads, 12);
+        assert_eq!(config.server.fanout.threads, 8);
+        assert_eq!(config.server.fanout.deadline, Duration::from_millis(2500));
+        assert_eq!(config.server.scheduler.max_messages, 500);
+        assert_eq!(config.server.scheduler.max_delay, Duration::from_secs(3600));
+        assert_eq!(config.server.dedup["payments"], DedupRule { window: Duration::from_secs(60), header: None });
+        assert_eq!(config.server.dedup["orders"].header.as_deref(), Some("idempotency-key"));
+        assert_eq!(config.server.rate_limits.action, RateLimitAction::Delay);
+        assert_eq!(config.server.rate_limits.max_delay, Duration::from_millis(750));
+        assert_eq!(config.server.rate_limits.per_peer, Some(RateLimit { per_second: 100.0, burst: 200.0 }));
+        assert_eq!(config.server.rate_limits.per_identity, None);
+        assert_eq!(config.server.rate_limits.per_event["*"], RateLimit { per_second: 0.5, burst: 1.0 });
+        assert!(!config.server.log_messages);
+        assert!(config.destination_policy.is_restricted());
+
+        assert_eq!(config.producers[0].events, vec!["user_login", "system_log"]);
+        assert_eq!(config.producers[0].timeout, Some(Duration::from_millis(2000)));
+        // Routes are applied in event-name order
+        assert_eq!(config.producers[1].events, vec!["system_log", "user_login"]);
+        assert_eq!(config.producers[1].timeout, Some(Duration::from_millis(3000)));
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let config = resolve(
+            r#"{ "listeners": { "ingress": { "address": "0.0.0.0:50000" },
+                                "registration": { "enabled": true } } }"#,
+            &[("TRANSPORT_ADDRESS", "127.0.0.1:60000"), ("REGISTRATION_ENABLED", "false")],
+        ).unwrap();
+        assert_eq!(config.server.address, "127.0.0.1:60000");
+        assert!(!config.server.registration.enabled);
+    }
+
+    #[test]
+    fn test_additional_ingress_listeners() {
+        let config = resolve(r#"{ "listeners": {
+            "ingress": { "address": "0.0.0.0:50000" },
+            "additional_ingress": [{ "name": "local", "address": "127.0.0.1:50010" }]
+        } }"#, &[]).unwrap();
+
+        let listeners = config.server.listeners();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].name, PRIMARY_LISTENER_NAME);
+        assert_eq!(listeners[1].name, "local");
+        assert_eq!(listeners[1].address, "127.0.0.1:50010");
+
+        let errors = resolve(r#"{ "listeners": {
+            "ingress": { "address": "0.0.0.0:50000" },
+            "additional_ingress": [
+                { "name": "ingress", "address": "127.0.0.1:50010" },
+                { "name": "copy", "address": "0.0.0.0:50000", "protocol": "udp" }
+            ]
+        } }"#, &[]).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        assert!(errors.iter().any(|e| e.contains("duplicate listener name 'ingress'")));
+        assert!(errors.iter().any(|e| e.contains("unsupported protocol 'udp'")));
+    }
+
+    #[test]
+    fn test_env_producers() {
+        let config = resolve(
+            r#"{ "producers": [{ "id": "audit-log", "uri": "tcp://127.0.0.1:9000", "events": ["a"] }],
+                 "limits": { "producer_timeout_ms": 1000 } }"#,
+            &[
+                ("PRODUCER_AUDIT_LOG", "tcp://127.0.0.1:9100?events=b,c&timeout=3s"),
+                ("PRODUCER_METRICS", "tcp://127.0.0.1:9200"),
+            ],
+        ).unwrap();
+
+        assert_eq!(config.producers.len(), 2);
+        assert_eq!(config.producers[0].id, "audit-log");
+        assert_eq!(config.producers[0].events, vec!["b", "c"]);
+        assert_eq!(config.producers[0].timeout, Some(Duration::from_secs(3)));
+        assert_eq!(config.producers[1].id, "metrics");
+        assert_eq!(config.producers[1].timeout, Some(Duration::from_millis(1000)));
+
+        let errors = resolve("{}", &[("PRODUCER_BAD", "127.0.0.1:9000"), ("PRODUCER_", "tcp://h:1")]).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("PRODUCER_BAD: Invalid URI format")));
+    }
+
+    #[test]
+    fn test_reports_all_errors() {
+        let errors = resolve(r#"{
+            "listeners": { "ingress": { "protocol": "udp", "address": "nowhere" } },
+            "producers": [
+                { "id": "a", "uri": "tcp://127.0.0.1:9000" },
+                { "id": "a", "uri": "127.0.0.1:9001", "timeout_ms": 0 },
+                { "id": "push-a", "uri": "tcp://127.0.0.1:9002" }
+            ],
+            "routes": { "e": ["missing"] },
+            "dedup": { "payments": { "window_ms": 0, "header": " " } },
+            "rate_limits": { "action": "throttle", "max_delay_ms": 0, "per_identity": { "per_second": 10, "burst": 0.5 } },
+            "security": { "destination_deny": ["10.0.0.0/99"] }
+        }"#, &[("REGISTRATION_SHARED_PORT", "maybe")]).unwrap_err();
+
+        assert_eq!(errors.len(), 14, "{:#?}", errors);
+        assert!(errors.iter().any(|e| e.contains("duplicate producer id 'a'")));
+        assert!(errors.iter().any(|e| e.contains("reserved for push subscribers")));
+        assert!(errors.iter().any(|e| e.contains("routes.e: unknown producer 'missing'")));
+        assert!(errors.iter().any(|e| e.starts_with("REGISTRATION_SHARED_PORT")));
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let error = FileConfig::parse(r#"{ "listners": {} }"#).unwrap_err();
+        assert!(error.contains("unknown field `listners`"), "{}", error);
+    }
+}
@@ -0,0 +1,102 @@
+//! Run modes and graceful shutdown
+//!
+//! In interactive mode the server reads commands from stdin. In headless mode
+//! (no terminal on stdin, `--headless` or `TRANSPORT_HEADLESS=true`) it ignores
+//! stdin and runs until SIGTERM or SIGINT. Either way shutdown stops the
+//! listeners and waits for in-flight messages before exiting.
+
+use std::io::{stdin, BufRead, IsTerminal};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::transport::shutdown;
+
+/// Environment variable forcing headless mode
+pub const HEADLESS_ENV: &str = "TRANSPORT_HEADLESS";
+
+/// Exit status after a clean shutdown
+pub const EXIT_OK: i32 = 0;
+/// Exit status when startup fails (bad configuration, port in use)
+pub const EXIT_STARTUP_FAILED: i32 = 1;
+/// Exit status when in-flight messages did not drain before the deadline
+pub const EXIT_DRAIN_TIMEOUT: i32 = 2;
+
+/// How the main thread waits for shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// Read quit commands from stdin; EOF also stops the server
+    Interactive,
+    /// Wait for SIGTERM/SIGINT only
+    Headless,
+}
+
+impl RunMode {
+    /// Pick the run mode from `--headless`, `TRANSPORT_HEADLESS` or whether stdin is a terminal
+    pub fn detect() -> Self {
+        let flag = std::env::args().skip(1).any(|arg| arg == "--headless");
+        let env = std::env::var(HEADLESS_ENV)
+            .is_ok_and(|value| matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"));
+
+        if flag || env || !stdin().is_terminal() {
+            RunMode::Headless
+        } else {
+            RunMode::Interactive
+        }
+    }
+}
+
+/// Block until a termination signal arrives or, in interactive mode, a quit command is typed
+pub fn wait_for_shutdown(mode: RunMode, quit_commands: &[&str]) {
+    let (sender, receiver) = mpsc::channel::<()>();
+
+    let signal_sender = sender.clone();
+    if let Err(e) = ctrlc::set_handler(move || {
+        // A second signal while draining skips the wait
+        if shutdown::is_shutting_down() {
+            eprintln!("Forced shutdown");
+            std::process::exit(EXIT_DRAIN_TIMEOUT);
+        }
+        let _ = signal_sender.send(());
+    }) {
+        eprintln!("Warning: failed to install signal handler: {}", e);
+    }
+
+    if mode == RunMode::Interactive {
+        let quit_commands: Vec<String> = quit_commands.iter().map(|command| command.to_string()).collect();
+        thread::spawn(move || {
+            for line in stdin().lock().lines() {
+                match line {
+                    Ok(input) => {
+                        let input = input.trim();
+                        if quit_commands.iter().any(|command| input.eq_ignore_ascii_case(command)) {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error reading input: {}", e);
+                        break;
+                    }
+                }
+            }
+            let _ = sender.send(());
+        });
+    }
+
+    let _ = receiver.recv();
+}
+
+/// Stop accepting connections and drain in-flight messages, returning the exit status
+pub fn shutdown(timeout: Duration) -> i32 {
+    shutdown::begin_shutdown();
+    println!("Stopped accepting connections, draining {} in-flight message(s) (deadline {:?})",
+             shutdown::in_flight(), timeout);
+
+    if shutdown::wait_for_drain(timeout) {
+        println!("Shutdown complete");
+        EXIT_OK
+    } else {
+        eprintln!("Shutdown deadline exceeded with {} message(s) still in flight", shutdown::in_flight());
+        EXIT_DRAIN_TIMEOUT
+    }
+}
@@ -1,21 +1,51 @@
 use crate::transport;
-use std::env;
 
-pub fn boot() {
-    // Allow overriding the default address via environment variable
-    let address = env::var("TRANSPORT_ADDRESS")
-        .unwrap_or_else(|_| "0.0.0.0:49152".to_string());
-    
+pub mod config;
+pub mod daemon;
+pub mod reload;
+
+/// Load the configuration and start the server, returning the resolved configuration
+pub fn boot() -> config::AppConfig {
     println!("Initializing transport server...");
-    println!("Address: {} (override with TRANSPORT_ADDRESS env var)", address);
     
-    // Example: Add producers to forward messages to external consumers
-    // You can configure these via environment variables or configuration
-    setup_producers();
+    // Load the configuration file (if any); environment variables override it
+    let config_path = config::config_path();
+    let app_config = match config::load(config_path.as_deref()) {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(daemon::EXIT_STARTUP_FAILED);
+        }
+    };
+    
+    match &config_path {
+        Some(path) => println!("Configuration: {}", path.display()),
+        None => println!("Configuration: defaults (pass --config <file> or set {})", config::CONFIG_ENV),
+    }
+    println!("Address: {} (override with TRANSPORT_ADDRESS env var)", app_config.server.address);
+    
+    transport::set_destination_policy(app_config.destination_policy.clone());
+    
+    // Add producers to forward messages to external consumers
+    setup_producers(&app_config.producers);
     
-    match transport::bind_transport_with_address(address) {
-        Ok(_) => {
-            println!("Transport server started successfully!");
+    // Apply edits to the configuration file without restarting
+    if let Some(path) = config_path {
+        println!("Watching {} for changes (every {:?})", path.display(), app_config.reload_interval);
+        reload::start_config_watcher(
+            path,
+            app_config.clone(),
+            transport::get_producer_pool(),
+            app_config.reload_interval,
+        );
+    }
+    
+    let config = app_config.server.clone();
+    
+    match transport::bind_transport_with_config(config) {
+        Ok(handle) => {
+            println!("Transport server started successfully on {}!", handle.local_addr());
+            transport::set_running_server(handle);
         }
         Err(e) => {
             eprintln!("Failed to bind transport: {}", e);
@@ -25,37 +55,32 @@ pub fn boot() {
             eprintln!("  • Run as administrator if needed");
             eprintln!("  • Check Windows Firewall rules");
             eprintln!("  • Add exception in Windows Defender/Antivirus");
-            std::process::exit(1);
+            std::process::exit(daemon::EXIT_STARTUP_FAILED);
         }
     }
+    
+    app_config
 }
 
 /// Setup producers for forwarding messages to external consumers
-fn setup_producers() {
-    // Example: Add producers from environment variables
-    // Format: PRODUCER_<ID>=<protocol>://<address>
-    // Example: PRODUCER_CONSUMER1=tcp://127.0.0.1:9000
-    
-    // Check for producer configurations
+fn setup_producers(producers: &[config::ProducerSpec]) {
     let mut producer_count = 0;
     
-    // Example producer 1 (if configured)
-    if let Ok(addr) = env::var("PRODUCER_CONSUMER1") {
-        if let Err(e) = transport::add_tcp_producer("consumer-1".to_string(), addr.clone()) {
-            eprintln!("Warning: Failed to add producer consumer-1: {}", e);
-        } else {
-            println!("✓ Added producer 'consumer-1' -> {}", addr);
-            producer_count += 1;
-        }
-    }
-    
-    // Example producer 2 (if configured)
-    if let Ok(addr) = env::var("PRODUCER_CONSUMER2") {
-        if let Err(e) = transport::add_tcp_producer("consumer-2".to_string(), addr.clone()) {
-            eprintln!("Warning: Failed to add producer consumer-2: {}", e);
-        } else {
-            println!("✓ Added producer 'consumer-2' -> {}", addr);
-            producer_count += 1;
+    // Producers declared in the configuration file or PRODUCER_<ID> variables
+    let pool = transport::get_producer_pool();
+    for spec in producers {
+        let result = transport::ClientFactory::create_from_uri_with_timeout(&spec.uri, spec.timeout)
+            .and_then(|client| {
+                let events: Vec<&str> = spec.events.iter().map(String::as_str).collect();
+                pool.add_producer_with_events(spec.id.clone(), client, &events)
+            });
+        
+        match result {
+            Ok(()) => {
+                println!("✓ Added producer '{}' -> {} (events: {:?})", spec.id, spec.uri, spec.events);
+                producer_count += 1;
+            }
+            Err(e) => eprintln!("Warning: Failed to add producer {}: {}", spec.id, e),
         }
     }
     
@@ -63,8 +88,8 @@ fn setup_producers() {
         println!("Configured {} producer(s) for message forwarding", producer_count);
     } else {
         println!("No producers configured. Messages will only be processed locally.");
-        println!("To add producers, set environment variables:");
+        println!("To add producers, declare them in the config file or set environment variables:");
         println!("  PRODUCER_CONSUMER1=tcp://127.0.0.1:9000");
-        println!("  PRODUCER_CONSUMER2=tcp://127.0.0.1:9001");
+        println!("  PRODUCER_AUDIT_LOG=tcp://127.0.0.1:9001?events=user_login,system_log&timeout=3s");
     }
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+//! Hot reload of the configuration file
+//!
+//! The file is polled for changes; a new version is validated as a whole and,
+//! if valid, its static producers are diffed against the previous version and
+//! applied to the live `ProducerPool`. Ingress connections are never touched.
+//! Producers added at runtime through the registration server are left alone
+//! unless the file declares the same id.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::transport::{self, ClientFactory, ProducerPool};
+
+use super::config::{self, AppConfig, ProducerSpec};
+
+/// How often the configuration file is checked for changes
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a reload changed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadSummary {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// Settings that differ but only take effect after a restart
+    pub restart_required: Vec<&'static str>,
+    /// Producers that could not be applied
+    pub failed: Vec<String>,
+}
+
+impl ReloadSummary {
+    /// Check if the reload changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.restart_required.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for ReloadSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let sections = [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            ("changed", &self.changed),
+            ("failed", &self.failed),
+        ];
+        let mut parts: Vec<String> = sections.iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(label, ids)| format!("{} [{}]", label, ids.join(", ")))
+            .collect();
+        if !self.restart_required.is_empty() {
+            parts.push(format!("restart required for [{}]", self.restart_required.join(", ")));
+        }
+
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Apply the difference between two sets of static producers to the pool
+pub fn apply_producers(
+    pool: &ProducerPool,
+    old: &[ProducerSpec],
+    new: &[ProducerSpec],
+    summary: &mut ReloadSummary,
+) {
+    for spec in old {
+        if !new.iter().any(|candidate| candidate.id == spec.id) && pool.remove_producer(&spec.id).is_some() {
+            summary.removed.push(spec.id.clone());
+        }
+    }
+
+    for spec in new {
+        let previous = old.iter().find(|candidate| candidate.id == spec.id);
+        let result = match previous {
+            // Unchanged, or removed from the pool since (e.g. via DEREGISTER): leave it
+            Some(previous) if previous == spec => continue,
+            Some(previous) if pool.has_producer(&spec.id) => update_producer(pool, previous, spec)
+                .map(|()| summary.changed.push(spec.id.clone())),
+            _ => add_producer(pool, spec).map(|()| summary.added.push(spec.id.clone())),
+        };
+
+        if let Err(e) = result {
+            summary.failed.push(format!("{}: {}", spec.id, e));
+        }
+    }
+}
+
+fn add_producer(pool: &ProducerPool, spec: &ProducerSpec) -> std::io::Result<()> {
+    let client = ClientFactory::create_from_uri_with_timeout(&spec.uri, spec.timeout)?;
+    let events: Vec<&str> = spec.events.iter().map(String::as_str).collect();
+
+    // A runtime-registered producer with the same id is replaced by the file's
+    pool.remove_producer(&spec.id);
+    pool.add_producer_with_events(spec.id.clone(), client, &events)
+}
+
+fn update_producer(pool: &ProducerPool, old: &ProducerSpec, new: &ProducerSpec) -> std::io::Result<()> {
+    if old.uri != new.uri || old.timeout != new.timeout {
+        let client = ClientFactory::create_from_uri_with_timeout(&new.uri, new.timeout)?;
+        pool.update_producer_client(&new.id, client)?;
+    }
+
+    // Only subscriptions owned by the file are touched; runtime SUBSCRIBEs stay
+    for event in old.events.iter().filter(|event| !new.events.contains(event)) {
+        pool.unsubscribe_producer_from_event(&new.id, event)?;
+    }
+    for event in new.events.iter().filter(|event| !old.events.contains(event)) {
+        pool.subscribe_producer_to_event(&new.id, event)?;
+    }
+
+    Ok(())
+}
+
+/// List server settings that changed but are only read at startup
+fn restart_required(old_config: &AppConfig, new_config: &AppConfig) -> Vec<&'static str> {
+    let (old, new) = (&old_config.server, &new_config.server);
+    let checks = [
+        ("listeners.ingress", old.listeners() != new.listeners()),
+        (
+            "listeners.registration",
+            old.registration.enabled != new.registration.enabled
+                || old.registration.address != new.registration.address
+                || old.registration.loopback_only != new.registration.loopback_only
+                || old.registration.shared_with_ingress != new.registration.shared_with_ingress,
+        ),
+        ("security.registration_tokens", old.registration.auth != new.registration.auth),
+        ("limits.lease_reaper_interval_ms", old.lease_reaper_interval != new.lease_reaper_interval),
+        ("limits.config_reload_interval_ms", old_config.reload_interval != new_config.reload_interval),
+        ("limits.shutdown_timeout_ms", old_config.shutdown_timeout != new_config.shutdown_timeout),
+        ("limits.request_timeout_ms", old.request_timeout != new.request_timeout),
+        ("limits.max_pending_requests", old.max_pending_requests != new.max_pending_requests),
+        ("limits.max_connections", old.connection_limits.max_connections != new.connection_limits.max_connections),
+        ("limits.idle_timeout_ms", old.connection_limits.idle_timeout != new.connection_limits.idle_timeout),
+        ("limits.read_timeout_ms", old.connection_limits.read_timeout != new.connection_limits.read_timeout),
+        ("limits.max_message_bytes", old.connection_limits.max_frame_size != new.connection_limits.max_frame_size),
+        ("limits.worker_threads", old.worker_threads != new.worker_threads),
+        ("limits.message_threads", old.message_threads != new.message_threads),
+        ("limits.fanout_threads", old.fanout.threads != new.fanout.threads),
+        ("logging.log_messages", old.log_messages != new.log_messages),
+    ];
+
+    checks.into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Apply a newly loaded configuration to the running server
+pub fn apply(pool: &ProducerPool, old: &AppConfig, new: &AppConfig) -> ReloadSummary {
+    let mut summary = ReloadSummary::default();
+
+    apply_producers(pool, &old.producers, &new.producers, &mut summary);
+
+    if old.destination_policy != new.destination_policy {
+        transport::set_destination_policy(new.destination_policy.clone());
+        summary.changed.push("security.destination_allow/deny".to_string());
+    }
+
+    if old.server.event_ttls != new.server.event_ttls {
+        pool.set_event_ttls(new.server.event_ttls.clone());
+        summary.changed.push("limits.event_ttl_ms".to_string());
+    }
+    if old.server.rate_limits != new.server.rate_limits {
+        pool.set_rate_limits(new.server.rate_limits.clone());
+        summary.changed.push("rate_limits".to_string());
+    }
+    if old.server.fanout.deadline != new.server.fanout.deadline {
+        pool.set_fanout(new.server.fanout);
+        summary.changed.push("limits.fanout_deadline_ms".to_string());
+    }
+    if old.server.scheduler != new.server.scheduler {
+        pool.scheduler().set_limits(new.server.scheduler);
+        summary.changed.push("limits.max_scheduled_messages/max_schedule_delay_ms".to_string());
+    }
+    if old.server.dedup != new.server.dedup {
+        pool.set_dedup_rules(new.server.dedup.clone());
+        summary.changed.push("dedup".to_string());
+    }
+
+    summary.restart_required = restart_required(old, new);
+    summary
+}
+
+/// Watch the configuration file and apply changes as they are saved
+///
+/// An invalid file is reported and ignored; the previous configuration stays active.
+pub fn start_config_watcher(
+    path: PathBuf,
+    mut current: AppConfig,
+    pool: Arc<ProducerPool>,
+    interval: Duration,
+) {
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(interval);
+
+            let modified = modified(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match config::load(Some(&path)) {
+                Ok(new) => {
+                    let summary = apply(&pool, &current, &new);
+                    println!("[Config Reload] {}: {}", path.display(), summary);
+                    current = new;
+                }
+                Err(e) => eprintln!("[Config Reload] Keeping previous configuration. {}", e),
+            }
+        }
+    });
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(id: &str, uri: &str, events: &[&str]) -> ProducerSpec {
+        ProducerSpec {
+            id: id.to_string(),
+            uri: uri.to_string(),
+            events: events.iter().map(|event| event.to_string()).collect(),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_producer_diff() {
+        let pool = ProducerPool::new();
+        let old = vec![
+            spec("keep", "tcp://127.0.0.1:9000", &["a"]),
+            spec("move", "tcp://127.0.0.1:9001", &["a", "b"]),
+            spec("drop", "tcp://127.0.0.1:9002", &["a"]),
+        ];
+        apply_producers(&pool, &[], &old, &mut ReloadSummary::default());
+
+        // Runtime subscriptions survive a reload
+        pool.subscribe_producer_to_event("move", "runtime").unwrap();
+
+        let new = vec![
+            spec("keep", "tcp://127.0.0.1:9000", &["a"]),
+            spec("move", "tcp://127.0.0.1:9101", &["b", "c"]),
+            spec("new", "tcp://127.0.0.1:9003", &["c"]),
+        ];
+        let mut summary = ReloadSummary::default();
+        apply_producers(&pool, &old, &new, &mut summary);
+
+        assert_eq!(summary.added, vec!["new"]);
+        assert_eq!(summary.removed, vec!["drop"]);
+        assert_eq!(summary.changed, vec!["move"]);
+        assert!(summary.failed.is_empty());
+
+        let moved = pool.get_producer("move").unwrap();
+        assert_eq!(moved.address(), "127.0.0.1:9101");
+        assert!(!moved.is_subscribed("a"));
+        assert!(moved.is_subscribed("c"));
+        assert!(moved.is_subscribed("runtime"));
+        assert!(!pool.has_producer("drop"));
+        assert_eq!(pool.get_event_subscribers("a"), vec!["keep".to_string()]);
+    }
+
+    fn load(json: &str) -> AppConfig {
+        let file = config::FileConfig::parse(json).unwrap();
+        AppConfig::resolve(file, &Default::default()).unwrap()
+    }
+
+    #[test]
+    fn test_startup_only_limits_require_restart() {
+        let old = load("{}");
+        let new = load(r#"{ "limits": { "shutdown_timeout_ms": 1500, "config_reload_interval_ms": 250 } }"#);
+
+        assert_eq!(
+            restart_required(&old, &new),
+            vec!["limits.config_reload_interval_ms", "limits.shutdown_timeout_ms"],
+        );
+        assert!(restart_required(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_scheduler_limits_apply_on_reload() {
+        let pool = ProducerPool::new();
+        let old = load("{}");
+        let new = load(r#"{ "limits": { "max_scheduled_messages": 5, "max_schedule_delay_ms": 60000 } }"#);
+
+        let summary = apply(&pool, &old, &new);
+        assert_eq!(pool.scheduler().limits().max_messages, 5);
+        assert_eq!(pool.scheduler().limits().max_delay, Duration::from_secs(60));
+        assert_eq!(summary.changed, vec!["limits.max_scheduled_messages/max_schedule_delay_ms"]);
+        assert!(summary.restart_required.is_empty());
+    }
+
+    #[test]
+    fn test_summary_format() {
+        assert_eq!(ReloadSummary::default().to_string(), "no changes");
+
+        let summary = ReloadSummary {
+            added: vec!["a".to_string()],
+            removed: vec!["b".to_string(), "c".to_string()],
+            restart_required: vec!["listeners.ingress"],
+            ..ReloadSummary::default()
+        };
+        assert_eq!(
+            summary.to_string(),
+            "added [a]; removed [b, c]; restart required for [listeners.ingress]",
+        );
+    }
+}
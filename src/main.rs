@@ -1,31 +1,25 @@
-use std::io::{stdin, BufRead};
 mod boot;
 mod transport;
 
+use boot::daemon::{self, RunMode};
+
 fn main() {
     println!("Starting Rust Samples application...");
     
     // Initialize transport server
-    boot::boot();
+    let app_config = boot::boot();
+    let mode = RunMode::detect();
     
     println!("\nTransport server is running and ready to accept connections!");
     println!("Connect using: telnet 0.0.0.0 49152 (or nc/your TCP client)");
-    println!("Press Ctrl+C to exit or type 'quit' to stop.\n");
-    
-    // Keep the main thread alive and allow user input
-    let stdin = stdin();
-    for line in stdin.lock().lines() {
-        match line {
-            Ok(input) => {
-                if input.trim().eq_ignore_ascii_case("quit") {
-                    println!("Shutting down...");
-                    break;
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                break;
-            }
-        }
+    match mode {
+        RunMode::Interactive => println!("Press Ctrl+C to exit or type 'quit' to stop.\n"),
+        RunMode::Headless => println!("Running headless; send SIGTERM or SIGINT to stop.\n"),
     }
+    
+    // Keep the main thread alive until asked to stop
+    daemon::wait_for_shutdown(mode, &["quit"]);
+    
+    println!("Shutting down...");
+    std::process::exit(daemon::shutdown(app_config.shutdown_timeout));
 }
@@ -6,10 +6,11 @@
 //! - Logs all received messages
 //! - Can be used for integration testing
 
-use std::io::{stdin, BufRead};
 mod boot;
 mod transport;
 
+use boot::daemon::{self, RunMode};
+
 fn main() {
     println!("==========================================");
     println!("Test Consumer Server");
@@ -19,32 +20,24 @@ fn main() {
     println!("\nServer Configuration:");
     
     // Initialize transport server
-    boot::boot();
+    let app_config = boot::boot();
+    let mode = RunMode::detect();
     
     println!("\n==========================================");
     println!("Consumer server is running!");
     println!("==========================================");
     println!("\nServer is ready to receive messages from:");
     println!("  - JS Driver: node drivers/js/test-consumer-producer.js");
-    println!("\nPress 'q' and Enter to quit, or Ctrl+C to exit.\n");
-    
-    // Keep the main thread alive and allow user input
-    let stdin = stdin();
-    for line in stdin.lock().lines() {
-        match line {
-            Ok(input) => {
-                let trimmed = input.trim();
-                if trimmed.eq_ignore_ascii_case("q") || trimmed.eq_ignore_ascii_case("quit") {
-                    println!("\nShutting down consumer server...");
-                    break;
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                break;
-            }
-        }
+    match mode {
+        RunMode::Interactive => println!("\nPress 'q' and Enter to quit, or Ctrl+C to exit.\n"),
+        RunMode::Headless => println!("\nRunning headless; send SIGTERM or SIGINT to stop.\n"),
     }
     
+    // Keep the main thread alive until asked to stop
+    daemon::wait_for_shutdown(mode, &["q", "quit"]);
+    
+    println!("\nShutting down consumer server...");
+    let status = daemon::shutdown(app_config.shutdown_timeout);
     println!("Consumer server stopped.");
+    std::process::exit(status);
 }
@@ -0,0 +1,232 @@
+use std::io::Write;
+use std::net::IpAddr;
+
+use crate::transport::request_reply::ConnectionWriter;
+
+/// Control line that switches an ingress connection's acknowledgement mode
+pub const ACK_MODE_COMMAND: &str = "ACKMODE";
+
+/// When, if at all, an ingress connection gets a reply per message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AckMode {
+    /// No replies (default, matches earlier versions)
+    #[default]
+    Off,
+    /// `ACK <id>` once the message has been parsed, before routing
+    Parsed,
+    /// `ACK`/`NACK` after routing, with subscriber and failure counts
+    Routed,
+}
+
+impl AckMode {
+    /// Parse an ack mode name (case-insensitive)
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "OFF" => Some(AckMode::Off),
+            "PARSED" => Some(AckMode::Parsed),
+            "ROUTED" => Some(AckMode::Routed),
+            _ => None,
+        }
+    }
+
+    /// Get the ack mode name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AckMode::Off => "OFF",
+            AckMode::Parsed => "PARSED",
+            AckMode::Routed => "ROUTED",
+        }
+    }
+
+    /// Parse an `ACKMODE <mode>` control line
+    ///
+    /// Returns `None` for ordinary messages and `Some(Err(..))` for an unknown mode.
+    pub fn from_command(line: &str) -> Option<Result<Self, String>> {
+        let mut parts = line.split_whitespace();
+        if !parts.next()?.eq_ignore_ascii_case(ACK_MODE_COMMAND) {
+            return None;
+        }
+
+        Some(match (parts.next(), parts.next()) {
+            (Some(mode), None) => Self::from_str(mode)
+                .ok_or_else(|| format!("Unknown ack mode '{}'. Use: ACKMODE <OFF|PARSED|ROUTED>", mode)),
+            _ => Err("Usage: ACKMODE <OFF|PARSED|ROUTED>".to_string()),
+        })
+    }
+}
+
+/// Outcome of routing one message to the producers subscribed to its event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteReport {
+    /// Number of producers subscribed to the event
+    pub subscribers: usize,
+    /// Producers the message could not be forwarded to
+    pub failed: Vec<String>,
+}
+
+/// Writes per-message replies to an ingress connection
+///
+/// The message consumer reports progress through it; in `AckMode::Off`
+/// every call is a no-op. Messages without their own id are identified by
+/// their 1-based sequence number on the connection.
+pub struct Acknowledger<'a> {
+    mode: AckMode,
+    sequence: u64,
+    writer: &'a mut dyn Write,
+    connection: Option<ConnectionWriter>,
+    peer: Option<IpAddr>,
+    identity: Option<String>,
+}
+
+impl<'a> Acknowledger<'a> {
+    /// Create an acknowledger for one message
+    pub fn new(mode: AckMode, sequence: u64, writer: &'a mut dyn Write) -> Self {
+        Self { mode, sequence, writer, connection: None, peer: None, identity: None }
+    }
+
+    /// Attach the connection's shared writer so replies to requests can be routed back later
+    pub fn with_connection(mut self, connection: ConnectionWriter) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    /// Get the connection's shared writer, if the transport can route replies back
+    pub fn connection(&self) -> Option<ConnectionWriter> {
+        self.connection.clone()
+    }
+
+    /// Attach who sent the message, used to apply rate limits
+    pub fn with_origin(mut self, peer: IpAddr, identity: Option<String>) -> Self {
+        self.peer = Some(peer);
+        self.identity = identity;
+        self
+    }
+
+    /// Get the sender's IP address, if the transport knows it
+    pub fn peer(&self) -> Option<IpAddr> {
+        self.peer
+    }
+
+    /// Get the identity the connection authenticated as, if any
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    /// Report that the message was parsed and accepted for routing
+    pub fn parsed(&mut self, id: Option<&str>) {
+        if self.mode == AckMode::Parsed {
+            let id = self.id(id);
+            self.reply(format!("ACK {}", id));
+        }
+    }
+
+    /// Report the routing outcome
+    pub fn routed(&mut self, id: Option<&str>, report: &RouteReport) {
+        if self.mode != AckMode::Routed {
+            return;
+        }
+
+        let id = self.id(id);
+        if report.failed.is_empty() {
+            self.reply(format!("ACK {} subscribers={}", id, report.subscribers));
+        } else {
+            self.reply(format!(
+                "NACK {} delivery failed for {}/{} subscriber(s): {}",
+                id,
+                report.failed.len(),
+                report.subscribers,
+                report.failed.join(","),
+            ));
+        }
+    }
+
+    /// Report that the message was scheduled for later delivery under `schedule_id`
+    pub fn scheduled(&mut self, id: Option<&str>, schedule_id: &str) {
+        if self.mode == AckMode::Routed {
+            let id = self.id(id);
+            self.reply(format!("ACK {} scheduled={}", id, schedule_id));
+        }
+    }
+
+    /// Report that the message was a duplicate, acknowledged without being routed again
+    pub fn duplicate(&mut self, id: Option<&str>) {
+        if self.mode == AckMode::Routed {
+            let id = self.id(id);
+            self.reply(format!("ACK {} duplicate", id));
+        }
+    }
+
+    /// Report that the message was accepted but discarded
+    pub fn dropped(&mut self, id: Option<&str>) {
+        if self.mode == AckMode::Routed {
+            let id = self.id(id);
+            self.reply(format!("ACK {} dropped", id));
+        }
+    }
+
+    /// Report that the message was not routed
+    pub fn rejected(&mut self, id: Option<&str>, reason: &str) {
+        if self.mode != AckMode::Off {
+            let id = self.id(id);
+            self.reply(format!("NACK {} {}", id, reason));
+        }
+    }
+
+    fn id(&self, id: Option<&str>) -> String {
+        id.map(str::to_string).unwrap_or_else(|| self.sequence.to_string())
+    }
+
+    fn reply(&mut self, line: String) {
+        // Best effort: a producer that stopped reading must not break routing.
+        // One write per line keeps it whole on a shared connection writer.
+        let _ = self.writer.write_all(format!("{}\n", line).as_bytes()).and_then(|()| self.writer.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replies(mode: AckMode, report: impl FnOnce(&mut Acknowledger)) -> String {
+        let mut output = Vec::new();
+        report(&mut Acknowledger::new(mode, 7, &mut output));
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_ack_mode_command() {
+        assert_eq!(AckMode::from_command("ACKMODE routed"), Some(Ok(AckMode::Routed)));
+        assert_eq!(AckMode::from_command("ackmode OFF"), Some(Ok(AckMode::Off)));
+        assert!(matches!(AckMode::from_command("ACKMODE sometimes"), Some(Err(_))));
+        assert!(matches!(AckMode::from_command("ACKMODE"), Some(Err(_))));
+        assert_eq!(AckMode::from_command("user_login:ACKMODE"), None);
+    }
+
+    #[test]
+    fn test_replies_per_mode() {
+        let report = RouteReport { subscribers: 2, failed: vec![] };
+        let ack = |ack: &mut Acknowledger| {
+            ack.parsed(None);
+            ack.routed(None, &report);
+        };
+        assert_eq!(replies(AckMode::Off, ack), "");
+        assert_eq!(replies(AckMode::Parsed, ack), "ACK 7\n");
+        assert_eq!(replies(AckMode::Routed, ack), "ACK 7 subscribers=2\n");
+
+        let failed = RouteReport { subscribers: 2, failed: vec!["audit".to_string()] };
+        assert_eq!(
+            replies(AckMode::Routed, |ack| ack.routed(Some("m-1"), &failed)),
+            "NACK m-1 delivery failed for 1/2 subscriber(s): audit\n",
+        );
+        assert_eq!(
+            replies(AckMode::Parsed, |ack| ack.rejected(None, "invalid JSON")),
+            "NACK 7 invalid JSON\n",
+        );
+        assert_eq!(
+            replies(AckMode::Routed, |ack| ack.scheduled(None, "sched-1")),
+            "ACK 7 scheduled=sched-1\n",
+        );
+        assert_eq!(replies(AckMode::Routed, |ack| ack.duplicate(Some("m-1"))), "ACK m-1 duplicate\n");
+        assert_eq!(replies(AckMode::Routed, |ack| ack.dropped(None)), "ACK 7 dropped\n");
+    }
+}
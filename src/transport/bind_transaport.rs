@@ -1,38 +1,71 @@
 use std::io::Result;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::transport::{
     Server, 
     ServerConfig, 
-    MessageConsumer, 
     get_producer_pool,
+    ProducerPool,
     EventMessage,
-    start_registration_server_default,
+    ServerHandle,
+    start_registration_server_with_config,
+    start_lease_reaper,
+    start_redelivery,
+    DEFAULT_REDELIVERY_INTERVAL,
+    DeadLetter,
+    EXPIRED_REASON,
+    RateLimitAction,
+    start_scheduler,
+    DEFAULT_SCHEDULER_INTERVAL,
 };
+use crate::transport::ack::{Acknowledger, RouteReport};
+use crate::transport::request_reply::{start_request_reaper, PendingRequests, Responder, REQUEST_SWEEP_INTERVAL};
+use crate::transport::reactor::init_reactor;
+use crate::transport::registration_server::REGISTRATION_HANDOFF_KEYWORD;
 
 /// Bind and start the transport server with default settings (TCP)
-pub fn bind_transport() -> Result<()> {
+pub fn bind_transport() -> Result<ServerHandle> {
     bind_transport_with_config(ServerConfig::new())
 }
 
-/// Bind and start the transport server with a custom address (defaults to TCP)
-pub fn bind_transport_with_address(address: String) -> Result<()> {
-    let config = ServerConfig::with_address(address);
-    bind_transport_with_config(config)
-}
-
 /// Bind and start the transport server with custom configuration
-pub fn bind_transport_with_config(config: ServerConfig) -> Result<()> {
+///
+/// Returns once the ingress listener is bound; a bind failure is returned here.
+pub fn bind_transport_with_config(config: ServerConfig) -> Result<ServerHandle> {
     // Initialize global producer pool
     let producer_pool = get_producer_pool();
+    producer_pool.set_event_ttls(config.event_ttls.clone());
+    producer_pool.set_dedup_rules(config.dedup.clone());
+    producer_pool.set_rate_limits(config.rate_limits.clone());
+    producer_pool.set_fanout(config.fanout);
+    producer_pool.scheduler().set_limits(config.scheduler);
     
-    // Start registration server in background thread
-    thread::spawn(move || {
-        if let Err(e) = start_registration_server_default() {
-            eprintln!("Registration server error: {}", e);
-        }
-    });
+    // Every listener hands its connections to the same worker pool
+    init_reactor(config.worker_threads, config.message_threads)?;
+    
+    // Start the dedicated registration listener in a background thread;
+    // a shared listener is attached to the ingress transport instead
+    let registration = config.registration.clone();
+    if registration.enabled && !registration.shared_with_ingress {
+        let (registration, limits) = (registration.clone(), config.connection_limits);
+        thread::spawn(move || {
+            if let Err(e) = start_registration_server_with_config(&registration, limits) {
+                eprintln!("Registration server error: {}", e);
+            }
+        });
+    }
+    
+    // Evict producers whose registration lease has expired
+    start_lease_reaper(Arc::clone(&producer_pool), config.lease_reaper_interval);
+    
+    // Resend messages that at-least-once consumers have not acknowledged
+    start_redelivery(Arc::clone(&producer_pool), DEFAULT_REDELIVERY_INTERVAL);
+    
+    // Requests published with `reply_to` wait here for their reply
+    let requests = Arc::new(PendingRequests::with_max_pending(config.max_pending_requests));
+    start_request_reaper(Arc::clone(&requests), REQUEST_SWEEP_INTERVAL);
     
     // Create server with configuration
     let mut server = Server::with_config(config);
@@ -40,54 +73,341 @@ pub fn bind_transport_with_config(config: ServerConfig) -> Result<()> {
     // Clone producer pool for the consumer closure
     let pool = Arc::clone(&producer_pool);
     
-    // Define message consumer that routes by event_name
-    let consumer: MessageConsumer = Box::new(move |message| {
-        println!("[Message Consumer] Received: {}", message);
+    let log_messages = server.config().log_messages;
+    let router = EventRouter {
+        pool: Arc::clone(&producer_pool),
+        requests,
+        request_timeout: server.config().request_timeout,
+        log_messages,
+    };
+    
+    // Route delayed messages once their delivery time comes
+    let scheduled_pool = Arc::clone(&producer_pool);
+    start_scheduler(Arc::clone(&producer_pool), DEFAULT_SCHEDULER_INTERVAL, move |scheduled| {
+        let message = &scheduled.message;
+        if scheduled.expires_at.is_some_and(|expires_at| expires_at <= Instant::now()) {
+            eprintln!("[Scheduler] Dropped expired message '{}' for event '{}'", 
+                      scheduled.schedule_id, message.event_name);
+            dead_letter_expired(&scheduled_pool, message);
+            return;
+        }
+        
+        let report = route_event(&scheduled_pool, message, scheduled.expires_at, log_messages);
+        if log_messages {
+            println!("[Scheduler] Delivered message '{}' for event '{}' to {} subscriber(s)", 
+                     scheduled.schedule_id, message.event_name, report.subscribers);
+        }
+    });
+    
+    // Define message consumer that routes by event_name; every listener shares it
+    let consumer = move |listener: &str, message: String, ack: &mut Acknowledger| {
+        if log_messages {
+            println!("[Message Consumer:{}] Received: {}", listener, message);
+        }
         
         // Try to parse as EventMessage (JSON or simple format)
         let event_message = match EventMessage::from_json(&message) {
             Ok(msg) => msg,
+            // A JSON object that doesn't match the message format is rejected
+            // instead of being routed as a simple-format message
+            Err(e) if message.trim_start().starts_with('{') => {
+                eprintln!("[Message Consumer:{}] Rejected invalid JSON message: {}", listener, e);
+                ack.rejected(None, &format!("invalid JSON message: {}", e));
+                return;
+            }
             Err(_) => {
                 // Try simple format: "event_name:message"
                 EventMessage::from_simple_format(&message)
             }
         };
         
-        println!("[Event Router] Event: '{}', Message: '{}'", 
-                 event_message.event_name, event_message.msg);
+        // Messages over a rate limit are rejected, dropped or held back in the scheduler
+        let mut hold = None;
+        let action = pool.rate_limiter().action();
+        let max_delay = pool.rate_limiter().max_delay();
+        let peer = ack.peer().map(|peer| peer.to_string());
+        let limit = pool.rate_limiter().check(
+            peer.as_deref(),
+            ack.identity(),
+            &event_message.event_name,
+            (action == RateLimitAction::Delay).then_some(max_delay),
+            Instant::now(),
+        );
+        if let Err(limited) = limit {
+            if log_messages {
+                println!("[Message Consumer:{}] {} ({:?})", listener, limited, action);
+            }
+            match action {
+                RateLimitAction::Nack => {
+                    ack.rejected(event_message.id.as_deref(), &limited.to_string());
+                    return;
+                }
+                RateLimitAction::Drop => {
+                    ack.dropped(event_message.id.as_deref());
+                    return;
+                }
+                RateLimitAction::Delay if limited.reserved => hold = Some(limited.wait),
+                RateLimitAction::Delay => {
+                    let reason = format!("{} (would wait over {} ms)", limited, max_delay.as_millis());
+                    ack.rejected(event_message.id.as_deref(), &reason);
+                    return;
+                }
+            }
+        }
+        
+        // Times too far out to count from now make the message invalid
+        let received = Instant::now();
+        if let Err(e) = event_message.check_times(received) {
+            eprintln!("[Message Consumer:{}] Rejected message for event '{}': {}", 
+                      listener, event_message.event_name, e);
+            ack.rejected(event_message.id.as_deref(), &format!("invalid message: {}", e));
+            return;
+        }
+        
+        // Messages whose TTL already ran out are dead-lettered instead of routed
+        let expires_at = event_message.expiry(received, pool.event_ttl(&event_message.event_name));
+        if expires_at.is_some_and(|expires_at| expires_at <= received) {
+            eprintln!("[Message Consumer:{}] Dropped expired message for event '{}'", 
+                      listener, event_message.event_name);
+            dead_letter_expired(&pool, &event_message);
+            ack.rejected(event_message.id.as_deref(), EXPIRED_REASON);
+            return;
+        }
+        ack.parsed(event_message.id.as_deref());
+        
+        router.route(message, &event_message, received, expires_at, hold, ack);
+    };
+    
+    // Initialize and start the server
+    server.initialize()?;
+    let handle = server.start_labeled(consumer)?;
+    
+    for (listener, bound) in server.config().listeners().iter().zip(handle.listeners()) {
+        println!("Transport server initialized with {} protocol on {} ('{}')", 
+                 listener.protocol.as_str(),
+                 bound.local_addr(),
+                 listener.name);
+    }
+    if !registration.enabled {
+        println!("Registration server disabled");
+    } else if registration.shared_with_ingress {
+        println!("Registration server sharing the ingress listener(s) (send '{}' as the first line{})",
+                 REGISTRATION_HANDOFF_KEYWORD,
+                 if registration.loopback_only { ", loopback only" } else { "" });
+    } else {
+        println!("Registration server started on {}", registration.bind_address());
+    }
+    
+    Ok(handle)
+}
+
+/// Routes accepted messages by event name: replies, requests, delayed and plain messages
+struct EventRouter {
+    pool: Arc<ProducerPool>,
+    requests: Arc<PendingRequests>,
+    request_timeout: Duration,
+    log_messages: bool,
+}
+
+impl EventRouter {
+    /// Route a valid message that has been acknowledged as parsed
+    ///
+    /// A message held back by a rate limit for `hold` is scheduled like a
+    /// delayed one. Replies and requests are bound to their connection, so
+    /// they are routed right away; the tokens they took still hold back the
+    /// messages behind them.
+    ///
+    /// A message's dedup key is recorded only once the message is taken
+    /// (scheduled, handed to a subscriber, or has no subscribers), so a
+    /// retry after a `NACK` is routed instead of suppressed.
+    fn route(
+        &self,
+        message: String,
+        event_message: &EventMessage,
+        received: Instant,
+        expires_at: Option<Instant>,
+        hold: Option<Duration>,
+        ack: &mut Acknowledger,
+    ) {
+        let pool = &self.pool;
+        let log_messages = self.log_messages;
+        
+        // A message seen within its event's dedup window is acknowledged but not routed again
+        if pool.dedup().is_duplicate(event_message, received) {
+            if log_messages {
+                println!("[Event Router] Suppressed duplicate message for event '{}'", event_message.event_name);
+            }
+            ack.duplicate(event_message.id.as_deref());
+            return;
+        }
         
-        // Forward message to producers subscribed to this event
-        let results = pool.forward_to_event(&event_message.event_name, &event_message.msg);
+        if log_messages {
+            println!("[Event Router] Event: '{}', Message: '{}'", 
+                     event_message.event_name, event_message.msg);
+        }
         
-        // Log forwarding results
-        for (producer_id, result) in results {
-            match result {
-                Ok(_) => println!("[Producer {}] Event '{}' forwarded successfully", 
-                                 producer_id, event_message.event_name),
-                Err(e) => eprintln!("[Producer {}] Failed to forward event '{}': {}", 
-                                   producer_id, event_message.event_name, e),
+        // A reply to a pending request goes back to the requesting connection
+        match self.requests.complete(event_message, ack.peer(), ack.connection().as_ref()) {
+            Ok(true) => {
+                pool.dedup().record(event_message, received);
+                ack.routed(event_message.id.as_deref(), &RouteReport { subscribers: 1, failed: Vec::new() });
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("[Request Router] Rejected reply on '{}': {}", event_message.event_name, e);
+                ack.rejected(event_message.id.as_deref(), &e.to_string());
+                return;
             }
         }
         
+        if event_message.reply_to.is_some() {
+            self.route_request(event_message, received, ack);
+            return;
+        }
+        
+        // Delayed and held back messages wait in the scheduler and are routed when due
+        if let Some(delay) = event_message.delivery_delay().max(hold) {
+            match pool.scheduler().schedule(event_message.clone(), delay, expires_at) {
+                Ok(schedule_id) => {
+                    pool.dedup().record(event_message, received);
+                    if log_messages {
+                        println!("[Event Router] Scheduled message '{}' for event '{}' in {:?}", 
+                                 schedule_id, event_message.event_name, delay);
+                    }
+                    ack.scheduled(event_message.id.as_deref(), &schedule_id);
+                }
+                Err(e) => ack.rejected(event_message.id.as_deref(), &e.to_string()),
+            }
+            return;
+        }
+        
+        let report = route_event(pool, event_message, expires_at, log_messages);
+        // A message no subscriber took may be sent again
+        if report.subscribers == 0 || report.failed.len() < report.subscribers {
+            pool.dedup().record(event_message, received);
+        }
+        ack.routed(event_message.id.as_deref(), &report);
+        
         // Also process locally
-        process_message(message);
-    });
+        process_message(message, log_messages);
+    }
     
-    // Initialize and start the server
-    server.initialize_and_start(consumer)?;
+    /// Hand a request to one subscriber and wait for its reply on the requesting connection
+    fn route_request(&self, request: &EventMessage, received: Instant, ack: &mut Acknowledger) {
+        let id = request.id.as_deref();
+        let (Some(reply_to), Some(correlation_id)) = (request.reply_to.as_deref(), request.correlation_id.as_deref()) else {
+            ack.rejected(id, "request with reply_to needs a correlation_id");
+            return;
+        };
+        let Some(connection) = ack.connection() else {
+            ack.rejected(id, "replies are not supported on this connection");
+            return;
+        };
+        let token = match self.requests.register(correlation_id, reply_to, connection, self.request_timeout) {
+            Ok(token) => token,
+            Err(e) => {
+                ack.rejected(id, &e.to_string());
+                return;
+            }
+        };
+        
+        // The subscriber gets the whole request so it knows where to reply, with
+        // the server's token in place of the requester's correlation id
+        let forwarded = EventMessage { correlation_id: Some(token.clone()), ..request.clone() };
+        let payload = forwarded.to_json().unwrap_or_else(|_| request.msg.clone());
+        let attempts = self.pool.forward_to_one_subscriber(&request.event_name, id, &payload, |producer| {
+            let responder = match producer.connection() {
+                Some(connection) => Responder::connection(producer.id(), connection),
+                None => Responder::address(producer.id(), producer.address()),
+            };
+            self.requests.assign(&token, responder);
+        });
+        
+        let handler = attempts.iter()
+            .find(|(_, result)| result.is_ok())
+            .map(|(producer_id, _)| producer_id.clone());
+        let mut report = RouteReport { subscribers: attempts.len(), failed: Vec::new() };
+        for (producer_id, result) in attempts {
+            if let Err(e) = result {
+                eprintln!("[Producer {}] Failed to forward request '{}': {}", producer_id, correlation_id, e);
+                report.failed.push(producer_id);
+            }
+        }
+        
+        match handler {
+            // Subscribers tried before the handler don't fail the request
+            Some(handler) => {
+                self.pool.dedup().record(request, received);
+                if self.log_messages {
+                    println!("[Request Router] Request '{}' handled by '{}'", correlation_id, handler);
+                }
+                report = RouteReport { subscribers: 1, failed: Vec::new() };
+            }
+            None if report.subscribers == 0 => self.requests.fail(
+                &token,
+                &format!("no subscribers for event '{}'", request.event_name),
+            ),
+            None => self.requests.fail(&token, "no subscriber accepted the request"),
+        }
+        ack.routed(id, &report);
+    }
+}
+
+/// Forward a message to the producers subscribed to its event
+fn route_event(
+    pool: &ProducerPool,
+    event_message: &EventMessage,
+    expires_at: Option<Instant>,
+    log_messages: bool,
+) -> RouteReport {
+    // Retained messages are kept for producers that subscribe later
+    if event_message.retain {
+        pool.retain_message_until(&event_message.event_name, &event_message.msg, expires_at);
+    }
     
-    println!("Transport server initialized with {} protocol on {}", 
-             server.config().protocol.as_str(),
-             server.config().address);
-    println!("Registration server started on port 49153");
+    // Forward message to producers subscribed to this event
+    let results = pool.forward_to_event_until(
+        &event_message.event_name,
+        event_message.id.as_deref(),
+        &event_message.msg,
+        expires_at,
+    );
+    let mut report = RouteReport { subscribers: results.len(), failed: Vec::new() };
     
-    Ok(())
+    // Log forwarding results
+    for (producer_id, result) in results {
+        match result {
+            Ok(_) if !log_messages => {}
+            Ok(_) => println!("[Producer {}] Event '{}' forwarded successfully", 
+                             producer_id, event_message.event_name),
+            Err(e) => {
+                eprintln!("[Producer {}] Failed to forward event '{}': {}", 
+                          producer_id, event_message.event_name, e);
+                report.failed.push(producer_id);
+            }
+        }
+    }
+    report
+}
+
+/// Move a message that expired before it was routed to the dead-letter store
+fn dead_letter_expired(pool: &ProducerPool, event_message: &EventMessage) {
+    pool.dead_letters().push(DeadLetter {
+        message_id: event_message.id.clone(),
+        event_name: event_message.event_name.clone(),
+        producer_id: None,
+        message: event_message.msg.clone(),
+        reason: EXPIRED_REASON.to_string(),
+    });
 }
 
 /// Process received messages
-fn process_message(message: String) {
+fn process_message(message: String, log_messages: bool) {
     // Example message processing
-    println!("Processing message: {}", message);
+    if log_messages {
+        println!("Processing message: {}", message);
+    }
     
     // Add your message handling logic here
     // For example:
@@ -96,3 +416,80 @@ fn process_message(message: String) {
     // - Send acknowledgments
     // etc.
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::transport::ack::AckMode;
+    use crate::transport::clients::TcpClient;
+    use crate::transport::producers::scheduler::SchedulerLimits;
+    use crate::transport::DedupRule;
+
+    fn router(pool: &Arc<ProducerPool>) -> EventRouter {
+        EventRouter {
+            pool: Arc::clone(pool),
+            requests: Arc::new(PendingRequests::default()),
+            request_timeout: Duration::from_secs(5),
+            log_messages: false,
+        }
+    }
+
+    /// Route a message, returning the acknowledgement it gets under `ACKMODE ROUTED`
+    fn send(router: &EventRouter, message: &EventMessage) -> String {
+        send_held(router, message, None)
+    }
+
+    fn send_held(router: &EventRouter, message: &EventMessage, hold: Option<Duration>) -> String {
+        let mut output = Vec::new();
+        let mut ack = Acknowledger::new(AckMode::Routed, 1, &mut output);
+        router.route(message.msg.clone(), message, Instant::now(), None, hold, &mut ack);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_retry_after_nack_is_not_a_duplicate() {
+        let pool = Arc::new(ProducerPool::new());
+        pool.set_dedup_rules(HashMap::from([
+            ("payments".to_string(), DedupRule { window: Duration::from_secs(60), header: None }),
+        ]));
+        pool.scheduler().set_limits(SchedulerLimits { max_messages: 10, max_delay: Duration::from_secs(1) });
+        let router = router(&pool);
+        let payment = EventMessage { id: Some("p-1".to_string()), ..EventMessage::new("charge".to_string(), "payments".to_string()) };
+
+        // Rejected by the scheduler
+        let delayed = EventMessage { delay_ms: Some(5000), ..payment.clone() };
+        assert!(send(&router, &delayed).starts_with("NACK p-1 delay of 5000 ms"));
+
+        // Not delivered to any subscriber
+        let client = Arc::new(TcpClient::new("127.0.0.1:1".to_string()));
+        pool.add_producer_with_events("ledger".to_string(), client, &["payments"]).unwrap();
+        for _ in 0..2 {
+            assert_eq!(send(&router, &payment), "NACK p-1 delivery failed for 1/1 subscriber(s): ledger\n");
+        }
+
+        // Accepted once, then suppressed
+        pool.remove_producer("ledger");
+        assert_eq!(send(&router, &payment), "ACK p-1 subscribers=0\n");
+        assert_eq!(send(&router, &payment), "ACK p-1 duplicate\n");
+        assert_eq!(pool.dedup().suppressed(), 1);
+    }
+
+    #[test]
+    fn test_held_message_is_scheduled() {
+        let pool = Arc::new(ProducerPool::new());
+        let router = router(&pool);
+        let alert = EventMessage { id: Some("a-1".to_string()), ..EventMessage::new("disk full".to_string(), "alerts".to_string()) };
+
+        assert_eq!(send_held(&router, &alert, Some(Duration::from_millis(300))), "ACK a-1 scheduled=a-1\n");
+        let scheduled = pool.scheduler().list();
+        assert_eq!(scheduled.len(), 1);
+        assert!(pool.scheduler().take_due(Instant::now() + Duration::from_millis(200)).is_empty());
+        assert_eq!(pool.scheduler().take_due(Instant::now() + Duration::from_millis(400)).len(), 1);
+
+        // A later delivery time wins over the hold
+        let later = EventMessage { id: Some("a-2".to_string()), delay_ms: Some(2000), ..alert };
+        send_held(&router, &later, Some(Duration::from_millis(300)));
+        assert!(pool.scheduler().take_due(Instant::now() + Duration::from_secs(1)).is_empty());
+    }
+}
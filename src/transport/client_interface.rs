@@ -1,13 +1,48 @@
 use std::io::Result;
+use std::time::Duration;
+
+use crate::transport::producers::delivery::DeliveryPolicy;
+use crate::transport::request_reply::ConnectionWriter;
 
 /// Trait for clients that can send messages to external consumers
 pub trait Client: Send + Sync {
     /// Send a message to the external consumer
     fn send(&self, message: &str) -> Result<()>;
-    
+
+    /// Send a message identified by `id`
+    ///
+    /// Clients with a delivery policy only succeed once the consumer has
+    /// acknowledged the id; others ignore it and behave like `send`.
+    fn send_with_id(&self, id: &str, message: &str) -> Result<()> {
+        let _ = id;
+        self.send(message)
+    }
+
+    /// Send a message, with an id if it has one, giving up after `timeout`
+    ///
+    /// Clients that can't bound a send ignore the timeout. Data already
+    /// written when the timeout passes may still reach the consumer.
+    fn send_within(&self, id: Option<&str>, message: &str, timeout: Duration) -> Result<()> {
+        let _ = timeout;
+        match id {
+            Some(id) => self.send_with_id(id, message),
+            None => self.send(message),
+        }
+    }
+
+    /// Get the at-least-once delivery policy, if the client uses one
+    fn delivery_policy(&self) -> Option<DeliveryPolicy> {
+        None
+    }
+
+    /// Get the connection the consumer opened itself, for clients that push on one
+    fn connection(&self) -> Option<ConnectionWriter> {
+        None
+    }
+
     /// Get the client protocol name
     fn protocol_name(&self) -> &str;
-    
+
     /// Get the target address
     fn address(&self) -> &str;
 }
@@ -0,0 +1,38 @@
+use std::io::Result;
+
+use crate::transport::client_interface::Client;
+use crate::transport::request_reply::ConnectionWriter;
+
+/// Client that pushes messages back on a connection the consumer opened
+///
+/// Used for consumers that subscribe over their own ingress connection
+/// instead of running a listener the bus dials into.
+pub struct ConnectionClient {
+    writer: ConnectionWriter,
+    peer: String,
+}
+
+impl ConnectionClient {
+    /// Create a client writing to the connection from `peer`
+    pub fn new(writer: ConnectionWriter, peer: String) -> Self {
+        Self { writer, peer }
+    }
+}
+
+impl Client for ConnectionClient {
+    fn send(&self, message: &str) -> Result<()> {
+        self.writer.write_line(message.trim_end_matches('\n'))
+    }
+
+    fn connection(&self) -> Option<ConnectionWriter> {
+        Some(self.writer.clone())
+    }
+
+    fn protocol_name(&self) -> &str {
+        "CONN"
+    }
+
+    fn address(&self) -> &str {
+        &self.peer
+    }
+}
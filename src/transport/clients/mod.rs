@@ -1,3 +1,5 @@
 pub mod tcp;
+pub mod connection;
 
 pub use tcp::TcpClient;
+pub use connection::ConnectionClient;
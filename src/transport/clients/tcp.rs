@@ -1,13 +1,16 @@
-use std::io::{Result, Write};
+use std::io::{BufRead, BufReader, ErrorKind, Result, Write};
 use std::net::{TcpStream, ToSocketAddrs};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::transport::client_interface::Client;
+use crate::transport::destination_policy::{get_destination_policy, split_host_port};
+use crate::transport::producers::delivery::DeliveryPolicy;
 
 /// TCP client for sending messages to external consumers
 pub struct TcpClient {
     address: String,
     timeout: Duration,
+    delivery: Option<DeliveryPolicy>,
 }
 
 impl TcpClient {
@@ -16,17 +19,23 @@ impl TcpClient {
         Self {
             address,
             timeout: Duration::from_secs(5),
+            delivery: None,
         }
     }
 
     /// Create a new TCP client with custom timeout
     pub fn with_timeout(address: String, timeout: Duration) -> Self {
-        Self { address, timeout }
+        Self { address, timeout, delivery: None }
     }
-}
 
-impl Client for TcpClient {
-    fn send(&self, message: &str) -> Result<()> {
+    /// Require the consumer to acknowledge every message sent with an id
+    pub fn with_delivery(mut self, policy: DeliveryPolicy) -> Self {
+        self.delivery = Some(policy);
+        self
+    }
+
+    /// Resolve the address, check the destination policy and connect
+    fn connect(&self, deadline: Option<Instant>) -> Result<TcpStream> {
         // Parse address and connect to the external consumer
         let addr = self.address.to_socket_addrs()
             .map_err(|_| std::io::Error::new(
@@ -39,22 +48,94 @@ impl Client for TcpClient {
                 format!("Could not resolve address: {}", self.address)
             ))?;
         
-        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        // Re-check the resolved address so DNS changes can't bypass the registration check
+        let host = split_host_port(&self.address).map_or(self.address.as_str(), |(host, _)| host);
+        get_destination_policy().check_resolved(host, &addr)?;
+        
+        let stream = TcpStream::connect_timeout(&addr, Self::bounded(self.timeout, deadline)?)?;
         
         // Set write timeout
-        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(Self::bounded(self.timeout, deadline)?))?;
         
-        // Send message with newline
-        let message_with_newline = if message.ends_with('\n') {
-            message.to_string()
-        } else {
-            format!("{}\n", message)
+        Ok(stream)
+    }
+
+    /// Shorten a timeout so it ends by the deadline, failing once the deadline has passed
+    fn bounded(timeout: Duration, deadline: Option<Instant>) -> Result<Duration> {
+        let Some(deadline) = deadline else {
+            return Ok(timeout);
         };
+        match deadline.saturating_duration_since(Instant::now()) {
+            Duration::ZERO => Err(std::io::Error::new(ErrorKind::TimedOut, "send deadline passed")),
+            remaining => Ok(timeout.min(remaining)),
+        }
+    }
+
+    /// Send a message, with an id and acknowledgement if the client has a delivery policy
+    fn transmit(&self, id: Option<&str>, message: &str, deadline: Option<Instant>) -> Result<()> {
+        let mut stream = self.connect(deadline)?;
+        let message = message.trim_end_matches('\n');
         
-        stream.write_all(message_with_newline.as_bytes())?;
+        let (Some(id), Some(policy)) = (id, self.delivery) else {
+            // Send message with newline
+            stream.write_all(format!("{}\n", message).as_bytes())?;
+            stream.flush()?;
+            return Ok(());
+        };
+        
+        writeln!(stream, "MSG {} {}", id, message)?;
         stream.flush()?;
         
-        Ok(())
+        Self::wait_for_ack(&stream, id, Self::bounded(policy.ack_timeout, deadline)?)
+    }
+
+    /// Wait for the consumer's `ACK <id>` reply
+    fn wait_for_ack(stream: &TcpStream, id: &str, ack_timeout: Duration) -> Result<()> {
+        stream.set_read_timeout(Some(ack_timeout))?;
+        
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)
+            .map_err(|e| match e.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("No ACK for message '{}' within {:?}", id, ack_timeout),
+                ),
+                _ => e,
+            })?;
+        
+        let mut parts = reply.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("ACK"), Some(acked)) if acked == id => Ok(()),
+            (Some("NACK"), Some(nacked)) if nacked == id => Err(std::io::Error::other(
+                format!("Consumer rejected message '{}': {}", id, parts.collect::<Vec<_>>().join(" ")),
+            )),
+            _ if reply.is_empty() => Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Consumer closed the connection before acknowledging message '{}'", id),
+            )),
+            _ => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unexpected reply to message '{}': {}", id, reply.trim_end()),
+            )),
+        }
+    }
+}
+
+impl Client for TcpClient {
+    fn send(&self, message: &str) -> Result<()> {
+        self.transmit(None, message, None)
+    }
+
+    fn send_with_id(&self, id: &str, message: &str) -> Result<()> {
+        self.transmit(Some(id), message, None)
+    }
+
+    fn send_within(&self, id: Option<&str>, message: &str, timeout: Duration) -> Result<()> {
+        self.transmit(id, message, Some(Instant::now() + timeout))
+    }
+
+    fn delivery_policy(&self) -> Option<DeliveryPolicy> {
+        self.delivery
     }
 
     fn protocol_name(&self) -> &str {
@@ -94,4 +175,60 @@ mod tests {
         let result = client.send("test");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_at_least_once_waits_for_ack() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let consumer = std::thread::spawn(move || {
+            let replies = ["ACK m-1", "NACK m-2 busy", "ACK other"];
+            let mut received = Vec::new();
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                received.push(line.trim_end().to_string());
+                writeln!(stream, "{}", reply).unwrap();
+            }
+            received
+        });
+
+        let client = TcpClient::new(address).with_delivery(DeliveryPolicy {
+            ack_timeout: Duration::from_secs(5),
+            ..DeliveryPolicy::default()
+        });
+        client.send_with_id("m-1", "hello").unwrap();
+        assert!(client.send_with_id("m-2", "hello").is_err());
+        assert_eq!(client.send_with_id("m-3", "hello").unwrap_err().kind(), ErrorKind::InvalidData);
+
+        assert_eq!(consumer.join().unwrap(), vec!["MSG m-1 hello", "MSG m-2 hello", "MSG m-3 hello"]);
+    }
+
+    #[test]
+    fn test_send_within_stops_waiting_for_the_ack_at_the_timeout() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let consumer = std::thread::spawn(move || {
+            // Read the message but never acknowledge it
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            std::thread::sleep(Duration::from_millis(500));
+        });
+
+        let client = TcpClient::new(address).with_delivery(DeliveryPolicy {
+            ack_timeout: Duration::from_secs(5),
+            ..DeliveryPolicy::default()
+        });
+        let started = Instant::now();
+        let result = client.send_within(Some("m-1"), "hello", Duration::from_millis(100));
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        consumer.join().unwrap();
+    }
 }
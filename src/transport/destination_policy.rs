@@ -0,0 +1,332 @@
+use std::io::{ErrorKind, Result};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Environment variable holding destination allow rules
+pub const DESTINATION_ALLOW_ENV: &str = "DESTINATION_ALLOW";
+
+/// Environment variable holding destination deny rules
+pub const DESTINATION_DENY_ENV: &str = "DESTINATION_DENY";
+
+/// Host part of a destination rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostMatcher {
+    /// Any host
+    Any,
+    /// IP network in CIDR notation (a bare IP is a /32 or /128)
+    Cidr { network: IpAddr, prefix_len: u8 },
+    /// Hostname, optionally with a leading `*.` wildcard for subdomains
+    Hostname(String),
+}
+
+impl HostMatcher {
+    /// Parse a host matcher from a CIDR, IP, hostname pattern or `*`
+    pub fn parse(s: &str) -> Result<Self> {
+        if s == "*" {
+            return Ok(HostMatcher::Any);
+        }
+
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        if let Ok(network) = addr.parse::<IpAddr>() {
+            let network = network.to_canonical();
+            let max_len = if network.is_ipv4() { 32 } else { 128 };
+            let prefix_len = match prefix {
+                Some(prefix) => prefix.parse::<u8>()
+                    .ok()
+                    .filter(|len| *len <= max_len)
+                    .ok_or_else(|| invalid_rule(s, "invalid CIDR prefix length"))?,
+                None => max_len,
+            };
+            return Ok(HostMatcher::Cidr { network, prefix_len });
+        }
+
+        if prefix.is_some() || s.is_empty() {
+            return Err(invalid_rule(s, "invalid host"));
+        }
+
+        Ok(HostMatcher::Hostname(s.to_ascii_lowercase()))
+    }
+
+    /// Check if the matcher covers a destination host and/or resolved IP
+    fn matches(&self, hostname: Option<&str>, ip: Option<IpAddr>) -> bool {
+        match self {
+            HostMatcher::Any => true,
+            HostMatcher::Cidr { network, prefix_len } => {
+                ip.is_some_and(|ip| cidr_contains(*network, *prefix_len, ip.to_canonical()))
+            }
+            HostMatcher::Hostname(pattern) => hostname.is_some_and(|hostname| {
+                let hostname = hostname.to_ascii_lowercase();
+                match pattern.strip_prefix("*.") {
+                    Some(domain) => hostname.ends_with(&format!(".{}", domain)),
+                    None => hostname == *pattern,
+                }
+            }),
+        }
+    }
+}
+
+/// Single allow or deny rule: a host matcher and an optional port range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationRule {
+    host: HostMatcher,
+    ports: Option<RangeInclusive<u16>>,
+}
+
+impl DestinationRule {
+    /// Parse a rule such as `10.0.0.0/8`, `*.internal:8000-8999` or `[fd00::/8]:9000`
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        let (host, ports) = if let Some(rest) = s.strip_prefix('[') {
+            let (host, rest) = rest.split_once(']')
+                .ok_or_else(|| invalid_rule(s, "missing ']'"))?;
+            match rest {
+                "" => (host, None),
+                _ => {
+                    let ports = rest.strip_prefix(':')
+                        .ok_or_else(|| invalid_rule(s, "expected ':' after ']'"))?;
+                    (host, Some(ports))
+                }
+            }
+        } else if s.matches(':').count() == 1 {
+            let (host, ports) = s.split_once(':').unwrap_or((s, ""));
+            (host, Some(ports))
+        } else {
+            // No port, or a bare IPv6 address/network
+            (s, None)
+        };
+
+        let ports = ports.map(|ports| parse_port_range(ports).ok_or_else(|| invalid_rule(s, "invalid port range")))
+            .transpose()?;
+
+        Ok(Self {
+            host: HostMatcher::parse(host)?,
+            ports,
+        })
+    }
+
+    fn matches(&self, hostname: Option<&str>, ip: Option<IpAddr>, port: u16) -> bool {
+        self.ports.as_ref().is_none_or(|ports| ports.contains(&port))
+            && self.host.matches(hostname, ip)
+    }
+}
+
+/// Allowlist/denylist for addresses the server is willing to connect to
+///
+/// A destination is rejected if it matches any deny rule, or if allow
+/// rules are configured and it matches none of them. An empty policy
+/// allows every destination.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DestinationPolicy {
+    allow: Vec<DestinationRule>,
+    deny: Vec<DestinationRule>,
+}
+
+impl DestinationPolicy {
+    /// Create a policy that allows every destination
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Create a policy from comma-separated allow and deny rule lists
+    pub fn parse(allow: &str, deny: &str) -> Result<Self> {
+        Ok(Self {
+            allow: parse_rules(allow)?,
+            deny: parse_rules(deny)?,
+        })
+    }
+
+    /// Check if any rules are configured
+    pub fn is_restricted(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    /// Check a `host:port` address, resolving it and checking every resolved IP
+    pub fn check_address(&self, address: &str) -> Result<()> {
+        if !self.is_restricted() {
+            return Ok(());
+        }
+
+        let (host, _) = split_host_port(address)
+            .ok_or_else(|| std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid address format: {}", address),
+            ))?;
+
+        let resolved: Vec<SocketAddr> = address.to_socket_addrs()
+            .map_err(|_| std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Could not resolve address: {}", address),
+            ))?
+            .collect();
+
+        if resolved.is_empty() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Could not resolve address: {}", address),
+            ));
+        }
+
+        for addr in resolved {
+            self.check_resolved(host, &addr)?;
+        }
+        Ok(())
+    }
+
+    /// Check a resolved socket address that is about to be connected to
+    pub fn check_resolved(&self, hostname: &str, addr: &SocketAddr) -> Result<()> {
+        let hostname = (hostname.parse::<IpAddr>().is_err()).then_some(hostname);
+        let ip = Some(addr.ip());
+        let port = addr.port();
+
+        let denied = self.deny.iter().any(|rule| rule.matches(hostname, ip, port));
+        let allowed = self.allow.is_empty()
+            || self.allow.iter().any(|rule| rule.matches(hostname, ip, port));
+
+        if denied || !allowed {
+            return Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Destination not allowed: {} ({})", hostname.unwrap_or("ip"), addr),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Global destination policy shared by the registration server and clients
+static DESTINATION_POLICY: OnceLock<RwLock<Arc<DestinationPolicy>>> = OnceLock::new();
+
+fn policy_slot() -> &'static RwLock<Arc<DestinationPolicy>> {
+    DESTINATION_POLICY.get_or_init(|| RwLock::new(Arc::new(DestinationPolicy::allow_all())))
+}
+
+/// Replace the global destination policy
+pub fn set_destination_policy(policy: DestinationPolicy) {
+    *policy_slot().write().unwrap() = Arc::new(policy);
+}
+
+/// Get the global destination policy
+pub fn get_destination_policy() -> Arc<DestinationPolicy> {
+    Arc::clone(&policy_slot().read().unwrap())
+}
+
+/// Split `host:port` or `[v6]:port` into host and port
+pub(crate) fn split_host_port(address: &str) -> Option<(&str, u16)> {
+    let (host, port) = address.rsplit_once(':')?;
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    Some((host, port.parse().ok()?))
+}
+
+fn parse_rules(list: &str) -> Result<Vec<DestinationRule>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(DestinationRule::parse)
+        .collect()
+}
+
+fn parse_port_range(s: &str) -> Option<RangeInclusive<u16>> {
+    match s.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(start..=end)
+        }
+        None => {
+            let port = s.parse().ok()?;
+            Some(port..=port)
+        }
+    }
+}
+
+fn cidr_contains(network: IpAddr, prefix_len: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+fn invalid_rule(rule: &str, reason: &str) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid destination rule '{}': {}", rule, reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rule = DestinationRule::parse("10.0.0.0/8").unwrap();
+        assert_eq!(rule.ports, None);
+
+        let rule = DestinationRule::parse("*.internal:8000-8999").unwrap();
+        assert_eq!(rule.host, HostMatcher::Hostname("*.internal".to_string()));
+        assert_eq!(rule.ports, Some(8000..=8999));
+
+        let rule = DestinationRule::parse("[fd00::/8]:9000").unwrap();
+        assert_eq!(rule.ports, Some(9000..=9000));
+
+        assert!(DestinationRule::parse("::1").is_ok());
+        assert!(DestinationRule::parse("10.0.0.0/33").is_err());
+        assert!(DestinationRule::parse("host:9100-9000").is_err());
+        assert!(DestinationRule::parse("host/8").is_err());
+    }
+
+    #[test]
+    fn test_deny_cidr() {
+        let policy = DestinationPolicy::parse("", "127.0.0.0/8, 169.254.0.0/16").unwrap();
+        assert!(policy.check_resolved("127.0.0.1", &addr("127.0.0.1:9000")).is_err());
+        assert!(policy.check_resolved("metadata", &addr("169.254.169.254:80")).is_err());
+        assert!(policy.check_resolved("10.1.2.3", &addr("10.1.2.3:9000")).is_ok());
+    }
+
+    #[test]
+    fn test_allow_hostname_and_ports() {
+        let policy = DestinationPolicy::parse("*.consumers.local:9000-9100", "").unwrap();
+        assert!(policy.check_resolved("a.consumers.local", &addr("10.0.0.5:9000")).is_ok());
+        assert!(policy.check_resolved("a.consumers.local", &addr("10.0.0.5:22")).is_err());
+        assert!(policy.check_resolved("consumers.local", &addr("10.0.0.5:9000")).is_err());
+        // IP literals never match hostname rules
+        assert!(policy.check_resolved("10.0.0.5", &addr("10.0.0.5:9000")).is_err());
+    }
+
+    #[test]
+    fn test_deny_wins_over_allow() {
+        let policy = DestinationPolicy::parse("10.0.0.0/8", "10.0.0.1").unwrap();
+        assert!(policy.check_resolved("10.0.0.2", &addr("10.0.0.2:9000")).is_ok());
+        let err = policy.check_resolved("10.0.0.1", &addr("10.0.0.1:9000")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_ipv4_mapped_ipv6_is_canonicalized() {
+        let policy = DestinationPolicy::parse("", "127.0.0.0/8").unwrap();
+        assert!(policy.check_resolved("::ffff:127.0.0.1", &addr("[::ffff:127.0.0.1]:9000")).is_err());
+    }
+
+    #[test]
+    fn test_check_address_resolves() {
+        let policy = DestinationPolicy::parse("", "127.0.0.0/8").unwrap();
+        assert!(policy.check_address("127.0.0.1:9000").is_err());
+        assert!(policy.check_address("not-an-address").is_err());
+        assert!(DestinationPolicy::allow_all().check_address("127.0.0.1:9000").is_ok());
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
 /// Message structure with event name for routing
@@ -7,12 +10,101 @@ pub struct EventMessage {
     pub msg: String,
     /// Event name for routing to subscribed producers
     pub event_name: String,
+    /// Producer-supplied id echoed in acknowledgements
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Event a subscriber publishes its reply on, making this a request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// Pairs a request with its reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// Keep this message as the event's current value for late subscribers
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retain: bool,
+    /// Time after which the message is dropped, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Milliseconds the message stays deliverable after the server receives it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+    /// Time to deliver the message at, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_at: Option<u64>,
+    /// Milliseconds to hold the message before delivering it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    /// Free-form metadata, e.g. an idempotency key used for duplicate suppression
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
 }
 
 impl EventMessage {
     /// Create a new event message
     pub fn new(msg: String, event_name: String) -> Self {
-        Self { msg, event_name }
+        Self {
+            msg,
+            event_name,
+            id: None,
+            reply_to: None,
+            correlation_id: None,
+            retain: false,
+            expires_at: None,
+            ttl_ms: None,
+            deliver_at: None,
+            delay_ms: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Get the instant the message expires, given when it was received
+    ///
+    /// The earlier of `expires_at` and `ttl_ms` wins; `default_ttl` only
+    /// applies to messages that set neither.
+    pub fn expiry(&self, received: Instant, default_ttl: Option<Duration>) -> Option<Instant> {
+        // A time already in the past expires the message on arrival
+        let absolute = self.expires_at
+            .and_then(until_epoch_millis)
+            .and_then(|left| received.checked_add(left));
+        let relative = self.ttl_ms.map(Duration::from_millis)
+            .or(if self.expires_at.is_none() { default_ttl } else { None })
+            .and_then(|ttl| received.checked_add(ttl));
+        
+        match (absolute, relative) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Check that every time the message sets can be counted from `received`
+    ///
+    /// Messages that fail are rejected; `expiry` and `delivery_delay` ignore
+    /// times they can't represent.
+    pub fn check_times(&self, received: Instant) -> Result<(), String> {
+        let fields = [
+            ("expires_at", self.expires_at, self.expires_at.and_then(until_epoch_millis)),
+            ("deliver_at", self.deliver_at, self.deliver_at.and_then(until_epoch_millis)),
+            ("ttl_ms", self.ttl_ms, self.ttl_ms.map(Duration::from_millis)),
+            ("delay_ms", self.delay_ms, self.delay_ms.map(Duration::from_millis)),
+        ];
+        for (field, value, left) in fields {
+            if let Some(value) = value
+                && left.and_then(|left| received.checked_add(left)).is_none()
+            {
+                return Err(format!("{} {} is out of range", field, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get how long to hold the message before delivering it, if at all
+    ///
+    /// With both `deliver_at` and `delay_ms` set, the later time wins. A
+    /// `deliver_at` in the past means the message is delivered right away.
+    pub fn delivery_delay(&self) -> Option<Duration> {
+        let absolute = self.deliver_at.and_then(until_epoch_millis);
+        let relative = self.delay_ms.map(Duration::from_millis);
+        absolute.max(relative).filter(|delay| !delay.is_zero())
     }
 
     /// Parse message from JSON string
@@ -31,26 +123,25 @@ impl EventMessage {
             let msg_part = msg.join(":");
             if msg_part.is_empty() {
                 // No message part, treat whole string as message
-                Self {
-                    event_name: "default".to_string(),
-                    msg: text.to_string(),
-                }
+                Self::new(text.to_string(), "default".to_string())
             } else {
-                Self {
-                    event_name: event.trim().to_string(),
-                    msg: msg_part.trim().to_string(),
-                }
+                Self::new(msg_part.trim().to_string(), event.trim().to_string())
             }
         } else {
             // Default event if no separator
-            Self {
-                event_name: "default".to_string(),
-                msg: text.to_string(),
-            }
+            Self::new(text.to_string(), "default".to_string())
         }
     }
 }
 
+/// Time left until a point given in milliseconds since the Unix epoch, zero if it has passed
+///
+/// `None` if the point can't be represented.
+fn until_epoch_millis(millis: u64) -> Option<Duration> {
+    let at = UNIX_EPOCH.checked_add(Duration::from_millis(millis))?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,6 +190,94 @@ mod tests {
         assert_eq!(msg.msg, "Hello:World:Test");
     }
 
+    #[test]
+    fn test_event_message_json_id() {
+        let msg = EventMessage::from_json(r#"{"msg":"Hello","event_name":"e","id":"m-1"}"#).unwrap();
+        assert_eq!(msg.id.as_deref(), Some("m-1"));
+        assert!(!EventMessage::new("Hello".to_string(), "e".to_string()).to_json().unwrap().contains("id"));
+    }
+
+    #[test]
+    fn test_event_message_request_fields() {
+        let json = r#"{"msg":"42","event_name":"get_user","reply_to":"get_user.reply","correlation_id":"c-1"}"#;
+        let msg = EventMessage::from_json(json).unwrap();
+        assert_eq!(msg.reply_to.as_deref(), Some("get_user.reply"));
+        assert_eq!(msg.correlation_id.as_deref(), Some("c-1"));
+        assert!(msg.to_json().unwrap().contains(r#""correlation_id":"c-1""#));
+    }
+
+    #[test]
+    fn test_event_message_expiry() {
+        let received = Instant::now();
+        let default_ttl = Some(Duration::from_secs(60));
+        let msg = |json: &str| EventMessage::from_json(json).unwrap();
+        
+        assert_eq!(msg(r#"{"msg":"a","event_name":"e"}"#).expiry(received, None), None);
+        assert_eq!(
+            msg(r#"{"msg":"a","event_name":"e"}"#).expiry(received, default_ttl),
+            Some(received + Duration::from_secs(60)),
+        );
+        assert_eq!(
+            msg(r#"{"msg":"a","event_name":"e","ttl_ms":500}"#).expiry(received, default_ttl),
+            Some(received + Duration::from_millis(500)),
+        );
+        // An expires_at in the past wins over a longer ttl_ms and the default
+        assert_eq!(
+            msg(r#"{"msg":"a","event_name":"e","expires_at":1000,"ttl_ms":500}"#).expiry(received, default_ttl),
+            Some(received),
+        );
+        let far = msg(r#"{"msg":"a","event_name":"e","expires_at":99999999999999}"#);
+        assert!(far.expiry(received, default_ttl).unwrap() > received + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_event_message_times_out_of_range() {
+        let received = Instant::now();
+        let msg = |json: &str| EventMessage::from_json(json).unwrap();
+        
+        assert!(msg(r#"{"msg":"a","event_name":"e","expires_at":99999999999999,"delay_ms":900000}"#)
+            .check_times(received).is_ok());
+        // Whether the largest times fit depends on the platform; either way nothing panics
+        for field in ["expires_at", "deliver_at", "ttl_ms", "delay_ms"] {
+            let message = msg(&format!(r#"{{"msg":"a","event_name":"e","{}":{}}}"#, field, u64::MAX));
+            match message.check_times(received) {
+                Ok(()) => assert!(message.expiry(received, None).is_some() || message.delivery_delay().is_some()),
+                Err(e) => assert_eq!(e, format!("{} {} is out of range", field, u64::MAX)),
+            }
+            message.expiry(received, None);
+            message.delivery_delay();
+        }
+    }
+
+    #[test]
+    fn test_event_message_delivery_delay() {
+        let msg = |json: &str| EventMessage::from_json(json).unwrap();
+        
+        assert_eq!(msg(r#"{"msg":"a","event_name":"e"}"#).delivery_delay(), None);
+        assert_eq!(msg(r#"{"msg":"a","event_name":"e","delay_ms":0}"#).delivery_delay(), None);
+        assert_eq!(msg(r#"{"msg":"a","event_name":"e","deliver_at":1000}"#).delivery_delay(), None);
+        assert_eq!(
+            msg(r#"{"msg":"a","event_name":"e","deliver_at":1000,"delay_ms":900000}"#).delivery_delay(),
+            Some(Duration::from_secs(900)),
+        );
+        let later = msg(r#"{"msg":"a","event_name":"e","deliver_at":99999999999999,"delay_ms":900000}"#);
+        assert!(later.delivery_delay().unwrap() > Duration::from_secs(900));
+    }
+
+    #[test]
+    fn test_event_message_headers() {
+        let msg = EventMessage::from_json(r#"{"msg":"a","event_name":"e","headers":{"idempotency-key":"k-1"}}"#).unwrap();
+        assert_eq!(msg.headers["idempotency-key"], "k-1");
+        assert!(!EventMessage::new("a".to_string(), "e".to_string()).to_json().unwrap().contains("headers"));
+    }
+
+    #[test]
+    fn test_event_message_retain_flag() {
+        let msg = EventMessage::from_json(r#"{"msg":"42","event_name":"price","retain":true}"#).unwrap();
+        assert!(msg.retain);
+        assert!(!EventMessage::new("42".to_string(), "price".to_string()).to_json().unwrap().contains("retain"));
+    }
+
     #[test]
     fn test_event_message_roundtrip_json() {
         let original = EventMessage::new("Test Message".to_string(), "my_event".to_string());
@@ -10,27 +10,40 @@ pub mod clients;
 pub mod producers;
 pub mod message;
 pub mod registration_server;
+pub mod registration_protocol;
+pub mod registration_auth;
+pub mod destination_policy;
+pub mod shutdown;
+pub mod server_handle;
+pub mod ack;
+pub mod request_reply;
+pub mod push;
+pub mod reactor;
+pub mod rate_limit;
 
-pub use transport_server::TransportServer;
-pub use transport_interface::MessageConsumer;
-pub use protocols::TcpTransport;
 pub use server_config::ServerConfig;
 pub use server::Server;
+pub use server_handle::{ServerHandle, set_running_server};
 pub use client_interface::Client;
-pub use clients::TcpClient;
 pub use producers::{
-    Producer,
     ProducerPool,
     get_producer_pool,
-    init_producer_pool,
     ClientFactory,
-    add_tcp_producer,
-    add_tcp_producer_with_timeout,
-    forward_to_producer,
-    forward_to_all_producers,
+    ProducerUri,
+    start_lease_reaper,
+    start_redelivery,
+    DEFAULT_REDELIVERY_INTERVAL,
+    DeadLetter,
+    EXPIRED_REASON,
+    DedupRule,
+    start_scheduler,
+    DEFAULT_SCHEDULER_INTERVAL,
 };
 
 // Re-export bind_transport functions
-pub use bind_transaport::{bind_transport, bind_transport_with_address, bind_transport_with_config};
+pub use bind_transaport::bind_transport_with_config;
 pub use message::EventMessage;
-pub use registration_server::{start_registration_server, start_registration_server_default};
\ No newline at end of file
+pub use registration_server::start_registration_server_with_config;
+pub use registration_auth::{RegistrationAuth, TokenScope};
+pub use rate_limit::{RateLimit, RateLimitAction};
+pub use destination_policy::{DestinationPolicy, set_destination_policy};
\ No newline at end of file
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// Number of dead letters kept for inspection
+pub const DEFAULT_DEAD_LETTER_CAPACITY: usize = 1_000;
+
+/// Reason recorded for messages dropped because their TTL ran out
+pub const EXPIRED_REASON: &str = "expired";
+
+/// Message that was given up on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadLetter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub event_name: String,
+    /// Producer the message was meant for; `None` when it was dropped before routing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer_id: Option<String>,
+    pub message: String,
+    pub reason: String,
+}
+
+/// Most recent dead letters, with counts of everything dropped since startup
+pub struct DeadLetterStore {
+    letters: Mutex<VecDeque<DeadLetter>>,
+    capacity: usize,
+    total: AtomicU64,
+    expired: AtomicU64,
+}
+
+impl DeadLetterStore {
+    /// Create a store keeping up to `capacity` dead letters
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            letters: Mutex::new(VecDeque::new()),
+            capacity,
+            total: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+        }
+    }
+
+    /// Add a dead letter, dropping the oldest one when full
+    pub fn push(&self, letter: DeadLetter) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if letter.reason == EXPIRED_REASON {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut letters = self.letters.lock().unwrap();
+        letters.push_back(letter);
+        while letters.len() > self.capacity {
+            letters.pop_front();
+        }
+    }
+
+    /// Get up to `limit` dead letters, newest first
+    pub fn recent(&self, limit: usize) -> Vec<DeadLetter> {
+        self.letters.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Number of messages dead-lettered since startup
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped because they expired
+    pub fn expired(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for DeadLetterStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEAD_LETTER_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter(message: &str, reason: &str) -> DeadLetter {
+        DeadLetter {
+            message_id: None,
+            event_name: "alerts".to_string(),
+            producer_id: Some("pager".to_string()),
+            message: message.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_store_is_bounded_and_counts_drops() {
+        let store = DeadLetterStore::new(2);
+        store.push(letter("a", EXPIRED_REASON));
+        store.push(letter("b", "connection refused"));
+        store.push(letter("c", EXPIRED_REASON));
+
+        let messages: Vec<String> = store.recent(10).into_iter().map(|letter| letter.message).collect();
+        assert_eq!(messages, vec!["c", "b"]);
+        assert_eq!(store.recent(1).len(), 1);
+        assert_eq!(store.total(), 3);
+        assert_eq!(store.expired(), 2);
+    }
+}
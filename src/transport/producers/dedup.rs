@@ -0,0 +1,195 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::transport::message::EventMessage;
+
+/// Duplicate suppression settings of one event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupRule {
+    /// How long after a key is first seen messages with the same key are dropped
+    pub window: Duration,
+    /// Header holding the key; the message id is used when not set
+    pub header: Option<String>,
+}
+
+impl DedupRule {
+    /// Get the key identifying a message under this rule, if it has one
+    pub fn key<'a>(&self, message: &'a EventMessage) -> Option<&'a str> {
+        match &self.header {
+            Some(header) => message.headers.get(header).map(String::as_str),
+            None => message.id.as_deref(),
+        }
+    }
+}
+
+/// Keys seen on one event, oldest first so expired ones can be pruned from the front
+#[derive(Default)]
+struct SeenKeys {
+    first_seen: HashMap<String, Instant>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl SeenKeys {
+    fn prune(&mut self, window: Duration, now: Instant) {
+        while let Some((seen, key)) = self.order.front() {
+            if now.duration_since(*seen) < window {
+                break;
+            }
+            if self.first_seen.get(key) == Some(seen) {
+                self.first_seen.remove(key);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+/// Drops messages whose key was already seen within their event's window
+#[derive(Default)]
+pub struct DedupFilter {
+    rules: Mutex<HashMap<String, DedupRule>>,
+    seen: Mutex<HashMap<String, SeenKeys>>,
+    suppressed: AtomicU64,
+}
+
+impl DedupFilter {
+    /// Create a filter without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the rules per event; keys seen on events without a rule are forgotten
+    pub fn set_rules(&self, rules: HashMap<String, DedupRule>) {
+        self.seen.lock().unwrap().retain(|event, _| rules.contains_key(event));
+        *self.rules.lock().unwrap() = rules;
+    }
+
+    /// Check whether a message is a duplicate to suppress, counting it if so
+    ///
+    /// Messages on events without a rule, or without a key, are never duplicates.
+    /// Only messages passed to `record` count as seen.
+    pub fn is_duplicate(&self, message: &EventMessage, now: Instant) -> bool {
+        let Some(rule) = self.rules.lock().unwrap().get(&message.event_name).cloned() else {
+            return false;
+        };
+        let Some(key) = rule.key(message) else {
+            return false;
+        };
+
+        let mut seen = self.seen.lock().unwrap();
+        let Some(keys) = seen.get_mut(&message.event_name) else {
+            return false;
+        };
+        keys.prune(rule.window, now);
+        if keys.first_seen.contains_key(key) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Record a message that was accepted, starting its key's window at `now`
+    pub fn record(&self, message: &EventMessage, now: Instant) {
+        let Some(rule) = self.rules.lock().unwrap().get(&message.event_name).cloned() else {
+            return;
+        };
+        let Some(key) = rule.key(message) else {
+            return;
+        };
+
+        let mut seen = self.seen.lock().unwrap();
+        let keys = seen.entry(message.event_name.clone()).or_default();
+        keys.prune(rule.window, now);
+        if !keys.first_seen.contains_key(key) {
+            keys.first_seen.insert(key.to_string(), now);
+            keys.order.push_back((now, key.to_string()));
+        }
+    }
+
+    /// Number of duplicates suppressed since startup
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(event: &str, id: Option<&str>, header: Option<&str>) -> EventMessage {
+        let mut message = EventMessage::new("charge".to_string(), event.to_string());
+        message.id = id.map(str::to_string);
+        if let Some(value) = header {
+            message.headers.insert("idempotency-key".to_string(), value.to_string());
+        }
+        message
+    }
+
+    fn filter() -> DedupFilter {
+        let filter = DedupFilter::new();
+        filter.set_rules(HashMap::from([
+            ("payments".to_string(), DedupRule { window: Duration::from_secs(60), header: None }),
+            ("orders".to_string(), DedupRule {
+                window: Duration::from_secs(60),
+                header: Some("idempotency-key".to_string()),
+            }),
+        ]));
+        filter
+    }
+
+    /// Check a message and record it when it is not a duplicate, as routing an accepted message does
+    fn route(filter: &DedupFilter, message: &EventMessage, now: Instant) -> bool {
+        let duplicate = filter.is_duplicate(message, now);
+        if !duplicate {
+            filter.record(message, now);
+        }
+        duplicate
+    }
+
+    #[test]
+    fn test_duplicates_within_window() {
+        let filter = filter();
+        let now = Instant::now();
+
+        assert!(!route(&filter, &message("payments", Some("p-1"), None), now));
+        assert!(route(&filter, &message("payments", Some("p-1"), None), now + Duration::from_secs(59)));
+        assert!(!route(&filter, &message("payments", Some("p-1"), None), now + Duration::from_secs(60)));
+
+        // Only events with a rule, and messages with a key, are checked
+        assert!(!route(&filter, &message("payments", None, None), now));
+        assert!(!route(&filter, &message("payments", None, None), now));
+        assert!(!route(&filter, &message("other", Some("p-1"), None), now));
+        assert!(!route(&filter, &message("other", Some("p-1"), None), now));
+        assert_eq!(filter.suppressed(), 1);
+    }
+
+    #[test]
+    fn test_header_key() {
+        let filter = filter();
+        let now = Instant::now();
+
+        assert!(!route(&filter, &message("orders", Some("a"), Some("k-1")), now));
+        assert!(route(&filter, &message("orders", Some("b"), Some("k-1")), now));
+        assert!(!route(&filter, &message("orders", Some("a"), Some("k-2")), now));
+        assert!(!route(&filter, &message("orders", Some("a"), None), now));
+        assert_eq!(filter.suppressed(), 1);
+    }
+
+    #[test]
+    fn test_only_recorded_messages_are_seen() {
+        let filter = filter();
+        let now = Instant::now();
+        let payment = message("payments", Some("p-1"), None);
+
+        // A message that was checked but not accepted can be retried
+        assert!(!filter.is_duplicate(&payment, now));
+        assert!(!filter.is_duplicate(&payment, now));
+        filter.record(&payment, now);
+        assert!(filter.is_duplicate(&payment, now + Duration::from_secs(1)));
+
+        // Recording again doesn't extend the window
+        filter.record(&payment, now + Duration::from_secs(30));
+        assert!(!filter.is_duplicate(&payment, now + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::producer_pool::ProducerPool;
+
+/// Default time a consumer has to acknowledge a message
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of delivery attempts before a message is dead-lettered
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Default delay between redelivery attempts
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default interval between redelivery sweeps
+pub const DEFAULT_REDELIVERY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of messages whose delivery receipts are kept for queries
+pub const DEFAULT_RECEIPT_CAPACITY: usize = 10_000;
+
+/// At-least-once delivery settings of a producer
+///
+/// Each message is sent as `MSG <id> <message>` and counts as delivered only
+/// once the consumer replies `ACK <id>` on the same connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryPolicy {
+    /// Time to wait for the consumer's `ACK <id>` line
+    pub ack_timeout: Duration,
+    /// Attempts (including the first) before the message is dead-lettered
+    pub max_attempts: u32,
+    /// Delay before an unacknowledged message is sent again
+    pub retry_interval: Duration,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self {
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+}
+
+/// Outcome of delivering one message to one producer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not acknowledged yet, waiting for redelivery
+    Pending { attempts: u32 },
+    /// Acknowledged on the first attempt
+    Delivered,
+    /// Acknowledged after being sent more than once
+    Redelivered { attempts: u32 },
+    /// Given up on after the last attempt
+    DeadLettered { attempts: u32, reason: String },
+}
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryStatus::Pending { attempts } => write!(f, "pending ({} attempt(s))", attempts),
+            DeliveryStatus::Delivered => write!(f, "delivered"),
+            DeliveryStatus::Redelivered { attempts } => write!(f, "redelivered ({} attempts)", attempts),
+            DeliveryStatus::DeadLettered { attempts, reason } => {
+                write!(f, "dead-lettered after {} attempt(s): {}", attempts, reason)
+            }
+        }
+    }
+}
+
+/// Message waiting to be sent again to one producer
+#[derive(Debug, Clone)]
+pub struct PendingDelivery {
+    pub message_id: String,
+    pub producer_id: String,
+    pub event_name: String,
+    pub message: String,
+    pub attempts: u32,
+    /// Instant after which the message is dropped instead of redelivered
+    pub expires_at: Option<Instant>,
+}
+
+impl PendingDelivery {
+    /// Check whether the message's TTL has run out
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Receipts per message id, oldest first so the store stays bounded
+#[derive(Default)]
+struct Receipts {
+    by_message: HashMap<String, BTreeMap<String, DeliveryStatus>>,
+    order: VecDeque<String>,
+}
+
+/// Delivery receipts and the redelivery queue for at-least-once producers
+pub struct DeliveryTracker {
+    receipts: Mutex<Receipts>,
+    // Next attempt -> delivery
+    pending: Mutex<Vec<(Instant, PendingDelivery)>>,
+    next_id: AtomicU64,
+    capacity: usize,
+}
+
+impl DeliveryTracker {
+    /// Create a tracker keeping receipts for up to `capacity` messages
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            receipts: Mutex::new(Receipts::default()),
+            pending: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            capacity,
+        }
+    }
+
+    /// Generate an id for a message published without one
+    pub fn generate_id(&self) -> String {
+        format!("auto-{}", self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Record the delivery status of a message for one producer
+    pub fn record(&self, message_id: &str, producer_id: &str, status: DeliveryStatus) {
+        let mut receipts = self.receipts.lock().unwrap();
+        if !receipts.by_message.contains_key(message_id) {
+            receipts.order.push_back(message_id.to_string());
+            while receipts.order.len() > self.capacity {
+                if let Some(oldest) = receipts.order.pop_front() {
+                    receipts.by_message.remove(&oldest);
+                }
+            }
+        }
+
+        receipts.by_message
+            .entry(message_id.to_string())
+            .or_default()
+            .insert(producer_id.to_string(), status);
+    }
+
+    /// Get the delivery status of a message per producer
+    pub fn receipt(&self, message_id: &str) -> Option<BTreeMap<String, DeliveryStatus>> {
+        self.receipts.lock().unwrap().by_message.get(message_id).cloned()
+    }
+
+    /// Queue a message for another attempt after `delay`
+    pub fn schedule(&self, delivery: PendingDelivery, delay: Duration) {
+        self.record(&delivery.message_id, &delivery.producer_id, DeliveryStatus::Pending {
+            attempts: delivery.attempts,
+        });
+        self.pending.lock().unwrap().push((Instant::now() + delay, delivery));
+    }
+
+    /// Remove and return the deliveries due or expired at the given instant
+    pub fn take_due(&self, now: Instant) -> Vec<PendingDelivery> {
+        let mut pending = self.pending.lock().unwrap();
+        let (due, waiting): (Vec<_>, Vec<_>) = pending.drain(..)
+            .partition(|(next_attempt, delivery)| *next_attempt <= now || delivery.is_expired_at(now));
+        *pending = waiting;
+        due.into_iter().map(|(_, delivery)| delivery).collect()
+    }
+}
+
+impl Default for DeliveryTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECEIPT_CAPACITY)
+    }
+}
+
+/// Start a background thread that redelivers unacknowledged messages
+///
+/// The thread only hands due messages to the pool's fan-out threads, one
+/// batch per producer, so a slow consumer doesn't delay the others.
+pub fn start_redelivery(pool: Arc<ProducerPool>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        pool.spawn_redeliveries();
+    });
+}
+
+/// Log the final outcome of a redelivery attempt
+pub(crate) fn log_redelivery(message_id: &str, producer_id: &str, status: &DeliveryStatus) {
+    match status {
+        DeliveryStatus::DeadLettered { .. } => eprintln!(
+            "[Redelivery] Message '{}' to producer '{}' {}", message_id, producer_id, status
+        ),
+        DeliveryStatus::Redelivered { .. } => println!(
+            "[Redelivery] Message '{}' to producer '{}' {}", message_id, producer_id, status
+        ),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipts_are_bounded() {
+        let tracker = DeliveryTracker::new(2);
+        tracker.record("m-1", "audit", DeliveryStatus::Delivered);
+        tracker.record("m-1", "metrics", DeliveryStatus::Pending { attempts: 1 });
+        tracker.record("m-2", "audit", DeliveryStatus::Delivered);
+        assert_eq!(tracker.receipt("m-1").unwrap().len(), 2);
+
+        tracker.record("m-3", "audit", DeliveryStatus::Delivered);
+        assert!(tracker.receipt("m-1").is_none());
+        assert!(tracker.receipt("m-2").is_some());
+        assert_ne!(tracker.generate_id(), tracker.generate_id());
+    }
+
+    fn pending(message_id: &str, expires_at: Option<Instant>) -> PendingDelivery {
+        PendingDelivery {
+            message_id: message_id.to_string(),
+            producer_id: "audit".to_string(),
+            event_name: "orders".to_string(),
+            message: "hello".to_string(),
+            attempts: 1,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_take_due() {
+        let tracker = DeliveryTracker::default();
+        tracker.schedule(pending("m-1", None), Duration::ZERO);
+        tracker.schedule(pending("m-2", None), Duration::from_secs(60));
+
+        let due = tracker.take_due(Instant::now());
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message_id, "m-1");
+        assert!(tracker.take_due(Instant::now()).is_empty());
+        assert_eq!(tracker.receipt("m-2").unwrap()["audit"], DeliveryStatus::Pending { attempts: 1 });
+    }
+
+    #[test]
+    fn test_expired_delivery_is_due_early() {
+        let tracker = DeliveryTracker::default();
+        let now = Instant::now();
+        tracker.schedule(pending("m-1", Some(now)), Duration::from_secs(60));
+        tracker.schedule(pending("m-2", Some(now + Duration::from_secs(600))), Duration::from_secs(60));
+
+        let due = tracker.take_due(now);
+        assert_eq!(due.len(), 1);
+        assert!(due[0].is_expired_at(now));
+        assert!(!pending("m-3", None).is_expired_at(now));
+    }
+}
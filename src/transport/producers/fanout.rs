@@ -0,0 +1,292 @@
+//! Concurrent delivery of one message to every subscriber of an event
+//!
+//! Each delivery runs on a shared pool of threads, so a broadcast takes as
+//! long as its slowest subscriber instead of the sum of all of them. The
+//! caller waits until every delivery finished or the deadline passed;
+//! deliveries still running at the deadline are reported as timed out and
+//! their results are discarded when they finish.
+//!
+//! Deliveries get the deadline so clients can bound their own sends by it,
+//! but a send that already reached the subscriber's socket can't be taken
+//! back: a delivery reported as timed out may still arrive. A subscriber
+//! whose previous delivery is still running gets no new one until it
+//! finishes, so a stuck subscriber ties up at most one thread.
+
+use std::collections::HashSet;
+use std::io::{ErrorKind, Result};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default number of threads delivering messages to subscribers
+pub const DEFAULT_FANOUT_THREADS: usize = 16;
+
+/// Default time a broadcast waits for its deliveries
+pub const DEFAULT_FANOUT_DEADLINE: Duration = Duration::from_secs(10);
+
+/// A delivery to one subscriber, given the instant by which it should finish
+pub type Delivery = Box<dyn FnOnce(Instant) -> Result<()> + Send>;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Fan-out settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FanOutConfig {
+    /// Threads shared by all broadcasts; only read when they are started
+    pub threads: usize,
+    /// How long a broadcast waits for all of its deliveries
+    pub deadline: Duration,
+}
+
+impl Default for FanOutConfig {
+    fn default() -> Self {
+        Self {
+            threads: DEFAULT_FANOUT_THREADS,
+            deadline: DEFAULT_FANOUT_DEADLINE,
+        }
+    }
+}
+
+/// Runs the deliveries of a broadcast concurrently with an overall deadline
+///
+/// Threads are started by the first broadcast and stop when the executor is dropped.
+#[derive(Default)]
+pub struct FanOut {
+    config: Mutex<FanOutConfig>,
+    queue: Mutex<Option<Sender<Task>>>,
+    // Ids of subscribers with a delivery queued or running
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Releases a subscriber's in-flight slot when its delivery is done or dropped
+struct InFlight {
+    id: String,
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl FanOut {
+    /// Create an executor with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the settings; a new thread count only applies before the first broadcast
+    pub fn set_config(&self, config: FanOutConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Get the current settings
+    pub fn config(&self) -> FanOutConfig {
+        *self.config.lock().unwrap()
+    }
+
+    /// Run every delivery and collect the results in the order they were given
+    ///
+    /// A delivery that hasn't finished by the deadline gets a `TimedOut` error,
+    /// and one still queued at the deadline is not attempted at all. A delivery
+    /// to a subscriber whose previous one is still in flight is not attempted
+    /// either and gets a `WouldBlock` error.
+    pub fn run(&self, deliveries: Vec<(String, Delivery)>) -> Vec<(String, Result<()>)> {
+        let config = self.config();
+        let deadline = Instant::now() + config.deadline;
+        let (done, results) = mpsc::channel();
+
+        let mut ids = Vec::with_capacity(deliveries.len());
+        for (index, (id, delivery)) in deliveries.into_iter().enumerate() {
+            let Some(slot) = self.claim(&id) else {
+                let _ = done.send((index, Err(Self::busy(&id))));
+                ids.push(id);
+                continue;
+            };
+            let sender = done.clone();
+            let task: Task = Box::new(move || {
+                let result = if Instant::now() < deadline {
+                    delivery(deadline)
+                } else {
+                    Err(Self::timed_out(config.deadline))
+                };
+                drop(slot);
+                let _ = sender.send((index, result));
+            });
+            if let Err(e) = self.dispatch(task) {
+                let _ = done.send((index, Err(e)));
+            }
+            ids.push(id);
+        }
+        drop(done);
+
+        let mut outcomes: Vec<Option<Result<()>>> = ids.iter().map(|_| None).collect();
+        let mut pending = ids.len();
+        while pending > 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match results.recv_timeout(remaining) {
+                Ok((index, result)) => {
+                    outcomes[index] = Some(result);
+                    pending -= 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        ids.into_iter()
+            .zip(outcomes)
+            .map(|(id, outcome)| (id, outcome.unwrap_or_else(|| Err(Self::timed_out(config.deadline)))))
+            .collect()
+    }
+
+    /// Run a task in the background without waiting for it
+    ///
+    /// A task for a subscriber that already has one in flight is dropped
+    /// without running.
+    pub fn spawn(&self, id: &str, task: impl FnOnce() + Send + 'static) {
+        let Some(slot) = self.claim(id) else {
+            return;
+        };
+        let task: Task = Box::new(move || {
+            task();
+            drop(slot);
+        });
+        if let Err(e) = self.dispatch(task) {
+            eprintln!("[Fan-out] Failed to start a task for '{}': {}", id, e);
+        }
+    }
+
+    /// Take the subscriber's in-flight slot, if it is free
+    fn claim(&self, id: &str) -> Option<InFlight> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        in_flight.insert(id.to_string()).then(|| InFlight {
+            id: id.to_string(),
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+
+    fn busy(id: &str) -> std::io::Error {
+        std::io::Error::new(
+            ErrorKind::WouldBlock,
+            format!("previous delivery to '{}' is still in flight", id),
+        )
+    }
+
+    fn timed_out(deadline: Duration) -> std::io::Error {
+        std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!("delivery did not finish within {} ms", deadline.as_millis()),
+        )
+    }
+
+    /// Queue a task, starting the threads on first use
+    fn dispatch(&self, task: Task) -> Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.is_none() {
+            *queue = Some(Self::start(self.config().threads)?);
+        }
+        queue.as_ref()
+            .unwrap()
+            .send(task)
+            .map_err(|_| std::io::Error::other("fan-out threads have stopped"))
+    }
+
+    fn start(threads: usize) -> Result<Sender<Task>> {
+        let (sender, tasks) = mpsc::channel();
+        let tasks = Arc::new(Mutex::new(tasks));
+        for index in 0..threads.max(1) {
+            let tasks = Arc::clone(&tasks);
+            thread::Builder::new()
+                .name(format!("fanout-{}", index))
+                .spawn(move || Self::work(&tasks))?;
+        }
+        Ok(sender)
+    }
+
+    fn work(tasks: &Mutex<Receiver<Task>>) {
+        loop {
+            let task = match tasks.lock().unwrap().recv() {
+                Ok(task) => task,
+                Err(_) => return,
+            };
+            task();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sleeper(millis: u64) -> Delivery {
+        Box::new(move |_| {
+            thread::sleep(Duration::from_millis(millis));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_deliveries_run_concurrently_in_order() {
+        let fanout = FanOut::new();
+        let started = Instant::now();
+        let results = fanout.run(vec![
+            ("a".to_string(), sleeper(300)),
+            ("b".to_string(), Box::new(|_| Err(std::io::Error::other("refused")))),
+            ("c".to_string(), sleeper(300)),
+            ("d".to_string(), sleeper(300)),
+        ]);
+
+        // One after another would take 900 ms
+        assert!(started.elapsed() < Duration::from_millis(700));
+        let ids = results.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+        assert!(results[0].1.is_ok());
+        assert_eq!(results[1].1.as_ref().unwrap_err().to_string(), "refused");
+    }
+
+    #[test]
+    fn test_slow_deliveries_time_out_at_the_deadline() {
+        let fanout = FanOut::new();
+        fanout.set_config(FanOutConfig { threads: 1, deadline: Duration::from_millis(100) });
+
+        let started = Instant::now();
+        let results = fanout.run(vec![
+            ("fast".to_string(), sleeper(0)),
+            ("slow".to_string(), sleeper(1000)),
+            // Still queued behind the slow one at the deadline
+            ("queued".to_string(), sleeper(0)),
+        ]);
+
+        assert!(started.elapsed() < Duration::from_millis(700));
+        assert!(results[0].1.is_ok());
+        for (_, result) in &results[1..] {
+            assert_eq!(result.as_ref().unwrap_err().kind(), ErrorKind::TimedOut);
+        }
+    }
+
+    #[test]
+    fn test_subscriber_with_a_delivery_in_flight_is_skipped() {
+        let fanout = FanOut::new();
+        fanout.set_config(FanOutConfig { threads: 4, deadline: Duration::from_millis(100) });
+
+        let first = fanout.run(vec![("slow".to_string(), sleeper(500))]);
+        assert_eq!(first[0].1.as_ref().unwrap_err().kind(), ErrorKind::TimedOut);
+
+        // The timed-out delivery is still running, so only the other subscriber gets this one
+        let second = fanout.run(vec![
+            ("slow".to_string(), sleeper(0)),
+            ("other".to_string(), sleeper(0)),
+        ]);
+        assert_eq!(second[0].1.as_ref().unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert!(second[1].1.is_ok());
+        let (ran, spawned) = mpsc::channel();
+        fanout.spawn("slow", move || ran.send(()).unwrap());
+        assert!(spawned.recv_timeout(Duration::from_millis(50)).is_err());
+
+        thread::sleep(Duration::from_millis(600));
+        let third = fanout.run(vec![("slow".to_string(), sleeper(0))]);
+        assert!(third[0].1.is_ok());
+    }
+}
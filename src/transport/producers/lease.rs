@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::producer_pool::ProducerPool;
+
+/// Default interval between lease reaper sweeps
+pub const DEFAULT_REAPER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Registration lease that must be renewed with heartbeats before it expires
+#[derive(Debug, Clone, Copy)]
+pub struct Lease {
+    ttl: Duration,
+    expires_at: Instant,
+}
+
+impl Lease {
+    /// Create a new lease starting now
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            expires_at: Instant::now() + ttl,
+        }
+    }
+
+    /// Extend the lease by its TTL starting now
+    pub fn renew(&mut self) {
+        self.expires_at = Instant::now() + self.ttl;
+    }
+
+    /// Check if the lease has expired at the given instant
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Get the lease TTL
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Get the time left before the lease expires
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Start a background thread that evicts producers whose lease has expired
+pub fn start_lease_reaper(pool: Arc<ProducerPool>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        for id in pool.evict_expired() {
+            println!("[Lease Reaper] Producer '{}' lease expired, evicted from pool", id);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_expiry() {
+        let lease = Lease::new(Duration::from_secs(30));
+        assert!(!lease.is_expired_at(Instant::now()));
+        assert!(lease.is_expired_at(Instant::now() + Duration::from_secs(31)));
+    }
+
+    #[test]
+    fn test_lease_renew() {
+        let mut lease = Lease::new(Duration::from_millis(0));
+        assert!(lease.is_expired_at(Instant::now()));
+
+        lease.ttl = Duration::from_secs(30);
+        lease.renew();
+        assert!(!lease.is_expired_at(Instant::now()));
+        assert_eq!(lease.ttl(), Duration::from_secs(30));
+    }
+}
@@ -4,14 +4,22 @@ pub mod producer_registry;
 pub mod producer_factory;
 pub mod producer_helpers;
 pub mod observer;
+pub mod lease;
+pub mod producer_uri;
+pub mod delivery;
+pub mod dead_letter;
+pub mod scheduler;
+pub mod dedup;
+pub mod fanout;
+pub mod routing_table;
 
 pub use producer::Producer;
 pub use producer_pool::ProducerPool;
-pub use producer_registry::{get_producer_pool, init_producer_pool};
+pub use producer_registry::get_producer_pool;
 pub use producer_factory::ClientFactory;
-pub use producer_helpers::{
-    add_tcp_producer,
-    add_tcp_producer_with_timeout,
-    forward_to_producer,
-    forward_to_all_producers,
-};
+pub use producer_uri::ProducerUri;
+pub use lease::{start_lease_reaper, DEFAULT_REAPER_INTERVAL};
+pub use delivery::{DeliveryStatus, start_redelivery, DEFAULT_REDELIVERY_INTERVAL};
+pub use dead_letter::{DeadLetter, EXPIRED_REASON};
+pub use dedup::DedupRule;
+pub use scheduler::{start_scheduler, DEFAULT_SCHEDULER_INTERVAL};
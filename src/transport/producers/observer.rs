@@ -14,6 +14,9 @@ pub trait Observer: Send + Sync {
 }
 
 /// Event subscriptions for an observer
+///
+/// Clones share the same underlying subscription set.
+#[derive(Clone)]
 pub struct EventSubscriptions {
     events: Arc<Mutex<HashSet<String>>>,
 }
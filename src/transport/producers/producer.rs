@@ -1,7 +1,10 @@
 use std::io::Result;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::transport::client_interface::Client;
+use crate::transport::request_reply::ConnectionWriter;
+use super::delivery::DeliveryPolicy;
 use super::observer::{Observer, EventSubscriptions};
 
 /// Producer that forwards messages to external consumers using an injected client
@@ -22,11 +25,42 @@ impl Producer {
         }
     }
 
+    /// Create a copy of this producer that sends through a different client
+    /// while sharing the same event subscriptions
+    pub fn with_client(&self, client: Arc<dyn Client>) -> Self {
+        Self {
+            client,
+            id: self.id.clone(),
+            subscriptions: self.subscriptions.clone(),
+        }
+    }
+
     /// Forward a message to the external consumer
     pub fn forward(&self, message: &str) -> Result<()> {
         self.client.send(message)
     }
 
+    /// Forward a message identified by `id`, waiting for an acknowledgement
+    /// if the client uses at-least-once delivery
+    pub fn forward_with_id(&self, id: &str, message: &str) -> Result<()> {
+        self.client.send_with_id(id, message)
+    }
+
+    /// Forward a message, with an id if it has one, giving up at `deadline`
+    /// if the client can bound its sends
+    pub fn forward_before(&self, id: Option<&str>, message: &str, deadline: Instant) -> Result<()> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout == Duration::ZERO {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "send deadline passed"));
+        }
+        self.client.send_within(id, message, timeout)
+    }
+
+    /// Get the client's at-least-once delivery policy, if any
+    pub fn delivery_policy(&self) -> Option<DeliveryPolicy> {
+        self.client.delivery_policy()
+    }
+
     /// Subscribe to an event
     pub fn subscribe(&self, event_name: &str) {
         self.subscriptions.subscribe(event_name);
@@ -62,6 +96,11 @@ impl Producer {
         self.client.protocol_name()
     }
 
+    /// Get the connection the consumer opened itself, if messages are pushed on one
+    pub fn connection(&self) -> Option<ConnectionWriter> {
+        self.client.connection()
+    }
+
     /// Get the target address
     pub fn address(&self) -> &str {
         self.client.address()
@@ -189,4 +228,25 @@ mod tests {
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0], "observer message");
     }
+
+    #[test]
+    fn test_producer_with_client_shares_subscriptions() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let producer = Producer::new("test-producer".to_string(), Arc::new(MockClient {
+            sent_messages: Arc::new(std::sync::Mutex::new(Vec::new())),
+        }));
+        producer.subscribe("event1");
+        
+        let swapped = producer.with_client(Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent),
+        }));
+        assert_eq!(swapped.id(), "test-producer");
+        assert!(swapped.is_subscribed("event1"));
+        
+        swapped.subscribe("event2");
+        assert!(producer.is_subscribed("event2"));
+        
+        swapped.forward("via new client").unwrap();
+        assert_eq!(sent.lock().unwrap().len(), 1);
+    }
 }
\ No newline at end of file
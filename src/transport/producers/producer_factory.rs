@@ -3,19 +3,42 @@ use std::sync::Arc;
 
 use crate::transport::client_interface::Client;
 use crate::transport::clients::TcpClient;
+use super::producer_uri::ProducerUri;
 
 /// Factory for creating clients based on protocol type
 pub struct ClientFactory;
 
 impl ClientFactory {
-    /// Create a client instance based on protocol type
-    pub fn create_tcp_client(address: String) -> Arc<dyn Client> {
-        Arc::new(TcpClient::new(address))
+    /// Create a client from a `protocol://address[?timeout=3s&delivery=at-least-once]` URI,
+    /// with an explicit timeout taking precedence over the URI's
+    pub fn create_from_uri_with_timeout(
+        uri: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<Arc<dyn Client>> {
+        let mut parsed = ProducerUri::parse(uri)?;
+        parsed.timeout = timeout.or(parsed.timeout);
+        Self::create_from_producer_uri(&parsed)
     }
 
-    /// Create a client with custom timeout
-    pub fn create_tcp_client_with_timeout(address: String, timeout: std::time::Duration) -> Arc<dyn Client> {
-        Arc::new(TcpClient::with_timeout(address, timeout))
+    /// Create a client from a parsed producer URI
+    pub fn create_from_producer_uri(uri: &ProducerUri) -> Result<Arc<dyn Client>> {
+        // Create client based on protocol
+        match uri.protocol.as_str() {
+            "TCP" => {
+                let client = match uri.timeout {
+                    Some(timeout) => TcpClient::with_timeout(uri.address.clone(), timeout),
+                    None => TcpClient::new(uri.address.clone()),
+                };
+                Ok(Arc::new(match uri.delivery {
+                    Some(policy) => client.with_delivery(policy),
+                    None => client,
+                }))
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unsupported protocol: {}", uri.protocol.to_lowercase()),
+            )),
+        }
     }
 
     // Future: Add other protocol clients here
@@ -1,27 +1,8 @@
-use std::io::Result;
-use std::sync::Arc;
-
-use super::{ProducerPool, ClientFactory, get_producer_pool};
+//! Helper functions for managing producers in the pool
 
-/// Helper functions for managing producers in the pool
-
-/// Add a TCP producer to the global producer pool
-pub fn add_tcp_producer(id: String, address: String) -> Result<()> {
-    let pool = get_producer_pool();
-    let client = ClientFactory::create_tcp_client(address);
-    pool.add_producer(id, client)
-}
+use std::io::Result;
 
-/// Add a TCP producer with custom timeout
-pub fn add_tcp_producer_with_timeout(
-    id: String, 
-    address: String, 
-    timeout: std::time::Duration
-) -> Result<()> {
-    let pool = get_producer_pool();
-    let client = ClientFactory::create_tcp_client_with_timeout(address, timeout);
-    pool.add_producer(id, client)
-}
+use super::get_producer_pool;
 
 /// Forward a message to a specific producer by ID
 pub fn forward_to_producer(producer_id: &str, message: &str) -> Result<()> {
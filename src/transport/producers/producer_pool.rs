@@ -1,33 +1,251 @@
-use std::collections::{HashMap, HashSet};
-use std::io::Result;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{ErrorKind, Result};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::transport::client_interface::Client;
+use crate::transport::rate_limit::{RateLimitConfig, RateLimiter};
+use super::dedup::{DedupFilter, DedupRule};
+use super::fanout::{Delivery, FanOut, FanOutConfig};
+use super::dead_letter::{DeadLetter, DeadLetterStore, EXPIRED_REASON};
+use super::delivery::{log_redelivery, DeliveryStatus, DeliveryTracker, PendingDelivery};
+use super::lease::Lease;
+use super::scheduler::MessageScheduler;
 use super::producer::Producer;
+use super::routing_table::SharedRoutingTable;
 use super::observer::Observer;
 
 /// Pool of producers for forwarding messages to external consumers
 /// Supports event-based routing using observer pattern
 pub struct ProducerPool {
-    producers: Arc<Mutex<HashMap<String, Arc<Producer>>>>,
-    // Event -> Set of producer IDs subscribed to this event
-    event_subscriptions: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    // Producers and event -> subscribers, read without locking on the routing path
+    routes: Arc<SharedRoutingTable>,
+    // Held while a subscription changes, so each producer's own event list matches `routes`
+    subscription_changes: Arc<Mutex<()>>,
+    // Producer ID -> Lease for producers registered with a TTL
+    leases: Arc<Mutex<HashMap<String, Lease>>>,
+    // Receipts and redelivery queue for at-least-once producers
+    deliveries: Arc<DeliveryTracker>,
+    // Event -> last retained message, sent to every new subscriber
+    retained: Arc<Mutex<HashMap<String, RetainedMessage>>>,
+    // Event -> TTL for messages that don't set their own
+    event_ttls: Arc<Mutex<HashMap<String, Duration>>>,
+    // Messages that expired or ran out of delivery attempts
+    dead_letters: Arc<DeadLetterStore>,
+    // Messages held back until their delivery time
+    scheduler: Arc<MessageScheduler>,
+    // Keys of recently routed messages on events with duplicate suppression
+    dedup: Arc<DedupFilter>,
+    // Token buckets limiting messages from ingress connections
+    rate_limiter: Arc<RateLimiter>,
+    // Threads delivering a broadcast to all subscribers at once
+    fanout: Arc<FanOut>,
+}
+
+/// Makes delivery attempts and records their outcomes, on any thread
+#[derive(Clone)]
+struct Deliverer {
+    tracker: Arc<DeliveryTracker>,
+    dead_letters: Arc<DeadLetterStore>,
+}
+
+impl Deliverer {
+    /// Make the first delivery attempt, tracking it for at-least-once producers
+    ///
+    /// With a deadline, the attempt gives up when it passes.
+    fn deliver(
+        &self,
+        producer: &Producer,
+        event_name: &str,
+        message_id: Option<&str>,
+        message: &str,
+        expires_at: Option<Instant>,
+        deadline: Option<Instant>,
+    ) -> Result<()> {
+        let send = |id: Option<&str>| match deadline {
+            Some(deadline) => producer.forward_before(id, message, deadline),
+            None => id.map_or_else(|| producer.forward(message), |id| producer.forward_with_id(id, message)),
+        };
+        let Some(policy) = producer.delivery_policy() else {
+            return send(None);
+        };
+        
+        let message_id = message_id.map(str::to_string)
+            .unwrap_or_else(|| self.tracker.generate_id());
+        match send(Some(&message_id)) {
+            Ok(()) => {
+                self.tracker.record(&message_id, producer.id(), DeliveryStatus::Delivered);
+                Ok(())
+            }
+            Err(e) if policy.max_attempts > 1 => {
+                eprintln!("[Producer {}] Message '{}' not acknowledged ({}), will redeliver",
+                          producer.id(), message_id, e);
+                self.tracker.schedule(PendingDelivery {
+                    message_id,
+                    producer_id: producer.id().to_string(),
+                    event_name: event_name.to_string(),
+                    message: message.to_string(),
+                    attempts: 1,
+                    expires_at,
+                }, policy.retry_interval);
+                Ok(())
+            }
+            Err(e) => {
+                let status = DeliveryStatus::DeadLettered { attempts: 1, reason: e.to_string() };
+                self.record_outcome(&message_id, producer.id(), event_name, message, status);
+                Err(e)
+            }
+        }
+    }
+
+    /// Queue a message for an at-least-once producer that couldn't take it yet
+    ///
+    /// Fails with `reason` for other producers, which have no redelivery.
+    fn defer(
+        &self,
+        producer: &Producer,
+        event_name: &str,
+        message_id: Option<&str>,
+        message: &str,
+        expires_at: Option<Instant>,
+        reason: std::io::Error,
+    ) -> Result<()> {
+        let Some(policy) = producer.delivery_policy() else {
+            return Err(reason);
+        };
+        self.tracker.schedule(PendingDelivery {
+            message_id: message_id.map(str::to_string).unwrap_or_else(|| self.tracker.generate_id()),
+            producer_id: producer.id().to_string(),
+            event_name: event_name.to_string(),
+            message: message.to_string(),
+            attempts: 0,
+            expires_at,
+        }, policy.retry_interval);
+        Ok(())
+    }
+
+    /// Make another attempt at an unacknowledged message and record its outcome
+    fn redeliver(&self, producer: Option<&Producer>, pending: &PendingDelivery) -> DeliveryStatus {
+        let attempts = pending.attempts + 1;
+        let policy = producer.and_then(Producer::delivery_policy);
+        
+        let status = match (producer, policy) {
+            _ if pending.is_expired_at(Instant::now()) => DeliveryStatus::DeadLettered {
+                attempts: pending.attempts,
+                reason: EXPIRED_REASON.to_string(),
+            },
+            (Some(producer), Some(policy)) => {
+                match producer.forward_with_id(&pending.message_id, &pending.message) {
+                    Ok(()) => DeliveryStatus::Redelivered { attempts },
+                    Err(_) if attempts < policy.max_attempts => {
+                        self.tracker.schedule(PendingDelivery {
+                            attempts,
+                            ..pending.clone()
+                        }, policy.retry_interval);
+                        DeliveryStatus::Pending { attempts }
+                    }
+                    Err(e) => DeliveryStatus::DeadLettered { attempts, reason: e.to_string() },
+                }
+            }
+            (Some(_), None) => DeliveryStatus::DeadLettered {
+                attempts: pending.attempts,
+                reason: "producer no longer uses at-least-once delivery".to_string(),
+            },
+            (None, _) => DeliveryStatus::DeadLettered {
+                attempts: pending.attempts,
+                reason: "producer removed".to_string(),
+            },
+        };
+        
+        self.record_outcome(
+            &pending.message_id,
+            &pending.producer_id,
+            &pending.event_name,
+            &pending.message,
+            status.clone(),
+        );
+        status
+    }
+
+    /// Record a delivery outcome, keeping dead-lettered messages in the dead-letter store
+    fn record_outcome(
+        &self,
+        message_id: &str,
+        producer_id: &str,
+        event_name: &str,
+        message: &str,
+        status: DeliveryStatus,
+    ) {
+        if let DeliveryStatus::DeadLettered { reason, .. } = &status {
+            self.dead_letters.push(DeadLetter {
+                message_id: Some(message_id.to_string()),
+                event_name: event_name.to_string(),
+                producer_id: Some(producer_id.to_string()),
+                message: message.to_string(),
+                reason: reason.clone(),
+            });
+        }
+        self.tracker.record(message_id, producer_id, status);
+    }
+}
+
+/// Due messages of one producer, retried in order
+///
+/// Messages not attempted when it is dropped, because the producer still had
+/// a send in flight or the fan-out deadline passed first, go back in the queue.
+struct Redelivery {
+    deliverer: Deliverer,
+    producer: Option<Arc<Producer>>,
+    batch: VecDeque<PendingDelivery>,
+}
+
+impl Redelivery {
+    fn run(mut self) -> Vec<(String, String, DeliveryStatus)> {
+        let mut attempts = Vec::with_capacity(self.batch.len());
+        while let Some(pending) = self.batch.pop_front() {
+            let status = self.deliverer.redeliver(self.producer.as_deref(), &pending);
+            attempts.push((pending.message_id, pending.producer_id, status));
+        }
+        attempts
+    }
+}
+
+impl Drop for Redelivery {
+    fn drop(&mut self) {
+        for pending in self.batch.drain(..) {
+            self.deliverer.tracker.schedule(pending, Duration::ZERO);
+        }
+    }
+}
+
+/// Retained message of an event and when it stops being sent
+struct RetainedMessage {
+    message: String,
+    expires_at: Option<Instant>,
 }
 
 impl ProducerPool {
     /// Create a new producer pool
     pub fn new() -> Self {
         Self {
-            producers: Arc::new(Mutex::new(HashMap::new())),
-            event_subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            routes: Arc::new(SharedRoutingTable::new()),
+            leases: Arc::new(Mutex::new(HashMap::new())),
+            deliveries: Arc::new(DeliveryTracker::default()),
+            retained: Arc::new(Mutex::new(HashMap::new())),
+            event_ttls: Arc::new(Mutex::new(HashMap::new())),
+            dead_letters: Arc::new(DeadLetterStore::default()),
+            scheduler: Arc::new(MessageScheduler::new()),
+            dedup: Arc::new(DedupFilter::new()),
+            rate_limiter: Arc::new(RateLimiter::new()),
+            fanout: Arc::new(FanOut::new()),
+            subscription_changes: Arc::new(Mutex::new(())),
         }
     }
 
     /// Add a producer to the pool
     pub fn add_producer(&self, id: String, client: Arc<dyn Client>) -> Result<()> {
-        let producer = Arc::new(Producer::new(id.clone(), client));
-        let mut producers = self.producers.lock().unwrap();
-        producers.insert(id.clone(), producer);
+        let producer = Arc::new(Producer::new(id, client));
+        self.publish_producer(producer, &[]);
         Ok(())
     }
 
@@ -45,50 +263,66 @@ impl ProducerPool {
             producer.subscribe(event);
         }
         
-        // Update event subscriptions index
-        {
-            let mut producers = self.producers.lock().unwrap();
-            producers.insert(id.clone(), producer);
-        }
+        // Publish the producer together with its subscriptions
+        self.publish_producer(Arc::clone(&producer), events);
         
-        // Update event -> producer mapping
-        {
-            let mut event_subs = self.event_subscriptions.lock().unwrap();
+        self.send_retained(&producer, events);
+        Ok(())
+    }
+
+    /// Publish a producer with its subscriptions, dropping the lease of any producer it replaces
+    ///
+    /// The lease is dropped under the leases lock, so the reaper can't evict
+    /// the new producer because the old one's lease expired.
+    fn publish_producer(&self, producer: Arc<Producer>, events: &[&str]) {
+        let mut leases = self.leases.lock().unwrap();
+        leases.remove(producer.id());
+        self.routes.update(|routes| {
+            routes.insert_producer(Arc::clone(&producer));
             for event in events {
-                event_subs
-                    .entry(event.to_string())
-                    .or_insert_with(HashSet::new)
-                    .insert(id.clone());
+                routes.subscribe(event, &producer);
             }
-        }
-        
-        Ok(())
+        });
     }
 
     /// Remove a producer from the pool
     pub fn remove_producer(&self, id: &str) -> Option<Arc<Producer>> {
-        let producer = {
-            let mut producers = self.producers.lock().unwrap();
-            producers.remove(id)
-        };
+        self.remove_producer_if(id, |_| true)
+    }
+
+    /// Remove a producer only if `is_current` accepts the one registered under its id
+    ///
+    /// Lets the owner of a producer remove it without removing a replacement
+    /// that has since been registered under the same id.
+    pub fn remove_producer_if(&self, id: &str, is_current: impl FnOnce(&Producer) -> bool) -> Option<Arc<Producer>> {
+        let producer = self.routes.update(|routes| {
+            routes.producer(id).filter(|producer| is_current(producer))?;
+            routes.remove_producer(id)
+        })?;
         
-        // Remove from event subscriptions
-        if producer.is_some() {
-            let mut event_subs = self.event_subscriptions.lock().unwrap();
-            for producer_ids in event_subs.values_mut() {
-                producer_ids.remove(id);
-            }
-            // Clean up empty event entries
-            event_subs.retain(|_, producer_ids| !producer_ids.is_empty());
-        }
+        self.leases.lock().unwrap().remove(id);
         
-        producer
+        Some(producer)
+    }
+
+    /// Replace the client of an existing producer, keeping its subscriptions and lease
+    pub fn update_producer_client(&self, id: &str, client: Arc<dyn Client>) -> Result<()> {
+        self.routes.update(|routes| {
+            let producer = routes.producer(id)
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Producer not found: {}", id),
+                ))?;
+            
+            let producer = Arc::new(producer.with_client(client));
+            routes.insert_producer(producer);
+            Ok(())
+        })
     }
 
     /// Get a producer by ID
     pub fn get_producer(&self, id: &str) -> Option<Arc<Producer>> {
-        let producers = self.producers.lock().unwrap();
-        producers.get(id).map(|p| Arc::clone(p))
+        self.routes.load().producer(id).map(Arc::clone)
     }
 
     /// Forward a message to a specific producer
@@ -104,18 +338,8 @@ impl ProducerPool {
 
     /// Forward a message to all producers in the pool
     pub fn forward_to_all(&self, message: &str) -> Vec<(String, Result<()>)> {
-        let producers = {
-            let producers = self.producers.lock().unwrap();
-            producers.iter()
-                .map(|(id, producer)| (id.clone(), Arc::clone(producer)))
-                .collect::<Vec<_>>()
-        };
-
-        producers.into_iter()
-            .map(|(id, producer)| {
-                let result = producer.forward(message);
-                (id, result)
-            })
+        self.routes.load().producers()
+            .map(|producer| (producer.id().to_string(), producer.forward(message)))
             .collect()
     }
 
@@ -131,97 +355,418 @@ impl ProducerPool {
 
     /// Get all producer IDs
     pub fn get_producer_ids(&self) -> Vec<String> {
-        let producers = self.producers.lock().unwrap();
-        producers.keys().cloned().collect()
+        self.routes.load().producers().map(|producer| producer.id().to_string()).collect()
     }
 
     /// Get the number of producers in the pool
     pub fn count(&self) -> usize {
-        let producers = self.producers.lock().unwrap();
-        producers.len()
+        self.routes.load().len()
     }
 
     /// Check if a producer exists
     pub fn has_producer(&self, id: &str) -> bool {
-        let producers = self.producers.lock().unwrap();
-        producers.contains_key(id)
+        self.routes.load().producer(id).is_some()
     }
 
     /// Subscribe a producer to an event
     pub fn subscribe_producer_to_event(&self, producer_id: &str, event_name: &str) -> Result<()> {
-        let producer = self.get_producer(producer_id)
-            .ok_or_else(|| std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Producer not found: {}", producer_id),
-            ))?;
-        
+        let changes = self.subscription_changes.lock().unwrap();
+        let producer = self.routes.update(|routes| {
+            let producer = routes.producer(producer_id)
+                .cloned()
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Producer not found: {}", producer_id),
+                ))?;
+            
+            routes.subscribe(event_name, &producer);
+            Ok::<_, std::io::Error>(producer)
+        })?;
+        let newly_subscribed = !producer.is_subscribed(event_name);
         producer.subscribe(event_name);
+        drop(changes);
         
-        // Update event subscriptions index
-        let mut event_subs = self.event_subscriptions.lock().unwrap();
-        event_subs
-            .entry(event_name.to_string())
-            .or_insert_with(HashSet::new)
-            .insert(producer_id.to_string());
-        
+        if newly_subscribed {
+            self.send_retained(&producer, &[event_name]);
+        }
         Ok(())
     }
 
     /// Unsubscribe a producer from an event
     pub fn unsubscribe_producer_from_event(&self, producer_id: &str, event_name: &str) -> Result<()> {
-        let producer = self.get_producer(producer_id)
-            .ok_or_else(|| std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Producer not found: {}", producer_id),
-            ))?;
-        
+        let _changes = self.subscription_changes.lock().unwrap();
+        let producer = self.routes.update(|routes| {
+            let producer = routes.producer(producer_id)
+                .cloned()
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Producer not found: {}", producer_id),
+                ))?;
+            
+            routes.unsubscribe(event_name, producer_id);
+            Ok::<_, std::io::Error>(producer)
+        })?;
         producer.unsubscribe(event_name);
+        Ok(())
+    }
+
+    /// Keep a message as the event's current value for future subscribers
+    ///
+    /// An empty message clears the retained value.
+    pub fn retain_message(&self, event_name: &str, message: &str) {
+        self.retain_message_until(event_name, message, None);
+    }
+
+    /// Keep a message as the event's current value until it expires
+    pub fn retain_message_until(&self, event_name: &str, message: &str, expires_at: Option<Instant>) {
+        let mut retained = self.retained.lock().unwrap();
+        if message.is_empty() {
+            retained.remove(event_name);
+        } else {
+            retained.insert(event_name.to_string(), RetainedMessage {
+                message: message.to_string(),
+                expires_at,
+            });
+        }
+    }
+
+    /// Get the retained message of an event
+    pub fn get_retained(&self, event_name: &str) -> Option<String> {
+        self.live_retained(event_name).map(|(message, _)| message)
+    }
+
+    /// Get the retained message of an event with its expiry, dropping it once expired
+    fn live_retained(&self, event_name: &str) -> Option<(String, Option<Instant>)> {
+        let expired = {
+            let mut retained = self.retained.lock().unwrap();
+            let entry = retained.get(event_name)?;
+            if entry.expires_at.is_none_or(|expires_at| expires_at > Instant::now()) {
+                return Some((entry.message.clone(), entry.expires_at));
+            }
+            retained.remove(event_name)?
+        };
         
-        // Update event subscriptions index
-        let mut event_subs = self.event_subscriptions.lock().unwrap();
-        if let Some(producer_ids) = event_subs.get_mut(event_name) {
-            producer_ids.remove(producer_id);
-            if producer_ids.is_empty() {
-                event_subs.remove(event_name);
+        self.dead_letters.push(DeadLetter {
+            message_id: None,
+            event_name: event_name.to_string(),
+            producer_id: None,
+            message: expired.message,
+            reason: EXPIRED_REASON.to_string(),
+        });
+        None
+    }
+
+    /// Get the number of events with a retained message
+    pub fn retained_count(&self) -> usize {
+        self.retained.lock().unwrap().len()
+    }
+
+    /// Clear the retained message of an event, returning whether there was one
+    pub fn clear_retained(&self, event_name: &str) -> bool {
+        self.retained.lock().unwrap().remove(event_name).is_some()
+    }
+
+    /// Clear every retained message, returning how many were removed
+    pub fn clear_all_retained(&self) -> usize {
+        let mut retained = self.retained.lock().unwrap();
+        let count = retained.len();
+        retained.clear();
+        count
+    }
+
+    /// Send the retained messages of newly subscribed events to a producer
+    fn send_retained(&self, producer: &Producer, events: &[&str]) {
+        // Bounded like a broadcast, so a slow subscriber can't hold up its registration
+        let deadline = Instant::now() + self.fanout.config().deadline;
+        for event in events {
+            let Some((message, expires_at)) = self.live_retained(event) else {
+                continue;
+            };
+            // A failed delivery doesn't undo the subscription
+            if let Err(e) = self.deliverer().deliver(producer, event, None, &message, expires_at, Some(deadline)) {
+                eprintln!("[Producer {}] Failed to send retained message for event '{}': {}",
+                          producer.id(), event, e);
             }
         }
-        
-        Ok(())
+    }
+
+    /// Replace the default TTLs of messages per event
+    pub fn set_event_ttls(&self, ttls: HashMap<String, Duration>) {
+        *self.event_ttls.lock().unwrap() = ttls;
+    }
+
+    /// Get the default TTL of messages published on an event
+    pub fn event_ttl(&self, event_name: &str) -> Option<Duration> {
+        self.event_ttls.lock().unwrap().get(event_name).copied()
+    }
+
+    /// Get the messages that expired or could not be delivered
+    pub fn dead_letters(&self) -> &DeadLetterStore {
+        &self.dead_letters
+    }
+
+    /// Replace the duplicate suppression rules per event
+    pub fn set_dedup_rules(&self, rules: HashMap<String, DedupRule>) {
+        self.dedup.set_rules(rules);
+    }
+
+    /// Get the filter that suppresses duplicate messages
+    pub fn dedup(&self) -> &DedupFilter {
+        &self.dedup
+    }
+
+    /// Replace the ingress rate limits
+    pub fn set_rate_limits(&self, config: RateLimitConfig) {
+        self.rate_limiter.set_config(config);
+    }
+
+    /// Get the rate limiter applied to incoming messages
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Replace the fan-out settings of broadcasts to event subscribers
+    pub fn set_fanout(&self, config: FanOutConfig) {
+        self.fanout.set_config(config);
+    }
+
+    /// Get the messages waiting for their delivery time
+    pub fn scheduler(&self) -> &MessageScheduler {
+        &self.scheduler
     }
 
     /// Forward message to producers subscribed to a specific event
     pub fn forward_to_event(&self, event_name: &str, message: &str) -> Vec<(String, Result<()>)> {
-        let producer_ids = {
-            let event_subs = self.event_subscriptions.lock().unwrap();
-            event_subs
-                .get(event_name)
-                .map(|ids| ids.iter().cloned().collect::<Vec<_>>())
-                .unwrap_or_default()
-        };
+        self.forward_to_event_with_id(event_name, None, message)
+    }
 
-        producer_ids.into_iter()
-            .filter_map(|id| {
-                self.get_producer(&id).map(|producer| {
-                    let result = producer.forward(message);
+    /// Forward a message with an optional id to producers subscribed to an event
+    ///
+    /// At-least-once producers get a generated id when the message has none.
+    /// A message they don't acknowledge is queued for redelivery and counts
+    /// as forwarded; its final outcome is available from `delivery_receipt`.
+    pub fn forward_to_event_with_id(
+        &self,
+        event_name: &str,
+        message_id: Option<&str>,
+        message: &str,
+    ) -> Vec<(String, Result<()>)> {
+        self.forward_to_event_until(event_name, message_id, message, None)
+    }
+
+    /// Forward a message to producers subscribed to an event, dropping it from
+    /// the redelivery queue once `expires_at` passes
+    pub fn forward_to_event_until(
+        &self,
+        event_name: &str,
+        message_id: Option<&str>,
+        message: &str,
+        expires_at: Option<Instant>,
+    ) -> Vec<(String, Result<()>)> {
+        let routes = self.routes.load();
+        let event_name: Arc<str> = Arc::from(event_name);
+        let message_id: Option<Arc<str>> = message_id.map(Arc::from);
+        let message: Arc<str> = Arc::from(message);
+        
+        let subscribers = routes.subscribers(&event_name);
+        let deliveries = subscribers.iter()
+            .map(|producer| {
+                let deliverer = self.deliverer();
+                let producer = Arc::clone(producer);
+                let (event_name, message_id, message) = (Arc::clone(&event_name), message_id.clone(), Arc::clone(&message));
+                let id = producer.id().to_string();
+                let delivery: Delivery = Box::new(move |deadline| {
+                    deliverer.deliver(&producer, &event_name, message_id.as_deref(), &message, expires_at, Some(deadline))
+                });
+                (id, delivery)
+            })
+            .collect();
+        
+        // Subscribers still busy with an earlier message get this one redelivered later
+        let deliverer = self.deliverer();
+        self.fanout.run(deliveries)
+            .into_iter()
+            .zip(subscribers)
+            .map(|((id, result), producer)| match result {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    let result = deliverer.defer(producer, &event_name, message_id.as_deref(), &message, expires_at, e);
                     (id, result)
-                })
+                }
+                result => (id, result),
+            })
+            .collect()
+    }
+
+    /// Forward a message to a single subscriber of an event
+    ///
+    /// Subscribers are tried in id order until one accepts the message, calling
+    /// `before_attempt` with each one first. Returns the attempts made, so only
+    /// the last one can have succeeded.
+    pub fn forward_to_one_subscriber(
+        &self,
+        event_name: &str,
+        message_id: Option<&str>,
+        message: &str,
+        mut before_attempt: impl FnMut(&Producer),
+    ) -> Vec<(String, Result<()>)> {
+        let routes = self.routes.load();
+        let deliverer = self.deliverer();
+        let mut attempts = Vec::new();
+        for producer in routes.subscribers(event_name) {
+            before_attempt(producer);
+            let result = deliverer.deliver(producer, event_name, message_id, message, None, None);
+            let delivered = result.is_ok();
+            attempts.push((producer.id().to_string(), result));
+            if delivered {
+                break;
+            }
+        }
+        attempts
+    }
+
+    /// Retry unacknowledged messages that are due, returning each attempt's outcome
+    ///
+    /// Each producer's messages are retried in order on a fan-out thread, so a
+    /// consumer that doesn't answer only holds up its own messages. Waits up to
+    /// the fan-out deadline; attempts finishing later are recorded but not
+    /// returned. Messages expired by then are dead-lettered as `expired` instead.
+    /// Returns `(message id, producer id, status)` per attempt, by producer id.
+    pub fn redeliver_due(&self) -> Vec<(String, String, DeliveryStatus)> {
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let deliveries = self.take_redeliveries()
+            .map(|(producer_id, redelivery)| {
+                let outcomes = Arc::clone(&outcomes);
+                let delivery: Delivery = Box::new(move |_| {
+                    let attempts = redelivery.run();
+                    outcomes.lock().unwrap().extend(attempts);
+                    Ok(())
+                });
+                (producer_id, delivery)
+            })
+            .collect();
+        self.fanout.run(deliveries);
+        
+        let mut outcomes = std::mem::take(&mut *outcomes.lock().unwrap());
+        outcomes.sort_by(|(_, a, _), (_, b, _)| a.cmp(b));
+        outcomes
+    }
+
+    /// Start retrying due messages on the fan-out threads without waiting for them
+    pub fn spawn_redeliveries(&self) {
+        for (producer_id, redelivery) in self.take_redeliveries() {
+            self.fanout.spawn(&producer_id, move || {
+                for (message_id, producer_id, status) in redelivery.run() {
+                    log_redelivery(&message_id, &producer_id, &status);
+                }
+            });
+        }
+    }
+
+    /// Take the due deliveries, grouped per producer
+    fn take_redeliveries(&self) -> impl Iterator<Item = (String, Redelivery)> + use<> {
+        let routes = self.routes.load();
+        let mut batches: BTreeMap<String, VecDeque<PendingDelivery>> = BTreeMap::new();
+        for pending in self.deliveries.take_due(Instant::now()) {
+            batches.entry(pending.producer_id.clone()).or_default().push_back(pending);
+        }
+        let deliverer = self.deliverer();
+        batches.into_iter().map(move |(producer_id, batch)| {
+            let redelivery = Redelivery {
+                deliverer: deliverer.clone(),
+                producer: routes.producer(&producer_id).cloned(),
+                batch,
+            };
+            (producer_id, redelivery)
+        })
+    }
+
+    fn deliverer(&self) -> Deliverer {
+        Deliverer {
+            tracker: Arc::clone(&self.deliveries),
+            dead_letters: Arc::clone(&self.dead_letters),
+        }
+    }
+
+    /// Get the delivery status of a message per at-least-once producer
+    pub fn delivery_receipt(&self, message_id: &str) -> Option<BTreeMap<String, DeliveryStatus>> {
+        self.deliveries.receipt(message_id)
+    }
+
+    /// Attach a lease to a producer; it is evicted unless renewed within `ttl`
+    pub fn set_lease(&self, producer_id: &str, ttl: Duration) -> Result<()> {
+        if !self.has_producer(producer_id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Producer not found: {}", producer_id),
+            ));
+        }
+        
+        let mut leases = self.leases.lock().unwrap();
+        leases.insert(producer_id.to_string(), Lease::new(ttl));
+        Ok(())
+    }
+
+    /// Remove a producer's lease so it never expires
+    pub fn clear_lease(&self, producer_id: &str) {
+        let mut leases = self.leases.lock().unwrap();
+        leases.remove(producer_id);
+    }
+
+    /// Renew a producer's lease, returning its TTL
+    pub fn renew_lease(&self, producer_id: &str) -> Result<Duration> {
+        if !self.has_producer(producer_id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Producer not found: {}", producer_id),
+            ));
+        }
+        
+        let mut leases = self.leases.lock().unwrap();
+        let lease = leases.get_mut(producer_id)
+            .ok_or_else(|| std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Producer '{}' was registered without a TTL", producer_id),
+            ))?;
+        
+        lease.renew();
+        Ok(lease.ttl())
+    }
+
+    /// Get a producer's lease, if it has one
+    pub fn get_lease(&self, producer_id: &str) -> Option<Lease> {
+        let leases = self.leases.lock().unwrap();
+        leases.get(producer_id).copied()
+    }
+
+    /// Remove all producers whose lease has expired, returning their IDs
+    pub fn evict_expired(&self) -> Vec<String> {
+        let now = Instant::now();
+        // Held until the producers are removed, so a heartbeat or re-registration
+        // either lands before the check or after the eviction
+        let mut leases = self.leases.lock().unwrap();
+        let expired = leases.iter()
+            .filter(|(_, lease)| lease.is_expired_at(now))
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        
+        expired.into_iter()
+            .filter(|id| {
+                leases.remove(id);
+                self.routes.update(|routes| routes.remove_producer(id)).is_some()
             })
             .collect()
     }
 
     /// Get all events that have subscribers
     pub fn get_subscribed_events(&self) -> Vec<String> {
-        let event_subs = self.event_subscriptions.lock().unwrap();
-        event_subs.keys().cloned().collect()
+        self.routes.load().events().cloned().collect()
     }
 
     /// Get producers subscribed to an event
     pub fn get_event_subscribers(&self, event_name: &str) -> Vec<String> {
-        let event_subs = self.event_subscriptions.lock().unwrap();
-        event_subs
-            .get(event_name)
-            .map(|ids| ids.iter().cloned().collect())
-            .unwrap_or_default()
+        self.routes.load().subscribers(event_name).iter()
+            .map(|producer| producer.id().to_string())
+            .collect()
     }
 }
 
@@ -234,6 +779,7 @@ impl Default for ProducerPool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::delivery::DeliveryPolicy;
     use crate::transport::clients::TcpClient;
 
     struct MockClient {
@@ -409,4 +955,316 @@ mod tests {
         assert!(ids.contains(&"producer-1".to_string()));
         assert!(ids.contains(&"producer-2".to_string()));
     }
+
+    #[test]
+    fn test_update_producer_client() {
+        let pool = ProducerPool::new();
+        let sent1 = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sent2 = Arc::new(std::sync::Mutex::new(Vec::new()));
+        
+        pool.add_producer_with_events("producer-1".to_string(), Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent1),
+        }), &["event1"]).unwrap();
+        
+        pool.update_producer_client("producer-1", Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent2),
+        })).unwrap();
+        
+        let results = pool.forward_to_event("event1", "after update");
+        assert_eq!(results.len(), 1);
+        assert_eq!(sent1.lock().unwrap().len(), 0);
+        assert_eq!(sent2.lock().unwrap().len(), 1);
+        assert!(pool.get_producer("producer-1").unwrap().is_subscribed("event1"));
+        
+        let missing = pool.update_producer_client("missing", Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent2),
+        }));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_forward_to_one_subscriber() {
+        let pool = ProducerPool::new();
+        let sent_b = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sent_c = Arc::new(std::sync::Mutex::new(Vec::new()));
+        pool.add_producer_with_events("a".to_string(), Arc::new(TcpClient::new("invalid".to_string())), &["rpc"]).unwrap();
+        pool.add_producer_with_events("b".to_string(), Arc::new(MockClient { sent_messages: Arc::clone(&sent_b) }), &["rpc"]).unwrap();
+        pool.add_producer_with_events("c".to_string(), Arc::new(MockClient { sent_messages: Arc::clone(&sent_c) }), &["rpc"]).unwrap();
+        
+        let mut tried = Vec::new();
+        let attempts = pool.forward_to_one_subscriber("rpc", None, "request", |producer| tried.push(producer.id().to_string()));
+        let ids: Vec<&str> = attempts.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(tried, ids);
+        assert!(attempts[0].1.is_err() && attempts[1].1.is_ok());
+        assert_eq!(sent_b.lock().unwrap().len(), 1);
+        assert!(sent_c.lock().unwrap().is_empty());
+        assert!(pool.forward_to_one_subscriber("missing", None, "request", |_| {}).is_empty());
+    }
+
+    #[test]
+    fn test_retained_message_sent_to_new_subscribers() {
+        let pool = ProducerPool::new();
+        let sent1 = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sent2 = Arc::new(std::sync::Mutex::new(Vec::new()));
+        
+        pool.retain_message("price", "41");
+        pool.retain_message("price", "42");
+        pool.add_producer_with_events("producer-1".to_string(), Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent1),
+        }), &["price", "other"]).unwrap();
+        assert_eq!(*sent1.lock().unwrap(), vec!["42"]);
+        
+        // Only a new subscription gets the retained value
+        pool.add_producer("producer-2".to_string(), Arc::new(MockClient {
+            sent_messages: Arc::clone(&sent2),
+        })).unwrap();
+        pool.subscribe_producer_to_event("producer-2", "price").unwrap();
+        pool.subscribe_producer_to_event("producer-2", "price").unwrap();
+        assert_eq!(*sent2.lock().unwrap(), vec!["42"]);
+        
+        pool.retain_message("price", "");
+        assert_eq!(pool.get_retained("price"), None);
+        pool.retain_message("a", "1");
+        pool.retain_message("b", "2");
+        assert!(pool.clear_retained("a"));
+        assert!(!pool.clear_retained("a"));
+        assert_eq!(pool.clear_all_retained(), 1);
+    }
+
+    #[test]
+    fn test_lease_renew_and_evict() {
+        let pool = ProducerPool::new();
+        let client = Arc::new(MockClient {
+            sent_messages: Arc::new(std::sync::Mutex::new(Vec::new())),
+        });
+        
+        pool.add_producer_with_events("producer-1".to_string(), client.clone(), &["event1"]).unwrap();
+        pool.add_producer("producer-2".to_string(), client).unwrap();
+        
+        // Producer without a lease cannot heartbeat
+        assert!(pool.renew_lease("producer-2").is_err());
+        assert!(pool.renew_lease("missing").is_err());
+        
+        pool.set_lease("producer-1", Duration::from_secs(30)).unwrap();
+        assert_eq!(pool.renew_lease("producer-1").unwrap(), Duration::from_secs(30));
+        assert!(pool.evict_expired().is_empty());
+        
+        pool.set_lease("producer-1", Duration::ZERO).unwrap();
+        assert_eq!(pool.evict_expired(), vec!["producer-1".to_string()]);
+        assert!(!pool.has_producer("producer-1"));
+        assert!(pool.has_producer("producer-2"));
+        assert!(pool.get_event_subscribers("event1").is_empty());
+        assert!(pool.get_lease("producer-1").is_none());
+        
+        // Registering again replaces the expired lease instead of being evicted by it
+        pool.set_lease("producer-2", Duration::ZERO).unwrap();
+        let client = Arc::new(MockClient {
+            sent_messages: Arc::new(std::sync::Mutex::new(Vec::new())),
+        });
+        pool.add_producer("producer-2".to_string(), client).unwrap();
+        assert!(pool.get_lease("producer-2").is_none());
+        assert!(pool.evict_expired().is_empty());
+        assert!(pool.has_producer("producer-2"));
+    }
+
+    /// Client that fails its first `failures` acknowledged sends
+    struct FlakyClient {
+        failures: std::sync::Mutex<u32>,
+        ids: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Client for FlakyClient {
+        fn send(&self, _message: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn send_with_id(&self, id: &str, _message: &str) -> Result<()> {
+            self.ids.lock().unwrap().push(id.to_string());
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no ack"));
+            }
+            Ok(())
+        }
+
+        fn delivery_policy(&self) -> Option<DeliveryPolicy> {
+            Some(DeliveryPolicy {
+                max_attempts: 3,
+                retry_interval: Duration::ZERO,
+                ..DeliveryPolicy::default()
+            })
+        }
+
+        fn protocol_name(&self) -> &str {
+            "FLAKY"
+        }
+
+        fn address(&self) -> &str {
+            "flaky://test"
+        }
+    }
+
+    fn flaky_client(failures: u32) -> (Arc<FlakyClient>, Arc<std::sync::Mutex<Vec<String>>>) {
+        let ids = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = Arc::new(FlakyClient {
+            failures: std::sync::Mutex::new(failures),
+            ids: Arc::clone(&ids),
+        });
+        (client, ids)
+    }
+
+    #[test]
+    fn test_at_least_once_redelivery_and_receipts() {
+        let pool = ProducerPool::new();
+        let (steady, _) = flaky_client(0);
+        let (flaky, flaky_ids) = flaky_client(1);
+        let (broken, _) = flaky_client(u32::MAX);
+        pool.add_producer_with_events("steady".to_string(), steady, &["event1"]).unwrap();
+        pool.add_producer_with_events("flaky".to_string(), flaky, &["event1"]).unwrap();
+        pool.add_producer_with_events("broken".to_string(), broken, &["event1"]).unwrap();
+        
+        // Unacknowledged messages are queued, not reported as failures
+        let results = pool.forward_to_event_with_id("event1", Some("m-1"), "hello");
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        let receipt = pool.delivery_receipt("m-1").unwrap();
+        assert_eq!(receipt["steady"], DeliveryStatus::Delivered);
+        assert_eq!(receipt["flaky"], DeliveryStatus::Pending { attempts: 1 });
+        
+        assert_eq!(pool.redeliver_due().len(), 2);
+        pool.redeliver_due();
+        let receipt = pool.delivery_receipt("m-1").unwrap();
+        assert_eq!(receipt["flaky"], DeliveryStatus::Redelivered { attempts: 2 });
+        assert!(matches!(receipt["broken"], DeliveryStatus::DeadLettered { attempts: 3, .. }));
+        assert!(pool.redeliver_due().is_empty());
+        assert_eq!(*flaky_ids.lock().unwrap(), vec!["m-1", "m-1"]);
+        
+        // Messages without an id get a generated one
+        pool.forward_to_event("event1", "no id");
+        assert!(flaky_ids.lock().unwrap()[2].starts_with("auto-"));
+        assert!(pool.delivery_receipt("unknown").is_none());
+    }
+
+    #[test]
+    fn test_expired_messages_are_dead_lettered() {
+        let pool = ProducerPool::new();
+        let (flaky, flaky_ids) = flaky_client(1);
+        pool.add_producer_with_events("flaky".to_string(), flaky, &["alerts"]).unwrap();
+        
+        // The first attempt fails, and the message expires before the retry
+        let expires_at = Instant::now() + Duration::from_millis(20);
+        pool.forward_to_event_until("alerts", Some("m-1"), "disk full", Some(expires_at));
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(pool.redeliver_due().len(), 1);
+        assert_eq!(*flaky_ids.lock().unwrap(), vec!["m-1"]);
+        assert_eq!(
+            pool.delivery_receipt("m-1").unwrap()["flaky"],
+            DeliveryStatus::DeadLettered { attempts: 1, reason: EXPIRED_REASON.to_string() },
+        );
+        
+        // An expired retained message is no longer sent to new subscribers
+        pool.retain_message_until("alerts", "stale", Some(Instant::now()));
+        assert_eq!(pool.get_retained("alerts"), None);
+        
+        let letters = pool.dead_letters().recent(10);
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].message, "stale");
+        assert_eq!(letters[1].producer_id.as_deref(), Some("flaky"));
+        assert_eq!(pool.dead_letters().expired(), 2);
+    }
+
+    struct SlowClient {
+        delay: Duration,
+        policy: Option<DeliveryPolicy>,
+    }
+
+    impl Client for SlowClient {
+        fn send(&self, _message: &str) -> Result<()> {
+            std::thread::sleep(self.delay);
+            Ok(())
+        }
+
+        fn delivery_policy(&self) -> Option<DeliveryPolicy> {
+            self.policy
+        }
+
+        fn protocol_name(&self) -> &str {
+            "SLOW"
+        }
+
+        fn address(&self) -> &str {
+            "slow://test"
+        }
+    }
+
+    #[test]
+    fn test_broadcasts_wait_for_the_slowest_subscriber_up_to_the_deadline() {
+        let pool = ProducerPool::new();
+        for (id, millis) in [("a", 150), ("b", 150), ("c", 150), ("stuck", 2_000)] {
+            let client = Arc::new(SlowClient { delay: Duration::from_millis(millis), policy: None });
+            pool.add_producer_with_events(id.to_string(), client, &["orders"]).unwrap();
+        }
+        pool.set_fanout(FanOutConfig { threads: 8, deadline: Duration::from_millis(400) });
+        
+        let started = Instant::now();
+        let results = pool.forward_to_event("orders", "order placed");
+        let elapsed = started.elapsed();
+        
+        assert!(elapsed >= Duration::from_millis(400) && elapsed < Duration::from_millis(900), "{:?}", elapsed);
+        let ids = results.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "b", "c", "stuck"]);
+        assert!(results[..3].iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(results[3].1.as_ref().unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_busy_at_least_once_subscriber_gets_the_message_redelivered_later() {
+        let pool = ProducerPool::new();
+        let client = Arc::new(SlowClient { delay: Duration::from_millis(600), policy: Some(DeliveryPolicy::default()) });
+        pool.add_producer_with_events("slow".to_string(), client, &["orders"]).unwrap();
+        pool.set_fanout(FanOutConfig { threads: 2, deadline: Duration::from_millis(100) });
+        
+        let first = pool.forward_to_event_with_id("orders", Some("m-1"), "first");
+        assert_eq!(first[0].1.as_ref().unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        
+        // The first send is still running, so the second waits for redelivery
+        let second = pool.forward_to_event_with_id("orders", Some("m-2"), "second");
+        assert!(second[0].1.is_ok());
+        assert_eq!(pool.delivery_receipt("m-2").unwrap()["slow"], DeliveryStatus::Pending { attempts: 0 });
+    }
+
+    #[test]
+    fn test_redelivery_to_a_slow_subscriber_does_not_hold_up_the_others() {
+        let pool = ProducerPool::new();
+        let slow = Arc::new(SlowClient { delay: Duration::from_millis(1000), policy: Some(DeliveryPolicy::default()) });
+        let (fast, _) = flaky_client(0);
+        pool.add_producer_with_events("slow".to_string(), slow, &["orders"]).unwrap();
+        pool.add_producer_with_events("fast".to_string(), fast, &["orders"]).unwrap();
+        pool.set_fanout(FanOutConfig { threads: 4, deadline: Duration::from_millis(300) });
+        let pending = |message_id: &str, producer_id: &str| PendingDelivery {
+            message_id: message_id.to_string(),
+            producer_id: producer_id.to_string(),
+            event_name: "orders".to_string(),
+            message: "hello".to_string(),
+            attempts: 1,
+            expires_at: None,
+        };
+        
+        pool.deliveries.schedule(pending("m-1", "slow"), Duration::ZERO);
+        pool.deliveries.schedule(pending("m-2", "fast"), Duration::ZERO);
+        let started = Instant::now();
+        let attempts = pool.redeliver_due();
+        
+        assert!(started.elapsed() < Duration::from_millis(900));
+        assert_eq!(attempts, vec![("m-2".to_string(), "fast".to_string(), DeliveryStatus::Redelivered { attempts: 2 })]);
+        
+        // The slow subscriber is still busy with m-1, so m-3 goes back in the queue
+        pool.deliveries.schedule(pending("m-3", "slow"), Duration::ZERO);
+        assert!(pool.redeliver_due().is_empty());
+        let requeued = pool.deliveries.take_due(Instant::now());
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].message_id, "m-3");
+        assert_eq!(requeued[0].attempts, 1);
+    }
 }
@@ -0,0 +1,185 @@
+use std::io::{ErrorKind, Result};
+use std::time::Duration;
+
+use super::delivery::DeliveryPolicy;
+
+/// Parsed producer URI: `<protocol>://<address>[?events=a,b&timeout=3s&delivery=at-least-once]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerUri {
+    /// Protocol scheme, upper-cased (e.g. `TCP`)
+    pub protocol: String,
+    /// Target address without scheme or query
+    pub address: String,
+    /// Events listed in the `events` query option
+    pub events: Vec<String>,
+    /// Send timeout from the `timeout` query option
+    pub timeout: Option<Duration>,
+    /// At-least-once settings from `delivery=at-least-once` and its
+    /// `ack_timeout`, `max_attempts` and `retry_interval` options
+    pub delivery: Option<DeliveryPolicy>,
+}
+
+impl ProducerUri {
+    /// Parse a producer URI
+    pub fn parse(uri: &str) -> Result<Self> {
+        // Parse URI: protocol://address
+        let (protocol, rest) = uri.split_once("://")
+            .ok_or_else(invalid_format)?;
+
+        let (address, query) = match rest.split_once('?') {
+            Some((address, query)) => (address, Some(query)),
+            None => (rest, None),
+        };
+
+        if protocol.is_empty() || address.is_empty() {
+            return Err(invalid_format());
+        }
+
+        let mut parsed = Self {
+            protocol: protocol.to_uppercase(),
+            address: address.to_string(),
+            events: Vec::new(),
+            timeout: None,
+            delivery: None,
+        };
+        let mut at_least_once = false;
+        let mut policy = DeliveryPolicy::default();
+        let mut policy_options = Vec::new();
+
+        for option in query.unwrap_or_default().split('&').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=')
+                .ok_or_else(|| invalid_option(uri, format!("Invalid URI option '{}' (expected key=value)", option)))?;
+
+            match key {
+                "events" => parsed.events.extend(
+                    value.split(',')
+                        .map(str::trim)
+                        .filter(|event| !event.is_empty())
+                        .map(str::to_string),
+                ),
+                "timeout" => {
+                    let timeout = parse_duration(value)
+                        .ok_or_else(|| invalid_option(uri, format!(
+                            "Invalid timeout '{}' (expected e.g. 500ms, 3s or 1m)", value
+                        )))?;
+                    parsed.timeout = Some(timeout);
+                }
+                "delivery" => match value {
+                    "at-least-once" => at_least_once = true,
+                    "best-effort" => at_least_once = false,
+                    _ => return Err(invalid_option(uri, format!(
+                        "Invalid delivery '{}' (expected at-least-once or best-effort)", value
+                    ))),
+                },
+                "ack_timeout" | "retry_interval" => {
+                    let duration = parse_duration(value)
+                        .ok_or_else(|| invalid_option(uri, format!(
+                            "Invalid {} '{}' (expected e.g. 500ms, 3s or 1m)", key, value
+                        )))?;
+                    if key == "ack_timeout" {
+                        policy.ack_timeout = duration;
+                    } else {
+                        policy.retry_interval = duration;
+                    }
+                    policy_options.push(key);
+                }
+                "max_attempts" => {
+                    policy.max_attempts = value.parse::<u32>().ok()
+                        .filter(|attempts| *attempts > 0)
+                        .ok_or_else(|| invalid_option(uri, format!(
+                            "Invalid max_attempts '{}' (expected a positive number)", value
+                        )))?;
+                    policy_options.push(key);
+                }
+                _ => return Err(invalid_option(uri, format!("Unknown URI option '{}'", key))),
+            }
+        }
+
+        if at_least_once {
+            parsed.delivery = Some(policy);
+        } else if let Some(option) = policy_options.first() {
+            return Err(invalid_option(uri, format!("URI option '{}' requires delivery=at-least-once", option)));
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Parse a positive duration with an `ms`, `s` or `m` suffix
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit_ms) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 1)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1_000)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60_000)
+    } else {
+        return None;
+    };
+
+    let millis = number.parse::<u64>().ok()?.checked_mul(unit_ms)?;
+    (millis > 0).then(|| Duration::from_millis(millis))
+}
+
+fn invalid_format() -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidInput, "Invalid URI format. Expected: protocol://address")
+}
+
+fn invalid_option(uri: &str, reason: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidInput, format!("{} in {}", reason, uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_uri() {
+        let uri = ProducerUri::parse("tcp://127.0.0.1:9000").unwrap();
+        assert_eq!(uri.protocol, "TCP");
+        assert_eq!(uri.address, "127.0.0.1:9000");
+        assert!(uri.events.is_empty());
+        assert_eq!(uri.timeout, None);
+    }
+
+    #[test]
+    fn test_parse_uri_options() {
+        let uri = ProducerUri::parse("tcp://127.0.0.1:9000?events=a,b&timeout=3s").unwrap();
+        assert_eq!(uri.address, "127.0.0.1:9000");
+        assert_eq!(uri.events, vec!["a", "b"]);
+        assert_eq!(uri.timeout, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_parse_delivery_options() {
+        let uri = ProducerUri::parse("tcp://h:1?delivery=at-least-once&ack_timeout=2s&max_attempts=3").unwrap();
+        assert_eq!(uri.delivery, Some(DeliveryPolicy {
+            ack_timeout: Duration::from_secs(2),
+            max_attempts: 3,
+            ..DeliveryPolicy::default()
+        }));
+        assert_eq!(ProducerUri::parse("tcp://h:1?delivery=best-effort").unwrap().delivery, None);
+
+        assert!(ProducerUri::parse("tcp://h:1?delivery=exactly-once").is_err());
+        assert!(ProducerUri::parse("tcp://h:1?ack_timeout=2s").is_err());
+        assert!(ProducerUri::parse("tcp://h:1?delivery=at-least-once&max_attempts=0").is_err());
+    }
+
+    #[test]
+    fn test_parse_uri_errors() {
+        assert!(ProducerUri::parse("127.0.0.1:9000").is_err());
+        assert!(ProducerUri::parse("tcp://").is_err());
+        assert!(ProducerUri::parse("tcp://h:1?timeout=3").is_err());
+        assert!(ProducerUri::parse("tcp://h:1?retries=3").is_err());
+        assert!(ProducerUri::parse("tcp://h:1?events").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("3s"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("0s"), None);
+        assert_eq!(parse_duration("5"), None);
+    }
+}
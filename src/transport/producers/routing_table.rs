@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+
+use super::producer::Producer;
+
+/// Immutable view of the registered producers and their event subscriptions
+///
+/// Subscriber lists are shared between snapshots, so publishing a change only
+/// copies the maps and the lists of the events it touches.
+#[derive(Clone, Default)]
+pub struct RoutingTable {
+    producers: HashMap<String, Arc<Producer>>,
+    // Event -> subscribers, sorted by producer id
+    subscribers: HashMap<String, Arc<Vec<Arc<Producer>>>>,
+}
+
+impl RoutingTable {
+    /// Get a producer by id
+    pub fn producer(&self, id: &str) -> Option<&Arc<Producer>> {
+        self.producers.get(id)
+    }
+
+    /// Get all producers, in no particular order
+    pub fn producers(&self) -> impl Iterator<Item = &Arc<Producer>> {
+        self.producers.values()
+    }
+
+    /// Get the number of producers
+    pub fn len(&self) -> usize {
+        self.producers.len()
+    }
+
+    /// Get the subscribers of an event, sorted by producer id
+    pub fn subscribers(&self, event_name: &str) -> &[Arc<Producer>] {
+        self.subscribers.get(event_name).map(|list| list.as_slice()).unwrap_or(&[])
+    }
+
+    /// Get all events that have subscribers
+    pub fn events(&self) -> impl Iterator<Item = &String> {
+        self.subscribers.keys()
+    }
+
+    /// Add or replace a producer, keeping the event lists it is in pointing at it
+    pub fn insert_producer(&mut self, producer: Arc<Producer>) {
+        let id = producer.id().to_string();
+        if self.producers.contains_key(&id) {
+            for list in self.subscribers.values_mut() {
+                if let Ok(index) = Self::position(list, &id) {
+                    Arc::make_mut(list)[index] = Arc::clone(&producer);
+                }
+            }
+        }
+        self.producers.insert(id, producer);
+    }
+
+    /// Remove a producer and all of its subscriptions
+    pub fn remove_producer(&mut self, id: &str) -> Option<Arc<Producer>> {
+        let producer = self.producers.remove(id)?;
+        self.subscribers.retain(|_, list| {
+            if let Ok(index) = Self::position(list, id) {
+                Arc::make_mut(list).remove(index);
+            }
+            !list.is_empty()
+        });
+        Some(producer)
+    }
+
+    /// Add a registered producer to an event's subscribers
+    pub fn subscribe(&mut self, event_name: &str, producer: &Arc<Producer>) {
+        let list = self.subscribers.entry(event_name.to_string()).or_default();
+        if let Err(index) = Self::position(list, producer.id()) {
+            Arc::make_mut(list).insert(index, Arc::clone(producer));
+        }
+    }
+
+    /// Remove a producer from an event's subscribers
+    pub fn unsubscribe(&mut self, event_name: &str, producer_id: &str) {
+        let Some(list) = self.subscribers.get_mut(event_name) else {
+            return;
+        };
+        if let Ok(index) = Self::position(list, producer_id) {
+            Arc::make_mut(list).remove(index);
+        }
+        if list.is_empty() {
+            self.subscribers.remove(event_name);
+        }
+    }
+
+    fn position(list: &[Arc<Producer>], id: &str) -> std::result::Result<usize, usize> {
+        list.binary_search_by(|producer| producer.id().cmp(id))
+    }
+}
+
+/// Routing table that is read without locking and replaced on every change
+///
+/// Readers load the current snapshot; writers take a lock, copy the snapshot,
+/// change the copy and publish it, so a reader never waits for a writer and
+/// never sees a half-applied change.
+#[derive(Default)]
+pub struct SharedRoutingTable {
+    current: ArcSwap<RoutingTable>,
+    writer: Mutex<()>,
+}
+
+impl SharedRoutingTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the current snapshot
+    pub fn load(&self) -> Arc<RoutingTable> {
+        self.current.load_full()
+    }
+
+    /// Apply a change to a copy of the table and publish it
+    ///
+    /// `change` runs under the writer lock and should only change the table;
+    /// anything else the caller needs to do belongs after `update` returns.
+    pub fn update<T>(&self, change: impl FnOnce(&mut RoutingTable) -> T) -> T {
+        let _writer = self.writer.lock().unwrap();
+        let mut table = RoutingTable::clone(&self.current.load());
+        let result = change(&mut table);
+        self.current.store(Arc::new(table));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::clients::TcpClient;
+
+    fn producer(id: &str) -> Arc<Producer> {
+        Arc::new(Producer::new(id.to_string(), Arc::new(TcpClient::new("127.0.0.1:1".to_string()))))
+    }
+
+    fn ids(producers: &[Arc<Producer>]) -> Vec<&str> {
+        producers.iter().map(|producer| producer.id()).collect()
+    }
+
+    #[test]
+    fn test_snapshots_are_unaffected_by_later_changes() {
+        let table = SharedRoutingTable::new();
+        table.update(|routes| {
+            for id in ["b", "a", "c"] {
+                let producer = producer(id);
+                routes.insert_producer(Arc::clone(&producer));
+                routes.subscribe("orders", &producer);
+            }
+        });
+
+        let before = table.load();
+        table.update(|routes| {
+            routes.remove_producer("b");
+            routes.unsubscribe("orders", "c");
+        });
+        let after = table.load();
+
+        assert_eq!(ids(before.subscribers("orders")), vec!["a", "b", "c"]);
+        assert_eq!(before.len(), 3);
+        assert_eq!(ids(after.subscribers("orders")), vec!["a"]);
+        assert_eq!(after.len(), 2);
+        assert!(after.producer("b").is_none());
+    }
+
+    #[test]
+    fn test_replacing_a_producer_updates_its_subscriptions() {
+        let mut routes = RoutingTable::default();
+        let original = producer("a");
+        routes.insert_producer(Arc::clone(&original));
+        routes.subscribe("orders", &original);
+        routes.subscribe("orders", &original);
+
+        let replacement = producer("a");
+        routes.insert_producer(Arc::clone(&replacement));
+
+        assert_eq!(routes.subscribers("orders").len(), 1);
+        assert!(Arc::ptr_eq(&routes.subscribers("orders")[0], &replacement));
+
+        routes.unsubscribe("orders", "a");
+        assert_eq!(routes.events().count(), 0);
+    }
+}
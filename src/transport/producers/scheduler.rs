@@ -0,0 +1,276 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::transport::message::EventMessage;
+
+use super::producer_pool::ProducerPool;
+
+/// Interval between checks for scheduled messages that are due
+pub const DEFAULT_SCHEDULER_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Default number of messages that may wait for their delivery time at once
+pub const DEFAULT_MAX_SCHEDULED_MESSAGES: usize = 10_000;
+
+/// Default longest delay a message may be scheduled for
+pub const DEFAULT_MAX_SCHEDULE_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Bounds on what the scheduler holds in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerLimits {
+    /// Messages waiting at once; more are rejected
+    pub max_messages: usize,
+    /// Longest delay accepted
+    pub max_delay: Duration,
+}
+
+impl Default for SchedulerLimits {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_SCHEDULED_MESSAGES,
+            max_delay: DEFAULT_MAX_SCHEDULE_DELAY,
+        }
+    }
+}
+
+/// Message held back until its delivery time
+#[derive(Debug, Clone)]
+pub struct ScheduledMessage {
+    /// Id used to list or cancel the message: its own id, or a generated `sched-<n>`
+    pub schedule_id: String,
+    pub message: EventMessage,
+    /// Delivery time in milliseconds since the Unix epoch
+    pub deliver_at: u64,
+    /// Instant after which the message is dropped instead of delivered
+    pub expires_at: Option<Instant>,
+    due: Instant,
+}
+
+#[derive(Default)]
+struct Schedule {
+    // (due, sequence, schedule id); entries of cancelled messages are skipped when popped
+    queue: BinaryHeap<Reverse<(Instant, u64, String)>>,
+    // Schedule id -> (sequence, message)
+    messages: HashMap<String, (u64, ScheduledMessage)>,
+}
+
+impl Schedule {
+    /// Remove a pending message, dropping the queue entries of cancelled
+    /// messages once they outnumber the pending ones
+    fn remove(&mut self, schedule_id: &str) -> Option<ScheduledMessage> {
+        let (_, message) = self.messages.remove(schedule_id)?;
+        if self.queue.len() > 2 * self.messages.len() {
+            let messages = &self.messages;
+            self.queue.retain(|Reverse((_, sequence, schedule_id))| {
+                messages.get(schedule_id).is_some_and(|(current, _)| current == sequence)
+            });
+        }
+        Some(message)
+    }
+}
+
+/// Messages published with `deliver_at` or `delay_ms`, ordered by delivery time
+///
+/// Messages are kept in memory only and are lost when the server stops.
+#[derive(Default)]
+pub struct MessageScheduler {
+    schedule: Mutex<Schedule>,
+    next_sequence: AtomicU64,
+    limits: Mutex<SchedulerLimits>,
+}
+
+impl MessageScheduler {
+    /// Create an empty scheduler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the limits; messages already scheduled are kept
+    pub fn set_limits(&self, limits: SchedulerLimits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    /// Get the current limits
+    pub fn limits(&self) -> SchedulerLimits {
+        *self.limits.lock().unwrap()
+    }
+
+    /// Hold a message for `delay`, returning its schedule id
+    ///
+    /// Fails if the delay is over the limit or the scheduler is full.
+    pub fn schedule(&self, message: EventMessage, delay: Duration, expires_at: Option<Instant>) -> Result<String> {
+        let limits = self.limits();
+        if delay > limits.max_delay {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("delay of {} ms exceeds the {} ms limit", delay.as_millis(), limits.max_delay.as_millis()),
+            ));
+        }
+
+        let sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let schedule_id = message.id.clone().unwrap_or_else(|| format!("sched-{}", sequence));
+        let deliver_at = (SystemTime::now() + delay)
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or_default();
+        let due = Instant::now() + delay;
+
+        let mut schedule = self.schedule.lock().unwrap();
+        if schedule.messages.contains_key(&schedule_id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("message '{}' is already scheduled", schedule_id),
+            ));
+        }
+        if schedule.messages.len() >= limits.max_messages {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::QuotaExceeded,
+                format!("{} messages are already scheduled", limits.max_messages),
+            ));
+        }
+
+        schedule.queue.push(Reverse((due, sequence, schedule_id.clone())));
+        schedule.messages.insert(schedule_id.clone(), (sequence, ScheduledMessage {
+            schedule_id: schedule_id.clone(),
+            message,
+            deliver_at,
+            expires_at,
+            due,
+        }));
+        Ok(schedule_id)
+    }
+
+    /// Cancel a scheduled message, returning it if it was still pending
+    pub fn cancel(&self, schedule_id: &str) -> Option<ScheduledMessage> {
+        self.schedule.lock().unwrap().remove(schedule_id)
+    }
+
+    /// Get every pending message, the next one due first
+    pub fn list(&self) -> Vec<ScheduledMessage> {
+        let mut messages: Vec<ScheduledMessage> = self.schedule.lock().unwrap().messages.values()
+            .map(|(_, message)| message.clone())
+            .collect();
+        messages.sort_by_key(|message| message.due);
+        messages
+    }
+
+    /// Remove and return the messages due at the given instant, in delivery order
+    pub fn take_due(&self, now: Instant) -> Vec<ScheduledMessage> {
+        let mut schedule = self.schedule.lock().unwrap();
+        let mut due = Vec::new();
+        while schedule.queue.peek().is_some_and(|Reverse((at, _, _))| *at <= now) {
+            let Some(Reverse((_, sequence, schedule_id))) = schedule.queue.pop() else {
+                break;
+            };
+            // Skip entries of messages that were cancelled (and possibly rescheduled)
+            let current = schedule.messages.get(&schedule_id).is_some_and(|(current, _)| *current == sequence);
+            if let Some((_, message)) = current.then(|| schedule.messages.remove(&schedule_id)).flatten() {
+                due.push(message);
+            }
+        }
+        due
+    }
+}
+
+/// Start a background thread that hands scheduled messages to `route` once they are due
+pub fn start_scheduler(
+    pool: Arc<ProducerPool>,
+    interval: Duration,
+    route: impl Fn(ScheduledMessage) + Send + 'static,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        for message in pool.scheduler().take_due(Instant::now()) {
+            route(message);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: Option<&str>, msg: &str) -> EventMessage {
+        EventMessage {
+            id: id.map(str::to_string),
+            ..EventMessage::new(msg.to_string(), "reminders".to_string())
+        }
+    }
+
+    #[test]
+    fn test_due_messages_in_order() {
+        let scheduler = MessageScheduler::new();
+        let now = Instant::now();
+        scheduler.schedule(message(None, "later"), Duration::from_millis(200), None).unwrap();
+        let first = scheduler.schedule(message(None, "first"), Duration::ZERO, None).unwrap();
+        scheduler.schedule(message(Some("r-1"), "second"), Duration::from_millis(100), None).unwrap();
+        assert!(first.starts_with("sched-"));
+        assert!(scheduler.schedule(message(Some("r-1"), "again"), Duration::ZERO, None).is_err());
+
+        let due: Vec<String> = scheduler.take_due(now + Duration::from_millis(150))
+            .into_iter()
+            .map(|scheduled| scheduled.message.msg)
+            .collect();
+        assert_eq!(due, vec!["first", "second"]);
+        assert_eq!(scheduler.list().len(), 1);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let scheduler = MessageScheduler::new();
+        scheduler.schedule(message(Some("r-1"), "stale"), Duration::ZERO, None).unwrap();
+        assert_eq!(scheduler.cancel("r-1").unwrap().message.msg, "stale");
+        assert!(scheduler.cancel("r-1").is_none());
+
+        // A cancelled id can be scheduled again; only the new message is delivered
+        scheduler.schedule(message(Some("r-1"), "fresh"), Duration::ZERO, None).unwrap();
+        let due = scheduler.take_due(Instant::now() + Duration::from_secs(1));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message.msg, "fresh");
+        assert!(scheduler.list().is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        let scheduler = MessageScheduler::new();
+        scheduler.set_limits(SchedulerLimits { max_messages: 2, max_delay: Duration::from_secs(60) });
+
+        let error = scheduler.schedule(message(None, "too late"), Duration::from_secs(61), None).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        scheduler.schedule(message(None, "a"), Duration::from_secs(60), None).unwrap();
+        scheduler.schedule(message(None, "b"), Duration::ZERO, None).unwrap();
+        let error = scheduler.schedule(message(None, "c"), Duration::ZERO, None).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::QuotaExceeded);
+
+        // Delivered messages make room again
+        scheduler.take_due(Instant::now());
+        scheduler.schedule(message(None, "c"), Duration::ZERO, None).unwrap();
+    }
+
+    #[test]
+    fn test_cancel_frees_room() {
+        let scheduler = MessageScheduler::new();
+        scheduler.set_limits(SchedulerLimits { max_messages: 2, max_delay: Duration::from_secs(60) });
+        scheduler.schedule(message(Some("kept"), "kept"), Duration::from_secs(60), None).unwrap();
+
+        // Cancelled messages neither count against the limit nor pile up in the queue
+        for n in 0..1000 {
+            let id = format!("r-{}", n % 3);
+            scheduler.schedule(message(Some(&id), "cancelled"), Duration::from_secs(60), None).unwrap();
+            assert!(scheduler.cancel(&id).is_some());
+        }
+        assert!(scheduler.schedule.lock().unwrap().queue.len() <= 3);
+
+        scheduler.schedule(message(None, "due"), Duration::ZERO, None).unwrap();
+        let due = scheduler.take_due(Instant::now());
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message.msg, "due");
+        assert_eq!(scheduler.list()[0].schedule_id, "kept");
+    }
+}
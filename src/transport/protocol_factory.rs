@@ -1,24 +1,44 @@
 use std::io::Result;
 use std::sync::Arc;
 
-use crate::transport::protocols::TcpTransport;
-use crate::transport::server_config::ProtocolType;
+use crate::transport::producers::get_producer_pool;
+use crate::transport::protocols::{TcpHandoff, TcpTransport};
+use crate::transport::push::PushSettings;
+use crate::transport::registration_server::{registration_handoff_handler, REGISTRATION_HANDOFF_KEYWORD};
+use crate::transport::server_config::{ConnectionLimits, ListenerConfig, ProtocolType, RegistrationConfig};
 use crate::transport::transport_interface::TransportProtocol;
 
 /// Factory for creating transport protocol instances based on configuration
 pub struct ProtocolFactory;
 
 impl ProtocolFactory {
-    /// Create a transport protocol instance based on the protocol type
-    pub fn create(protocol_type: ProtocolType, address: &str) -> Result<Arc<dyn TransportProtocol>> {
-        match protocol_type {
+    /// Create a transport protocol instance for an ingress listener,
+    /// attaching the registration handoff when registration shares the ingress port
+    /// and push subscriptions when runtime registration is enabled
+    pub fn create_for_listener(
+        listener: &ListenerConfig,
+        registration: &RegistrationConfig,
+        limits: ConnectionLimits,
+    ) -> Result<Arc<dyn TransportProtocol>> {
+        match listener.protocol {
+            ProtocolType::Tcp if !registration.enabled => {
+                Ok(Arc::new(TcpTransport::new(listener.address.clone()).with_limits(limits)))
+            }
             ProtocolType::Tcp => {
-                Ok(Arc::new(TcpTransport::new(address.to_string())))
+                let transport = if registration.shared_with_ingress {
+                    let handoff = TcpHandoff::new(
+                        REGISTRATION_HANDOFF_KEYWORD,
+                        registration_handoff_handler(registration)?,
+                    );
+                    TcpTransport::with_handoff(listener.address.clone(), handoff)
+                } else {
+                    TcpTransport::new(listener.address.clone())
+                };
+                
+                // Subscribing over a connection is runtime registration, guarded by the same tokens
+                let push = PushSettings::new(get_producer_pool(), registration.auth.clone());
+                Ok(Arc::new(transport.with_push(Arc::new(push)).with_limits(limits)))
             }
-            // Future protocols can be added here:
-            // ProtocolType::Udp => {
-            //     Ok(Arc::new(UdpTransport::new(address.to_string())))
-            // }
         }
     }
 
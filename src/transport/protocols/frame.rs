@@ -0,0 +1,111 @@
+use std::io::{ErrorKind, Result};
+
+/// One newline-terminated frame read from a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// A complete line, including its line terminator if it had one
+    Line(String),
+    /// A line longer than the frame limit; only its size was counted
+    TooLarge(usize),
+}
+
+/// Splits the bytes read from a connection into frames
+///
+/// At most `max_size` bytes of a frame are kept in memory. Bytes past the
+/// limit are counted and discarded up to the end of the line, so the
+/// connection can carry on with the next frame.
+pub struct FrameDecoder {
+    max_size: usize,
+    line: Vec<u8>,
+    size: usize,
+}
+
+impl FrameDecoder {
+    /// Create a decoder for frames of up to `max_size` bytes, newline included
+    pub fn new(max_size: usize) -> Self {
+        Self { max_size, line: Vec::new(), size: 0 }
+    }
+
+    /// Check whether part of a frame has been received
+    pub fn is_partial(&self) -> bool {
+        self.size > 0
+    }
+
+    /// Add bytes read from the connection, returning the frames they complete
+    pub fn decode(&mut self, mut bytes: &[u8]) -> Result<Vec<Frame>> {
+        let mut frames = Vec::new();
+        while !bytes.is_empty() {
+            let (chunk, rest, complete) = match bytes.iter().position(|&byte| byte == b'\n') {
+                Some(end) => (&bytes[..=end], &bytes[end + 1..], true),
+                None => (bytes, &[][..], false),
+            };
+            self.push(chunk);
+            if complete {
+                frames.push(self.take()?);
+            }
+            bytes = rest;
+        }
+        Ok(frames)
+    }
+
+    /// Get the unterminated frame left when the connection closes, if any
+    pub fn finish(&mut self) -> Result<Option<Frame>> {
+        if self.is_partial() {
+            self.take().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        if self.size + chunk.len() <= self.max_size {
+            self.line.extend_from_slice(chunk);
+        } else {
+            self.line = Vec::new();
+        }
+        self.size += chunk.len();
+    }
+
+    fn take(&mut self) -> Result<Frame> {
+        let line = std::mem::take(&mut self.line);
+        let size = std::mem::take(&mut self.size);
+        if size > self.max_size {
+            return Ok(Frame::TooLarge(size));
+        }
+        String::from_utf8(line)
+            .map(Frame::Line)
+            .map_err(|_| std::io::Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oversized_frames_are_skipped() {
+        let mut decoder = FrameDecoder::new(10);
+        let mut frames = Vec::new();
+        // Small reads make long lines span several of them
+        for chunk in b"hello\nthis line is too long\nok\npartial".chunks(4) {
+            frames.extend(decoder.decode(chunk).unwrap());
+        }
+        assert!(decoder.is_partial());
+        frames.extend(decoder.finish().unwrap());
+
+        assert_eq!(frames, vec![
+            Frame::Line("hello\n".to_string()),
+            Frame::TooLarge(22),
+            Frame::Line("ok\n".to_string()),
+            Frame::Line("partial".to_string()),
+        ]);
+        assert_eq!(decoder.finish().unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_an_error() {
+        let mut decoder = FrameDecoder::new(100);
+        assert_eq!(decoder.decode(b"\xff\n").unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(decoder.decode(b"next\n").unwrap(), vec![Frame::Line("next\n".to_string())]);
+    }
+}
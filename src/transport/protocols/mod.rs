@@ -1,3 +1,4 @@
+pub mod frame;
 pub mod tcp;
 
-pub use tcp::TcpTransport;
+pub use tcp::{TcpTransport, TcpHandoff, TcpHandoffHandler};
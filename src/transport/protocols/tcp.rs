@@ -1,10 +1,18 @@
-use std::io::{BufRead, BufReader, ErrorKind, Result};
-use std::net::{TcpListener, TcpStream};
+use std::io::{ErrorKind, Result, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::sync::Arc;
-use std::thread;
 
+use crate::transport::ack::{AckMode, Acknowledger};
+use crate::transport::push::{PushCommand, PushSession, PushSettings, PUSH_WRITE_TIMEOUT};
+use crate::transport::reactor::{get_reactor, CloseReason, ConnectionGroup, ConnectionHandler, Flow};
+use crate::transport::request_reply::ConnectionWriter;
+use crate::transport::server_config::ConnectionLimits;
+use crate::transport::server_handle::ServerControl;
+use crate::transport::shutdown;
 use crate::transport::transport_interface::{MessageConsumer, TransportProtocol};
 
+use super::frame::Frame;
+
 /// Format error message with Windows-specific suggestions
 fn format_error(e: &std::io::Error) -> String {
     match e.kind() {
@@ -21,23 +29,68 @@ fn format_error(e: &std::io::Error) -> String {
     }
 }
 
+/// Builds the handler that takes over an ingress connection after its first line
+pub type TcpHandoffHandler = Arc<dyn Fn(&TcpStream) -> Result<Box<dyn ConnectionHandler>> + Send + Sync>;
+
+/// Connection handoff triggered when the first line of a connection equals a keyword
+#[derive(Clone)]
+pub struct TcpHandoff {
+    keyword: String,
+    handler: TcpHandoffHandler,
+}
+
+impl TcpHandoff {
+    /// Create a handoff for connections whose first line is `keyword` (case-insensitive)
+    pub fn new(keyword: &str, handler: TcpHandoffHandler) -> Self {
+        Self {
+            keyword: keyword.to_string(),
+            handler,
+        }
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        line.trim().eq_ignore_ascii_case(&self.keyword)
+    }
+}
+
 /// TCP transport protocol implementation
-pub struct TcpTransport;
+pub struct TcpTransport {
+    handoff: Option<TcpHandoff>,
+    push: Option<Arc<PushSettings>>,
+    limits: ConnectionLimits,
+}
 
 impl TcpTransport {
     /// Create a new TCP transport instance
     pub fn new(_address: String) -> Self {
-        Self
+        Self { handoff: None, push: None, limits: ConnectionLimits::default() }
+    }
+
+    /// Create a TCP transport that hands matching connections to another handler
+    pub fn with_handoff(_address: String, handoff: TcpHandoff) -> Self {
+        Self { handoff: Some(handoff), push: None, limits: ConnectionLimits::default() }
+    }
+
+    /// Replace the default connection and message size limits
+    pub fn with_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Let connections `SUBSCRIBE` to events and get them pushed back on the same socket
+    pub fn with_push(mut self, push: Arc<PushSettings>) -> Self {
+        self.push = Some(push);
+        self
     }
 }
 
 impl TransportProtocol for TcpTransport {
-    fn listen(&self, address: &str, consumer: MessageConsumer) -> Result<()> {
+    fn listen(&self, address: &str, consumer: MessageConsumer, control: Arc<ServerControl>) -> Result<()> {
         // Try to bind with better error handling
         let listener = match TcpListener::bind(address) {
             Ok(listener) => listener,
             Err(e) => {
-                eprintln!("Failed to bind TCP listener to {}: {}", address, e);
+                eprintln!("Failed to bind TCP listener '{}' to {}: {}", control.name(), address, e);
                 eprintln!("Error details: {}", format_error(&e));
                 eprintln!("Suggestions:");
                 eprintln!("  1. Check if the port is already in use");
@@ -49,28 +102,53 @@ impl TransportProtocol for TcpTransport {
             }
         };
         
+        let local_addr = listener.local_addr()?;
         let consumer = Arc::new(consumer);
+        shutdown::register_listener(local_addr);
+        control.notify_bound(Ok(local_addr));
 
-        println!("TCP Transport successfully listening on {}", address);
+        println!("TCP Transport '{}' successfully listening on {}", control.name(), local_addr);
+
+        // Connections are served by the shared reactor; this thread only accepts them
+        let reactor = get_reactor()?;
+        let connections = Arc::new(ConnectionGroup::new());
 
         for stream in listener.incoming() {
+            // Dropping the listener stops accepting connections
+            if control.is_stopping() {
+                break;
+            }
+            
             match stream {
-                Ok(stream) => {
-                    let consumer = Arc::clone(&consumer);
-                    let addr = stream.peer_addr()?;
+                Ok(mut stream) => {
+                    if connections.count() >= self.limits.max_connections {
+                        eprintln!(
+                            "Refusing connection from {} on '{}': {} connections already open",
+                            stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default(),
+                            control.name(),
+                            self.limits.max_connections,
+                        );
+                        let _ = stream.write_all(b"ERROR too many connections\n");
+                        let _ = stream.shutdown(Shutdown::Both);
+                        continue;
+                    }
                     
-                    thread::spawn(move || {
-                        if let Err(e) = handle_client(stream, consumer) {
-                            eprintln!("Error handling client {}: {}", addr, e);
-                        }
-                    });
+                    control.record_connection();
+                    let result = IngressConnection::new(&stream, self, Arc::clone(&consumer), Arc::clone(&control))
+                        .and_then(|handler| reactor.register(stream, Box::new(handler), self.limits, &connections));
+                    if let Err(e) = result {
+                        eprintln!("Error accepting client on '{}': {}", control.name(), e);
+                    }
                 }
                 Err(e) => {
-                    eprintln!("Connection failed: {}", e);
+                    eprintln!("Connection failed on '{}': {}", control.name(), e);
                 }
             }
         }
 
+        // Stop reading from open connections; messages already read still finish
+        connections.shutdown_read();
+
         Ok(())
     }
 
@@ -79,20 +157,142 @@ impl TransportProtocol for TcpTransport {
     }
 }
 
-/// Handle individual client connection
-fn handle_client(stream: TcpStream, consumer: Arc<MessageConsumer>) -> Result<()> {
-    let reader = BufReader::new(&stream);
-    
-    for line in reader.lines() {
-        let message = line?;
+/// Ingress protocol of one client connection
+struct IngressConnection {
+    consumer: Arc<MessageConsumer>,
+    handoff: Option<TcpHandoff>,
+    // Removes this connection's push subscriber from the pool when dropped
+    push_session: Option<PushSession>,
+    // Shared with requests waiting for a reply from another connection
+    writer: ConnectionWriter,
+    peer: SocketAddr,
+    control: Arc<ServerControl>,
+    max_frame_size: usize,
+    first_line: bool,
+    ack_mode: AckMode,
+    sequence: u64,
+    subscribed: bool,
+}
+
+impl IngressConnection {
+    fn new(
+        stream: &TcpStream,
+        transport: &TcpTransport,
+        consumer: Arc<MessageConsumer>,
+        control: Arc<ServerControl>,
+    ) -> Result<Self> {
+        let peer = stream.peer_addr()?;
+        let writer = ConnectionWriter::new(stream.try_clone()?);
+        // A client that stops reading must not stall a shared worker for long
+        stream.set_write_timeout(Some(PUSH_WRITE_TIMEOUT))?;
+        
+        Ok(Self {
+            consumer,
+            handoff: transport.handoff.clone(),
+            push_session: transport.push.clone().map(|settings| PushSession::new(settings, peer, writer.clone())),
+            writer,
+            peer,
+            control,
+            max_frame_size: transport.limits.max_frame_size,
+            first_line: true,
+            ack_mode: AckMode::Off,
+            sequence: 0,
+            subscribed: false,
+        })
+    }
+}
+
+impl ConnectionHandler for IngressConnection {
+    fn on_frame(&mut self, frame: Frame, stream: &TcpStream) -> Result<Flow> {
+        let first_line = std::mem::take(&mut self.first_line);
+        let line = match frame {
+            Frame::Line(line) => line,
+            Frame::TooLarge(size) => {
+                self.sequence += 1;
+                let mut ack_writer = self.writer.clone();
+                let reason = format!("message of {} bytes exceeds the {} byte limit", size, self.max_frame_size);
+                eprintln!("Rejected message from {} on '{}': {}", self.peer, self.control.name(), reason);
+                Acknowledger::new(self.ack_mode, self.sequence, &mut ack_writer).rejected(None, &reason);
+                return Ok(Flow::Continue);
+            }
+        };
         
+        // The first line may switch the connection to another handler
+        if first_line
+            && let Some(handoff) = self.handoff.as_ref().filter(|handoff| handoff.matches(&line))
+        {
+            return Ok(Flow::Replace((handoff.handler)(stream)?));
+        }
+        
+        let message = line.trim_end_matches(['\r', '\n']);
         if message.is_empty() {
-            continue;
+            return Ok(Flow::Continue);
+        }
+        
+        // Producers opt into per-message replies with `ACKMODE <mode>`
+        if let Some(result) = AckMode::from_command(message) {
+            let reply = match result {
+                Ok(mode) => {
+                    self.ack_mode = mode;
+                    format!("OK ACKMODE {}", mode.as_str())
+                }
+                Err(e) => format!("ERROR {}", e),
+            };
+            self.writer.write_line(&reply)?;
+            return Ok(Flow::Continue);
         }
         
-        // Consume the message
-        consumer(message);
+        // `SUBSCRIBE <event>...` turns the connection into a push subscriber
+        if let (Some(session), Some(result)) = (self.push_session.as_mut(), PushCommand::from_line(message)) {
+            let reply = match result {
+                Ok(command) => session.execute(command),
+                Err(e) => format!("ERROR {}", e),
+            };
+            self.subscribed = session.is_subscribed();
+            self.writer.write_line(&reply)?;
+            return Ok(Flow::Continue);
+        }
+        
+        self.sequence += 1;
+        let (ack_mode, sequence, writer, peer) = (self.ack_mode, self.sequence, self.writer.clone(), self.peer.ip());
+        let identity = self.push_session.as_ref().and_then(PushSession::identity);
+        
+        // Track the message before checking for shutdown so draining waits for it
+        let in_flight = shutdown::track_message();
+        if self.control.is_stopping() {
+            let mut ack_writer = writer;
+            Acknowledger::new(ack_mode, sequence, &mut ack_writer).rejected(None, "server shutting down");
+            return Ok(Flow::Close);
+        }
+        
+        // Routing may wait on slow consumers, so it runs off the connection workers
+        self.control.record_message();
+        let consumer = Arc::clone(&self.consumer);
+        let message = message.to_string();
+        Ok(Flow::Defer(Box::new(move || {
+            let _in_flight = in_flight;
+            let mut ack_writer = writer.clone();
+            let mut ack = Acknowledger::new(ack_mode, sequence, &mut ack_writer)
+                .with_connection(writer)
+                .with_origin(peer, identity);
+            consumer(message, &mut ack);
+        })))
+    }
+
+    fn on_close(&mut self, reason: &CloseReason) {
+        let name = self.control.name();
+        match reason {
+            CloseReason::Closed => {}
+            CloseReason::IdleTimeout => println!("Closing connection {} on '{}': timed out idle", self.peer, name),
+            CloseReason::ReadTimeout => {
+                println!("Closing connection {} on '{}': timed out sending a message", self.peer, name)
+            }
+            CloseReason::Failed(e) => eprintln!("Error handling client {} on '{}': {}", self.peer, name, e),
+        }
+    }
+
+    // Push subscribers only receive, so they are never idle
+    fn is_idle_exempt(&self) -> bool {
+        self.subscribed
     }
-    
-    Ok(())
 }
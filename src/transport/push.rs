@@ -0,0 +1,254 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::transport::clients::ConnectionClient;
+use crate::transport::producers::{Producer, ProducerPool};
+use crate::transport::registration_auth::{RegistrationAuth, TokenScope};
+use crate::transport::request_reply::ConnectionWriter;
+
+/// Longest a push to a subscriber that stopped reading may block routing
+pub const PUSH_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prefix of the producer ids given to push subscribers, reserved for them
+pub const PUSH_PRODUCER_PREFIX: &str = "push-";
+
+/// Check that a producer id registered by other means doesn't use the push prefix
+pub fn check_not_reserved(producer_id: &str) -> std::io::Result<()> {
+    if producer_id.starts_with(PUSH_PRODUCER_PREFIX) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("producer ids starting with '{}' are reserved for push subscribers", PUSH_PRODUCER_PREFIX),
+        ));
+    }
+    Ok(())
+}
+
+/// Subscription control line sent on an ingress connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushCommand {
+    /// `AUTH <token>`, needed first when registration tokens are configured
+    Auth(String),
+    /// `SUBSCRIBE <event> [event...]`
+    Subscribe(Vec<String>),
+    /// `UNSUBSCRIBE <event> [event...]`
+    Unsubscribe(Vec<String>),
+}
+
+impl PushCommand {
+    /// Parse a control line
+    ///
+    /// Returns `None` for ordinary messages and `Some(Err(..))` for a malformed command.
+    pub fn from_line(line: &str) -> Option<Result<Self, String>> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next()?.to_uppercase();
+        let args: Vec<String> = parts.map(str::to_string).collect();
+
+        Some(match command.as_str() {
+            "AUTH" if args.len() == 1 => Ok(PushCommand::Auth(args[0].clone())),
+            "AUTH" => Err("Usage: AUTH <token>".to_string()),
+            "SUBSCRIBE" if !args.is_empty() => Ok(PushCommand::Subscribe(args)),
+            "SUBSCRIBE" => Err("Usage: SUBSCRIBE <event> [event...]".to_string()),
+            "UNSUBSCRIBE" if !args.is_empty() => Ok(PushCommand::Unsubscribe(args)),
+            "UNSUBSCRIBE" => Err("Usage: UNSUBSCRIBE <event> [event...]".to_string()),
+            _ => return None,
+        })
+    }
+}
+
+/// Push settings shared by every connection of a listener
+pub struct PushSettings {
+    pool: Arc<ProducerPool>,
+    auth: RegistrationAuth,
+}
+
+impl PushSettings {
+    /// Create push settings adding subscribers to `pool`, guarded by the registration tokens
+    pub fn new(pool: Arc<ProducerPool>, auth: RegistrationAuth) -> Self {
+        Self { pool, auth }
+    }
+}
+
+/// Push subscriptions of one ingress connection
+///
+/// The first `SUBSCRIBE` adds a producer named `push-<peer address>` whose
+/// client writes to the connection. It is removed from the pool when the
+/// session is dropped, i.e. when the connection closes.
+pub struct PushSession {
+    settings: Arc<PushSettings>,
+    id: String,
+    peer: String,
+    writer: ConnectionWriter,
+    scope: Option<TokenScope>,
+    registered: bool,
+}
+
+impl PushSession {
+    /// Create the push session for a connection
+    pub fn new(settings: Arc<PushSettings>, peer: SocketAddr, writer: ConnectionWriter) -> Self {
+        Self {
+            settings,
+            id: format!("{}{}", PUSH_PRODUCER_PREFIX, peer),
+            peer: peer.to_string(),
+            writer,
+            scope: None,
+            registered: false,
+        }
+    }
+
+    /// Run a command, returning the `OK ...`/`ERROR ...` reply line
+    pub fn execute(&mut self, command: PushCommand) -> String {
+        match self.try_execute(command) {
+            Ok(reply) => format!("OK {}", reply),
+            Err(e) => format!("ERROR {}", e),
+        }
+    }
+
+    /// Check whether the connection has pushed events to receive
+    pub fn is_subscribed(&self) -> bool {
+        self.registered && !self.events().is_empty()
+    }
+
+    /// Get the identity the connection authenticated as: its token's prefixes
+    pub fn identity(&self) -> Option<String> {
+        self.scope.as_ref().map(|scope| scope.prefixes().join(","))
+    }
+
+    fn try_execute(&mut self, command: PushCommand) -> std::io::Result<String> {
+        let pool = &self.settings.pool;
+
+        match command {
+            PushCommand::Auth(token) => {
+                self.scope = Some(self.settings.auth.authenticate(&token)?);
+                Ok("AUTH".to_string())
+            }
+            PushCommand::Subscribe(events) => {
+                self.settings.auth.authorize(self.scope.as_ref(), &self.id)?;
+                if !self.registered {
+                    let client = ConnectionClient::new(self.writer.clone(), self.peer.clone());
+                    pool.add_producer(self.id.clone(), Arc::new(client))?;
+                    self.registered = true;
+                }
+                for event in &events {
+                    pool.subscribe_producer_to_event(&self.id, event)?;
+                }
+                Ok(format!("SUBSCRIBE {} {}", self.id, self.events().join(" ")))
+            }
+            PushCommand::Unsubscribe(events) => {
+                if self.registered {
+                    for event in &events {
+                        pool.unsubscribe_producer_from_event(&self.id, event)?;
+                    }
+                }
+                Ok(format!("UNSUBSCRIBE {} {}", self.id, self.events().join(" ")).trim_end().to_string())
+            }
+        }
+    }
+
+    fn events(&self) -> Vec<String> {
+        let mut events = self.settings.pool.get_producer(&self.id)
+            .map(|producer| producer.subscribed_events())
+            .unwrap_or_default();
+        events.sort();
+        events
+    }
+}
+
+impl Drop for PushSession {
+    fn drop(&mut self) {
+        // A new connection from the same address may have taken over the id
+        let is_ours = |producer: &Producer| {
+            producer.connection().is_some_and(|connection| connection.is_same_connection(&self.writer))
+        };
+        if self.registered && self.settings.pool.remove_producer_if(&self.id, is_ours).is_some() {
+            println!("[Push] Connection {} closed, removed producer '{}'", self.peer, self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(auth: RegistrationAuth) -> (PushSession, Arc<ProducerPool>, Arc<std::sync::Mutex<Vec<u8>>>) {
+        struct Recorder(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let pool = Arc::new(ProducerPool::new());
+        let output = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let settings = Arc::new(PushSettings::new(Arc::clone(&pool), auth));
+        let writer = ConnectionWriter::new(Recorder(Arc::clone(&output)));
+        let session = PushSession::new(settings, "10.0.0.7:5000".parse().unwrap(), writer);
+        (session, pool, output)
+    }
+
+    #[test]
+    fn test_parse_push_commands() {
+        assert_eq!(
+            PushCommand::from_line("subscribe a b"),
+            Some(Ok(PushCommand::Subscribe(vec!["a".to_string(), "b".to_string()]))),
+        );
+        assert!(matches!(PushCommand::from_line("SUBSCRIBE"), Some(Err(_))));
+        assert_eq!(PushCommand::from_line("user_login:SUBSCRIBE a"), None);
+    }
+
+    #[test]
+    fn test_subscribe_pushes_and_cleans_up_on_drop() {
+        let (mut session, pool, output) = session(RegistrationAuth::disabled());
+        assert_eq!(
+            session.execute(PushCommand::Subscribe(vec!["b".to_string(), "a".to_string()])),
+            "OK SUBSCRIBE push-10.0.0.7:5000 a b",
+        );
+        assert_eq!(
+            session.execute(PushCommand::Unsubscribe(vec!["b".to_string()])),
+            "OK UNSUBSCRIBE push-10.0.0.7:5000 a",
+        );
+
+        pool.forward_to_event("a", "hello");
+        pool.forward_to_event("b", "not subscribed");
+        assert_eq!(String::from_utf8(output.lock().unwrap().clone()).unwrap(), "hello\n");
+
+        drop(session);
+        assert!(!pool.has_producer("push-10.0.0.7:5000"));
+        assert!(pool.get_event_subscribers("a").is_empty());
+    }
+
+    #[test]
+    fn test_closing_a_replaced_session_keeps_the_new_subscriber() {
+        let (mut old, pool, _) = session(RegistrationAuth::disabled());
+        old.execute(PushCommand::Subscribe(vec!["a".to_string()]));
+
+        // A new connection from the same address takes over the producer id
+        let settings = Arc::new(PushSettings::new(Arc::clone(&pool), RegistrationAuth::disabled()));
+        let writer = ConnectionWriter::new(std::io::sink());
+        let mut new = PushSession::new(settings, "10.0.0.7:5000".parse().unwrap(), writer);
+        new.execute(PushCommand::Subscribe(vec!["a".to_string()]));
+
+        drop(old);
+        assert!(pool.has_producer("push-10.0.0.7:5000"));
+        drop(new);
+        assert!(!pool.has_producer("push-10.0.0.7:5000"));
+    }
+
+    #[test]
+    fn test_subscribe_requires_token_when_enabled() {
+        let (mut session, pool, _) = session(RegistrationAuth::parse("team=team-;pusher=push-").unwrap());
+        assert!(session.execute(PushCommand::Subscribe(vec!["a".to_string()])).starts_with("ERROR"));
+
+        session.execute(PushCommand::Auth("team".to_string()));
+        assert!(session.execute(PushCommand::Subscribe(vec!["a".to_string()])).starts_with("ERROR"));
+        assert!(!pool.has_producer("push-10.0.0.7:5000"));
+
+        assert_eq!(session.execute(PushCommand::Auth("pusher".to_string())), "OK AUTH");
+        assert_eq!(session.identity().as_deref(), Some("push-"));
+        assert!(session.execute(PushCommand::Subscribe(vec!["a".to_string()])).starts_with("OK"));
+        assert!(pool.has_producer("push-10.0.0.7:5000"));
+    }
+}
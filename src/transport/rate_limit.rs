@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Number of idle buckets kept before full ones are pruned
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// Limit key that applies to every event without its own limit
+pub const ANY_EVENT: &str = "*";
+
+/// Longest a delayed message is held before it is NACKed instead
+pub const DEFAULT_MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(5);
+
+/// Sustained rate and burst allowed for one key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Messages per second refilled into the bucket
+    pub per_second: f64,
+    /// Bucket size, i.e. messages allowed at once after a quiet period
+    pub burst: f64,
+}
+
+/// What happens to a message over its limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Reply `NACK <id> rate limit exceeded ...` and drop the message
+    #[default]
+    Nack,
+    /// Drop the message, acknowledging it as `ACK <id> dropped`
+    Drop,
+    /// Hold the connection until the message fits the limit, then route it;
+    /// messages that would wait longer than the max delay are NACKed
+    Delay,
+}
+
+impl RateLimitAction {
+    /// Convert string to RateLimitAction
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "NACK" => Some(RateLimitAction::Nack),
+            "DROP" => Some(RateLimitAction::Drop),
+            "DELAY" => Some(RateLimitAction::Delay),
+            _ => None,
+        }
+    }
+}
+
+/// Rate limits applied to messages from ingress connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    pub action: RateLimitAction,
+    /// Longest wait the `delay` action holds a message for
+    pub max_delay: Duration,
+    /// Limit per peer IP address
+    pub per_peer: Option<RateLimit>,
+    /// Limit per authenticated identity (connections that sent `AUTH`)
+    pub per_identity: Option<RateLimit>,
+    /// Limit per event name, shared by all publishers; `*` covers unlisted events
+    pub per_event: HashMap<String, RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            action: RateLimitAction::default(),
+            max_delay: DEFAULT_MAX_RATE_LIMIT_DELAY,
+            per_peer: None,
+            per_identity: None,
+            per_event: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Get the limit of one key
+    fn limit(&self, kind: LimitKind, key: &str) -> Option<RateLimit> {
+        match kind {
+            LimitKind::Peer => self.per_peer,
+            LimitKind::Identity => self.per_identity,
+            LimitKind::Event => self.per_event.get(key).or_else(|| self.per_event.get(ANY_EVENT)).copied(),
+        }
+    }
+}
+
+/// Kind of key a limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    Peer,
+    Identity,
+    Event,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitKind::Peer => write!(f, "peer"),
+            LimitKind::Identity => write!(f, "identity"),
+            LimitKind::Event => write!(f, "event"),
+        }
+    }
+}
+
+/// Message that went over a limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimited {
+    pub kind: LimitKind,
+    pub key: String,
+    /// How long until the message would fit the limit
+    pub wait: Duration,
+    /// Whether the tokens were taken, i.e. the message may go after `wait`
+    pub reserved: bool,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limit exceeded for {} '{}'", self.kind, self.key)
+    }
+}
+
+/// Messages over each kind of limit since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RateLimitCounts {
+    pub peer: u64,
+    pub identity: u64,
+    pub event: u64,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimitCounts {
+    /// Number of messages over any limit
+    pub fn total(&self) -> u64 {
+        self.peer + self.identity + self.event
+    }
+}
+
+impl TokenBucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.updated = now;
+    }
+}
+
+/// Token buckets per peer, identity and event
+#[derive(Default)]
+pub struct RateLimiter {
+    config: Mutex<RateLimitConfig>,
+    buckets: Mutex<HashMap<(LimitKind, String), TokenBucket>>,
+    limited_peer: AtomicU64,
+    limited_identity: AtomicU64,
+    limited_event: AtomicU64,
+}
+
+impl RateLimiter {
+    /// Create a limiter without any limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the limits; buckets restart full
+    pub fn set_config(&self, config: RateLimitConfig) {
+        *self.config.lock().unwrap() = config;
+        self.buckets.lock().unwrap().clear();
+    }
+
+    /// Get the configured action for messages over a limit
+    pub fn action(&self) -> RateLimitAction {
+        self.config.lock().unwrap().action
+    }
+
+    /// Get the longest wait the `delay` action holds a message for
+    pub fn max_delay(&self) -> Duration {
+        self.config.lock().unwrap().max_delay
+    }
+
+    /// Take one token from every bucket that applies to a message
+    ///
+    /// With `max_wait` set the tokens are taken even when a bucket runs short,
+    /// as long as the message fits the limit within `max_wait`; the returned
+    /// `wait` then says how long the caller must hold the message. Otherwise
+    /// nothing is taken from any bucket when one of them is short.
+    pub fn check(
+        &self,
+        peer: Option<&str>,
+        identity: Option<&str>,
+        event_name: &str,
+        max_wait: Option<Duration>,
+        now: Instant,
+    ) -> Result<(), RateLimited> {
+        let limits = {
+            let config = self.config.lock().unwrap();
+            [(LimitKind::Peer, peer), (LimitKind::Identity, identity), (LimitKind::Event, Some(event_name))]
+                .into_iter()
+                .filter_map(|(kind, key)| {
+                    let key = key?;
+                    Some(((kind, key.to_string()), config.limit(kind, key)?))
+                })
+                .collect::<Vec<_>>()
+        };
+        if limits.is_empty() {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            let config = self.config.lock().unwrap().clone();
+            // A full bucket behaves the same as a missing one
+            buckets.retain(|(kind, key), bucket| {
+                config.limit(*kind, key).is_some_and(|limit| {
+                    bucket.refill(limit, now);
+                    bucket.tokens < limit.burst
+                })
+            });
+        }
+
+        // The longest wait decides; its limit is the one reported
+        let mut limited: Option<RateLimited> = None;
+        for (key, limit) in &limits {
+            let bucket = buckets.entry(key.clone())
+                .or_insert(TokenBucket { tokens: limit.burst, updated: now });
+            bucket.refill(*limit, now);
+            if bucket.tokens < 1.0 {
+                let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_second);
+                if limited.as_ref().is_none_or(|limited| wait > limited.wait) {
+                    limited = Some(RateLimited { kind: key.0, key: key.1.clone(), wait, reserved: false });
+                }
+            }
+        }
+
+        if let Some(limited) = &mut limited {
+            limited.reserved = max_wait.is_some_and(|max_wait| limited.wait <= max_wait);
+        }
+        if limited.as_ref().is_none_or(|limited| limited.reserved) {
+            for (key, _) in &limits {
+                if let Some(bucket) = buckets.get_mut(key) {
+                    bucket.tokens -= 1.0;
+                }
+            }
+        }
+
+        match limited {
+            Some(limited) => {
+                let counter = match limited.kind {
+                    LimitKind::Peer => &self.limited_peer,
+                    LimitKind::Identity => &self.limited_identity,
+                    LimitKind::Event => &self.limited_event,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+                Err(limited)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Number of messages over each kind of limit since startup
+    pub fn counts(&self) -> RateLimitCounts {
+        RateLimitCounts {
+            peer: self.limited_peer.load(Ordering::Relaxed),
+            identity: self.limited_identity.load(Ordering::Relaxed),
+            event: self.limited_event.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        let limiter = RateLimiter::new();
+        limiter.set_config(RateLimitConfig {
+            action: RateLimitAction::Nack,
+            max_delay: DEFAULT_MAX_RATE_LIMIT_DELAY,
+            per_peer: Some(RateLimit { per_second: 10.0, burst: 2.0 }),
+            per_identity: None,
+            per_event: HashMap::from([
+                ("alerts".to_string(), RateLimit { per_second: 1.0, burst: 1.0 }),
+            ]),
+        });
+        limiter
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert!(limiter.check(Some("10.0.0.1"), None, "orders", None, now).is_ok());
+        assert!(limiter.check(Some("10.0.0.1"), None, "orders", None, now).is_ok());
+        let limited = limiter.check(Some("10.0.0.1"), None, "orders", None, now).unwrap_err();
+        assert_eq!(limited.to_string(), "rate limit exceeded for peer '10.0.0.1'");
+        assert_eq!(limited.wait, Duration::from_millis(100));
+
+        // Other peers have their own bucket, and tokens come back over time
+        assert!(limiter.check(Some("10.0.0.2"), None, "orders", None, now).is_ok());
+        assert!(limiter.check(Some("10.0.0.1"), None, "orders", None, now + Duration::from_millis(100)).is_ok());
+        assert_eq!(limiter.counts(), RateLimitCounts { peer: 1, identity: 0, event: 0 });
+    }
+
+    #[test]
+    fn test_short_bucket_takes_nothing_unless_reserved() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert!(limiter.check(Some("10.0.0.1"), None, "alerts", None, now).is_ok());
+        let limited = limiter.check(Some("10.0.0.1"), None, "alerts", None, now).unwrap_err();
+        assert_eq!(limited.kind, LimitKind::Event);
+
+        // The rejected alert didn't use the peer's second token
+        assert!(limiter.check(Some("10.0.0.1"), None, "orders", None, now).is_ok());
+
+        // A reservation waits for the next token and queues behind it
+        let max_wait = Some(Duration::from_secs(2));
+        let reserved = limiter.check(None, None, "alerts", max_wait, now).unwrap_err();
+        assert_eq!((reserved.wait, reserved.reserved), (Duration::from_secs(1), true));
+        let reserved = limiter.check(None, None, "alerts", max_wait, now).unwrap_err();
+        assert_eq!((reserved.wait, reserved.reserved), (Duration::from_secs(2), true));
+        assert_eq!(limiter.counts().event, 3);
+    }
+
+    #[test]
+    fn test_reservations_stop_at_the_max_wait() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let max_wait = Some(Duration::from_secs(2));
+
+        // A flood only queues up to the max wait; the rest is refused without taking tokens
+        assert!(limiter.check(None, None, "alerts", max_wait, now).is_ok());
+        for _ in 0..100 {
+            let limited = limiter.check(None, None, "alerts", max_wait, now).unwrap_err();
+            assert!(limited.wait <= Duration::from_secs(3));
+        }
+        let limited = limiter.check(None, None, "alerts", max_wait, now).unwrap_err();
+        assert_eq!((limited.wait, limited.reserved), (Duration::from_secs(3), false));
+
+        // Once the queue drains the next message is reserved again
+        let later = now + Duration::from_secs(1);
+        let limited = limiter.check(None, None, "alerts", max_wait, later).unwrap_err();
+        assert_eq!((limited.wait, limited.reserved), (Duration::from_secs(2), true));
+    }
+}
@@ -0,0 +1,540 @@
+//! Readiness-based connection handling shared by every listener
+//!
+//! Listeners only accept connections and hand them to the reactor. One
+//! thread waits for any registered connection to become readable and queues
+//! it for a bounded pool of workers, which read what arrived and pass whole
+//! lines to the connection's handler. A connection is not polled again until
+//! its worker is done with it, so its lines are handled one at a time and in
+//! order, while idle connections cost no thread at all.
+//!
+//! Workers never wait on anything but their own connection's socket. Work
+//! that may block, like routing a message to slow consumers, is deferred by
+//! the handler to a separate pool of message threads. Its connection is
+//! parked meanwhile: it is not read again, and its next frame is not
+//! handled, until the deferred work is done. The message thread then handles
+//! the frames read along with it before the connection is watched again.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{ErrorKind, Read, Result};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use polling::{Event, Events, Poller};
+
+use crate::transport::protocols::frame::{Frame, FrameDecoder};
+use crate::transport::server_config::ConnectionLimits;
+
+/// Default number of threads reading from all connections
+pub const DEFAULT_WORKER_THREADS: usize = 4;
+
+/// Default number of threads running work deferred by connection handlers
+pub const DEFAULT_MESSAGE_THREADS: usize = 16;
+
+/// Interval between checks for connections past their idle or read timeout
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bytes read from a connection per readiness event
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Upper bound on a read after a readiness event, in case the event was spurious
+const READ_GUARD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Work a handler runs off the connection workers
+pub type DeferredJob = Box<dyn FnOnce() + Send>;
+
+/// What to do with a connection after one of its frames was handled
+pub enum Flow {
+    Continue,
+    Close,
+    /// Hand the rest of the connection to another handler
+    Replace(Box<dyn ConnectionHandler>),
+    /// Run the job on a message thread; the next frame is handled once it is done
+    Defer(DeferredJob),
+}
+
+/// Why a connection was closed
+#[derive(Debug)]
+pub enum CloseReason {
+    /// The peer closed it, or its handler asked to close it
+    Closed,
+    /// Nothing was received within the idle timeout
+    IdleTimeout,
+    /// A message started but did not arrive in full within the read timeout
+    ReadTimeout,
+    Failed(std::io::Error),
+}
+
+/// Protocol spoken on a connection, driven one frame at a time
+pub trait ConnectionHandler: Send {
+    /// Handle one frame; `stream` can be used to reply
+    fn on_frame(&mut self, frame: Frame, stream: &TcpStream) -> Result<Flow>;
+
+    /// Called once when the connection is closed
+    fn on_close(&mut self, _reason: &CloseReason) {}
+
+    /// Whether the connection may stay quiet past the idle timeout
+    fn is_idle_exempt(&self) -> bool {
+        false
+    }
+}
+
+/// Open connections of one listener
+#[derive(Default)]
+pub struct ConnectionGroup {
+    streams: Mutex<HashMap<usize, TcpStream>>,
+}
+
+impl ConnectionGroup {
+    /// Create an empty group
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of open connections
+    pub fn count(&self) -> usize {
+        self.streams.lock().unwrap().len()
+    }
+
+    /// Stop reading from every connection; frames already read are still handled
+    pub fn shutdown_read(&self) {
+        for stream in self.streams.lock().unwrap().values() {
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    handler: Box<dyn ConnectionHandler>,
+    decoder: FrameDecoder,
+    limits: ConnectionLimits,
+    group: Arc<ConnectionGroup>,
+    last_read: Instant,
+    // When the first byte of the frame being received arrived
+    frame_started: Option<Instant>,
+    // Frames read but not handled yet, because an earlier one was deferred
+    pending: VecDeque<Frame>,
+    // The peer closed its side; the connection closes once `pending` is handled
+    finished: bool,
+    // A deferred job is running, so the connection is neither read nor timed out
+    parked: bool,
+    closed: bool,
+}
+
+/// State of a connection after a worker is done with it
+enum Progress {
+    Open,
+    Deferred(DeferredJob),
+    Closed,
+}
+
+impl Connection {
+    /// Read what is available and handle the frames it completes
+    fn read(&mut self) -> Result<Progress> {
+        let mut buffer = [0u8; READ_BUFFER_SIZE];
+        let read = match self.stream.read(&mut buffer) {
+            Ok(read) => read,
+            Err(e) if is_timeout(&e) || e.kind() == ErrorKind::Interrupted => return Ok(Progress::Open),
+            Err(e) => return Err(e),
+        };
+        if read == 0 {
+            self.pending.extend(self.decoder.finish()?);
+            self.finished = true;
+            return self.handle_pending();
+        }
+
+        let now = Instant::now();
+        self.last_read = now;
+        let was_partial = self.decoder.is_partial();
+        let frames = self.decoder.decode(&buffer[..read])?;
+        self.frame_started = match (self.decoder.is_partial(), was_partial && frames.is_empty()) {
+            (false, _) => None,
+            (true, true) => self.frame_started,
+            (true, false) => Some(now),
+        };
+
+        self.pending.extend(frames);
+        self.handle_pending()
+    }
+
+    /// Handle frames in order until one of them is deferred
+    fn handle_pending(&mut self) -> Result<Progress> {
+        while let Some(frame) = self.pending.pop_front() {
+            match self.handler.on_frame(frame, &self.stream)? {
+                Flow::Continue => {}
+                Flow::Close => return Ok(Progress::Closed),
+                Flow::Replace(handler) => self.handler = handler,
+                Flow::Defer(job) => return Ok(Progress::Deferred(job)),
+            }
+        }
+        Ok(if self.finished { Progress::Closed } else { Progress::Open })
+    }
+
+    /// Resume after a deferred job; the time it ran counts neither as idle nor as reading
+    fn unpark(&mut self, now: Instant) {
+        self.parked = false;
+        self.last_read = now;
+        self.frame_started = self.frame_started.map(|_| now);
+    }
+
+    fn timed_out(&self, now: Instant) -> Option<CloseReason> {
+        if self.parked {
+            return None;
+        }
+        match self.frame_started {
+            Some(started) => self.limits.read_timeout
+                .filter(|timeout| now.duration_since(started) >= *timeout)
+                .map(|_| CloseReason::ReadTimeout),
+            None => self.limits.idle_timeout
+                .filter(|timeout| now.duration_since(self.last_read) >= *timeout && !self.handler.is_idle_exempt())
+                .map(|_| CloseReason::IdleTimeout),
+        }
+    }
+}
+
+/// Poller and worker pool serving every registered connection
+pub struct Reactor {
+    poller: Poller,
+    connections: Mutex<HashMap<usize, Arc<Mutex<Connection>>>>,
+    next_key: AtomicUsize,
+    ready: Sender<usize>,
+    deferred: Sender<(usize, DeferredJob)>,
+    workers: usize,
+}
+
+impl Reactor {
+    /// Start the polling thread, `workers` worker threads and `message_threads` message threads
+    pub fn start(workers: usize, message_threads: usize) -> Result<Arc<Self>> {
+        let (ready, queue) = mpsc::channel();
+        let (deferred, jobs) = mpsc::channel();
+        let reactor = Arc::new(Self {
+            poller: Poller::new()?,
+            connections: Mutex::new(HashMap::new()),
+            next_key: AtomicUsize::new(0),
+            ready,
+            deferred,
+            workers: workers.max(1),
+        });
+
+        let queue = Arc::new(Mutex::new(queue));
+        for index in 0..reactor.workers {
+            let reactor = Arc::clone(&reactor);
+            let queue = Arc::clone(&queue);
+            thread::Builder::new()
+                .name(format!("connection-worker-{}", index))
+                .spawn(move || reactor.work(&queue))?;
+        }
+        let jobs = Arc::new(Mutex::new(jobs));
+        for index in 0..message_threads.max(1) {
+            let reactor = Arc::clone(&reactor);
+            let jobs = Arc::clone(&jobs);
+            thread::Builder::new()
+                .name(format!("message-worker-{}", index))
+                .spawn(move || reactor.run_deferred(&jobs))?;
+        }
+        let poll = Arc::clone(&reactor);
+        thread::Builder::new()
+            .name("connection-poller".to_string())
+            .spawn(move || poll.poll())?;
+
+        Ok(reactor)
+    }
+
+    /// Serve a connection until it closes, adding it to its listener's group meanwhile
+    pub fn register(
+        &self,
+        stream: TcpStream,
+        handler: Box<dyn ConnectionHandler>,
+        limits: ConnectionLimits,
+        group: &Arc<ConnectionGroup>,
+    ) -> Result<()> {
+        stream.set_read_timeout(Some(READ_GUARD_TIMEOUT))?;
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed);
+        group.streams.lock().unwrap().insert(key, stream.try_clone()?);
+
+        let connection = Arc::new(Mutex::new(Connection {
+            stream,
+            handler,
+            decoder: FrameDecoder::new(limits.max_frame_size),
+            limits,
+            group: Arc::clone(group),
+            last_read: Instant::now(),
+            frame_started: None,
+            pending: VecDeque::new(),
+            finished: false,
+            parked: false,
+            closed: false,
+        }));
+        let guard = connection.lock().unwrap();
+        self.connections.lock().unwrap().insert(key, Arc::clone(&connection));
+
+        // SAFETY: `close` deletes the stream from the poller before the connection drops it
+        if let Err(e) = unsafe { self.poller.add(&guard.stream, Event::readable(key)) } {
+            self.connections.lock().unwrap().remove(&key);
+            group.streams.lock().unwrap().remove(&key);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn poll(&self) {
+        let mut events = Events::new();
+        let mut last_check = Instant::now();
+        loop {
+            events.clear();
+            if let Err(e) = self.poller.wait(&mut events, Some(TIMEOUT_CHECK_INTERVAL)) {
+                if e.kind() != ErrorKind::Interrupted {
+                    eprintln!("[Reactor] Failed to wait for connections: {}", e);
+                    thread::sleep(TIMEOUT_CHECK_INTERVAL);
+                }
+                continue;
+            }
+            for event in events.iter() {
+                let _ = self.ready.send(event.key);
+            }
+
+            if last_check.elapsed() >= TIMEOUT_CHECK_INTERVAL {
+                last_check = Instant::now();
+                self.close_timed_out(last_check);
+            }
+        }
+    }
+
+    fn work(&self, queue: &Mutex<Receiver<usize>>) {
+        loop {
+            let key = match queue.lock().unwrap().recv() {
+                Ok(key) => key,
+                Err(_) => return,
+            };
+            let Some(connection) = self.connections.lock().unwrap().get(&key).cloned() else {
+                continue; // Closed while the event was queued
+            };
+
+            let mut connection = connection.lock().unwrap();
+            if connection.closed {
+                continue;
+            }
+            let progress = connection.read();
+            self.settle(key, &mut connection, progress);
+        }
+    }
+
+    fn run_deferred(&self, jobs: &Mutex<Receiver<(usize, DeferredJob)>>) {
+        loop {
+            let (key, job) = match jobs.lock().unwrap().recv() {
+                Ok(next) => next,
+                Err(_) => return,
+            };
+            job();
+
+            let Some(connection) = self.connections.lock().unwrap().get(&key).cloned() else {
+                continue;
+            };
+            let mut connection = connection.lock().unwrap();
+            if connection.closed {
+                continue;
+            }
+            // Frames read along with the deferred one are handled here rather than
+            // going back to a worker, and later deferred ones run right away
+            connection.unpark(Instant::now());
+            let progress = loop {
+                match connection.handle_pending() {
+                    Ok(Progress::Deferred(job)) => job(),
+                    progress => break progress,
+                }
+            };
+            self.settle(key, &mut connection, progress);
+        }
+    }
+
+    /// Watch the connection again, park it for a deferred job, or close it
+    fn settle(&self, key: usize, connection: &mut Connection, progress: Result<Progress>) {
+        let reason = match progress {
+            // Oneshot polling: the connection is only watched again once this read is done
+            Ok(Progress::Open) => match self.poller.modify(&connection.stream, Event::readable(key)) {
+                Ok(()) => return,
+                Err(e) => CloseReason::Failed(e),
+            },
+            Ok(Progress::Deferred(job)) => {
+                connection.parked = true;
+                let _ = self.deferred.send((key, job));
+                return;
+            }
+            Ok(Progress::Closed) => CloseReason::Closed,
+            Err(e) => CloseReason::Failed(e),
+        };
+        self.close(key, connection, reason);
+    }
+
+    fn close_timed_out(&self, now: Instant) {
+        let connections: Vec<(usize, Arc<Mutex<Connection>>)> = self.connections.lock().unwrap()
+            .iter()
+            .map(|(key, connection)| (*key, Arc::clone(connection)))
+            .collect();
+
+        for (key, connection) in connections {
+            // A connection held by a worker is receiving data, so it is not idle
+            let Ok(mut connection) = connection.try_lock() else {
+                continue;
+            };
+            if let Some(reason) = connection.timed_out(now) {
+                // The handler hears why before the client sees the connection close
+                self.close(key, &mut connection, reason);
+                let _ = connection.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    fn close(&self, key: usize, connection: &mut Connection, reason: CloseReason) {
+        connection.closed = true;
+        self.connections.lock().unwrap().remove(&key);
+        let _ = self.poller.delete(&connection.stream);
+        connection.group.streams.lock().unwrap().remove(&key);
+        connection.handler.on_close(&reason);
+    }
+}
+
+/// Check whether a read failed because a socket timeout ran out
+fn is_timeout(e: &std::io::Error) -> bool {
+    // Unix reports `WouldBlock`, Windows `TimedOut`
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+static REACTOR: Mutex<Option<Arc<Reactor>>> = Mutex::new(None);
+
+/// Start the shared reactor with its thread counts; later calls return the running one
+pub fn init_reactor(workers: usize, message_threads: usize) -> Result<Arc<Reactor>> {
+    let mut reactor = REACTOR.lock().unwrap();
+    if let Some(reactor) = reactor.as_ref() {
+        return Ok(Arc::clone(reactor));
+    }
+    let started = Reactor::start(workers, message_threads)?;
+    *reactor = Some(Arc::clone(&started));
+    Ok(started)
+}
+
+/// Get the shared reactor, starting it with the default number of threads if needed
+pub fn get_reactor() -> Result<Arc<Reactor>> {
+    init_reactor(DEFAULT_WORKER_THREADS, DEFAULT_MESSAGE_THREADS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Echoes lines back in upper case and closes on `BYE`
+    struct Shout(Arc<Mutex<Vec<String>>>);
+
+    impl ConnectionHandler for Shout {
+        fn on_frame(&mut self, frame: Frame, mut stream: &TcpStream) -> Result<Flow> {
+            let Frame::Line(line) = frame else {
+                writeln!(stream, "TOO LARGE")?;
+                return Ok(Flow::Continue);
+            };
+            if line.trim() == "BYE" {
+                return Ok(Flow::Close);
+            }
+            if line.trim() == "slow" {
+                let mut stream = stream.try_clone()?;
+                return Ok(Flow::Defer(Box::new(move || {
+                    thread::sleep(Duration::from_millis(300));
+                    let _ = writeln!(stream, "SLOW");
+                })));
+            }
+            writeln!(stream, "{}", line.trim().to_uppercase())?;
+            Ok(Flow::Continue)
+        }
+
+        fn on_close(&mut self, reason: &CloseReason) {
+            self.0.lock().unwrap().push(format!("{:?}", reason));
+        }
+    }
+
+    fn connect(reactor: &Reactor, limits: ConnectionLimits) -> (TcpStream, Arc<ConnectionGroup>, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let group = Arc::new(ConnectionGroup::new());
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        reactor.register(server, Box::new(Shout(Arc::clone(&closed))), limits, &group).unwrap();
+        (client, group, closed)
+    }
+
+    #[test]
+    fn test_serves_many_connections_with_few_workers() {
+        let reactor = Reactor::start(2, 1).unwrap();
+        let limits = ConnectionLimits { max_frame_size: 16, ..ConnectionLimits::default() };
+        let connections: Vec<_> = (0..20).map(|_| connect(&reactor, limits)).collect();
+        assert_eq!(connections.iter().map(|(_, group, _)| group.count()).sum::<usize>(), 20);
+
+        for (index, (client, _, _)) in connections.iter().enumerate() {
+            let mut client = client;
+            write!(client, "hello {}\n{}\n", index, "x".repeat(40)).unwrap();
+        }
+        for (index, (client, _, _)) in connections.iter().enumerate() {
+            let mut reader = BufReader::new(client);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("HELLO {}\n", index));
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, "TOO LARGE\n");
+        }
+
+        let (mut client, group, closed) = connections.into_iter().next().unwrap();
+        client.write_all(b"BYE\n").unwrap();
+        let mut rest = String::new();
+        client.read_to_string(&mut rest).unwrap();
+        assert_eq!(group.count(), 0);
+        assert_eq!(*closed.lock().unwrap(), vec!["Closed"]);
+    }
+
+    #[test]
+    fn test_deferred_work_does_not_hold_up_other_connections() {
+        let reactor = Reactor::start(1, 2).unwrap();
+        let (mut slow, _, _) = connect(&reactor, ConnectionLimits::default());
+        let (mut fast, _, _) = connect(&reactor, ConnectionLimits::default());
+        let started = Instant::now();
+        slow.write_all(b"slow\nafter\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        fast.write_all(b"fast\n").unwrap();
+
+        // The only worker is free while the slow line's job runs
+        let mut line = String::new();
+        BufReader::new(&fast).read_line(&mut line).unwrap();
+        assert_eq!(line, "FAST\n");
+        assert!(started.elapsed() < Duration::from_millis(250), "{:?}", started.elapsed());
+
+        // Lines after a deferred one wait for it
+        let mut reader = BufReader::new(&slow);
+        let mut lines = String::new();
+        reader.read_line(&mut lines).unwrap();
+        reader.read_line(&mut lines).unwrap();
+        assert_eq!(lines, "SLOW\nAFTER\n");
+    }
+
+    #[test]
+    fn test_closes_idle_and_slow_connections() {
+        let reactor = Reactor::start(1, 1).unwrap();
+        let limits = ConnectionLimits {
+            idle_timeout: Some(Duration::from_millis(200)),
+            read_timeout: Some(Duration::from_millis(100)),
+            ..ConnectionLimits::default()
+        };
+        let (mut idle, _, idle_closed) = connect(&reactor, limits);
+        let (mut slow, _, slow_closed) = connect(&reactor, limits);
+        slow.write_all(b"never finished").unwrap();
+
+        let mut rest = String::new();
+        idle.read_to_string(&mut rest).unwrap();
+        slow.read_to_string(&mut rest).unwrap();
+        assert_eq!(*idle_closed.lock().unwrap(), vec!["IdleTimeout"]);
+        assert_eq!(*slow_closed.lock().unwrap(), vec!["ReadTimeout"]);
+    }
+}
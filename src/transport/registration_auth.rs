@@ -0,0 +1,195 @@
+use std::io::{ErrorKind, Result};
+
+/// Environment variable holding registration tokens
+///
+/// Format: `<token>=<prefix>,<prefix>;<token>=*`
+pub const REGISTRATION_TOKENS_ENV: &str = "REGISTRATION_TOKENS";
+
+/// Producer-id prefixes a token is allowed to manage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenScope {
+    prefixes: Vec<String>,
+}
+
+impl TokenScope {
+    /// Create a scope from producer-id prefixes (`*` allows every id)
+    pub fn new(prefixes: Vec<String>) -> Self {
+        Self { prefixes }
+    }
+
+    /// Check if the scope covers a producer id
+    pub fn allows(&self, producer_id: &str) -> bool {
+        self.prefixes.iter()
+            .any(|prefix| prefix == "*" || producer_id.starts_with(prefix.as_str()))
+    }
+
+    /// Get the producer-id prefixes
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+}
+
+/// Token-based authentication for the registration server
+///
+/// With no tokens configured authentication is disabled and every
+/// command is allowed, matching the behaviour of earlier versions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegistrationAuth {
+    tokens: Vec<(String, TokenScope)>,
+}
+
+impl RegistrationAuth {
+    /// Create an auth configuration with no tokens (authentication disabled)
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Create an auth configuration from token/scope pairs
+    pub fn with_tokens(tokens: Vec<(String, TokenScope)>) -> Self {
+        Self { tokens }
+    }
+
+    /// Parse tokens from the `<token>=<prefix>,<prefix>;...` format
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+
+        for entry in spec.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (token, prefixes) = entry.split_once('=')
+                .ok_or_else(|| std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid token entry (expected <token>=<prefixes>): {}", entry),
+                ))?;
+
+            let token = token.trim();
+            let prefixes: Vec<String> = prefixes.split(',')
+                .map(str::trim)
+                .filter(|prefix| !prefix.is_empty())
+                .map(str::to_string)
+                .collect();
+
+            if token.is_empty() || prefixes.is_empty() {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid token entry (empty token or prefixes): {}", entry),
+                ));
+            }
+
+            tokens.push((token.to_string(), TokenScope::new(prefixes)));
+        }
+
+        Ok(Self { tokens })
+    }
+
+    /// Check if authentication is required
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Look up the scope granted to a token
+    pub fn authenticate(&self, token: &str) -> Result<TokenScope> {
+        self.tokens.iter()
+            .find(|(candidate, _)| constant_time_eq(candidate.as_bytes(), token.as_bytes()))
+            .map(|(_, scope)| scope.clone())
+            .ok_or_else(|| std::io::Error::new(ErrorKind::PermissionDenied, "Invalid token"))
+    }
+
+    /// Check that a session may manage the given producer id
+    pub fn authorize(&self, scope: Option<&TokenScope>, producer_id: &str) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        match scope {
+            None => Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                "Authentication required. Use: AUTH <token>",
+            )),
+            Some(scope) if !scope.allows(producer_id) => Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Token is not allowed to manage producer '{}'", producer_id),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Check that a session may run a command that affects every producer
+    pub fn authorize_unrestricted(&self, scope: Option<&TokenScope>, action: &str) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        match scope {
+            None => Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                "Authentication required. Use: AUTH <token>",
+            )),
+            Some(scope) if !scope.prefixes().iter().any(|prefix| prefix == "*") => Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Only a token scoped to '*' may {}", action),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tokens() {
+        let auth = RegistrationAuth::parse("secret-a=team-a-,shared-; admin=*").unwrap();
+        assert!(auth.is_enabled());
+
+        let scope = auth.authenticate("secret-a").unwrap();
+        assert_eq!(scope.prefixes(), ["team-a-".to_string(), "shared-".to_string()]);
+        assert!(auth.authenticate("admin").unwrap().allows("anything"));
+        assert!(auth.authenticate("wrong").is_err());
+
+        assert!(RegistrationAuth::parse("no-separator").is_err());
+        assert!(RegistrationAuth::parse("token=").is_err());
+        assert!(!RegistrationAuth::parse("").unwrap().is_enabled());
+    }
+
+    #[test]
+    fn test_authorize_by_prefix() {
+        let auth = RegistrationAuth::parse("secret-a=team-a-").unwrap();
+        let scope = auth.authenticate("secret-a").unwrap();
+
+        assert!(auth.authorize(Some(&scope), "team-a-consumer").is_ok());
+        assert_eq!(
+            auth.authorize(Some(&scope), "team-b-consumer").unwrap_err().kind(),
+            ErrorKind::PermissionDenied,
+        );
+        assert_eq!(
+            auth.authorize(None, "team-a-consumer").unwrap_err().kind(),
+            ErrorKind::PermissionDenied,
+        );
+    }
+
+    #[test]
+    fn test_authorize_unrestricted() {
+        let auth = RegistrationAuth::parse("secret-a=team-a-;admin=*").unwrap();
+        let team = auth.authenticate("secret-a").unwrap();
+        let admin = auth.authenticate("admin").unwrap();
+
+        assert!(auth.authorize_unrestricted(Some(&admin), "clear retained messages").is_ok());
+        assert!(auth.authorize_unrestricted(Some(&team), "clear retained messages").is_err());
+        assert!(auth.authorize_unrestricted(None, "clear retained messages").is_err());
+    }
+
+    #[test]
+    fn test_disabled_allows_everything() {
+        let auth = RegistrationAuth::disabled();
+        assert!(auth.authorize(None, "anyone").is_ok());
+        assert!(auth.authorize_unrestricted(None, "clear retained messages").is_ok());
+    }
+}
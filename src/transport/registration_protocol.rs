@@ -0,0 +1,674 @@
+use std::io::{ErrorKind, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::transport::producers::{DeadLetter, DeliveryStatus};
+use crate::transport::rate_limit::RateLimitCounts;
+use crate::transport::server_handle::ListenerStatus;
+
+/// Number of dead letters `DEADLETTERS` shows without a limit
+pub const DEFAULT_DEAD_LETTER_LIMIT: usize = 20;
+
+/// Wire format used on a registration connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolMode {
+    /// Line-based `OK:`/`ERROR:` responses for interactive (telnet) use
+    #[default]
+    Text,
+    /// One JSON object per line for requests and responses
+    Json,
+}
+
+impl ProtocolMode {
+    /// Convert string to ProtocolMode
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "TEXT" => Some(ProtocolMode::Text),
+            "JSON" => Some(ProtocolMode::Json),
+            _ => None,
+        }
+    }
+
+    /// Get protocol mode as string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolMode::Text => "TEXT",
+            ProtocolMode::Json => "JSON",
+        }
+    }
+}
+
+/// Command accepted by the registration server
+///
+/// In JSON mode commands are tagged by `cmd`, e.g.
+/// `{"cmd":"SUBSCRIBE","id":"consumer1","event":"user_message"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "UPPERCASE")]
+pub enum RegistrationCommand {
+    Register {
+        id: String,
+        uri: String,
+        #[serde(default)]
+        events: Vec<String>,
+        /// Lease TTL in seconds
+        #[serde(default)]
+        ttl: Option<u64>,
+    },
+    Deregister {
+        id: String,
+    },
+    Update {
+        id: String,
+        uri: String,
+    },
+    Heartbeat {
+        id: String,
+    },
+    Subscribe {
+        id: String,
+        event: String,
+    },
+    Unsubscribe {
+        id: String,
+        event: String,
+    },
+    List,
+    Receipt {
+        id: String,
+    },
+    /// Show routing counters
+    Stats,
+    /// Show the most recent dead letters
+    DeadLetters {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// List messages waiting for their delivery time
+    Scheduled,
+    /// Cancel a scheduled message
+    Cancel {
+        id: String,
+    },
+    /// Clear the retained message of an event, or of every event with `*`
+    Unretain {
+        event: String,
+    },
+    Proto {
+        mode: ProtocolMode,
+    },
+    Auth {
+        token: String,
+    },
+    Quit,
+}
+
+impl RegistrationCommand {
+    /// Parse a command from a text protocol line
+    pub fn from_text(line: &str) -> Result<Self> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts.is_empty() {
+            return Err(invalid_input("Empty command"));
+        }
+
+        match parts[0].to_uppercase().as_str() {
+            "REGISTER" => {
+                if parts.len() < 3 {
+                    return Err(invalid_input(
+                        "Usage: REGISTER <id> <protocol>://<address> [events...] [ttl=<seconds>]",
+                    ));
+                }
+
+                // Get event subscriptions and lease TTL (both optional)
+                let mut events = Vec::new();
+                let mut ttl = None;
+                for arg in parts.iter().skip(3) {
+                    match arg.split_once('=') {
+                        Some((key, value)) if key.eq_ignore_ascii_case("ttl") => {
+                            let secs = value.parse::<u64>().map_err(|_| invalid_input(format!(
+                                "Invalid ttl: {} (expected a positive number of seconds)", value
+                            )))?;
+                            ttl = Some(secs);
+                        }
+                        _ => events.push(arg.to_string()),
+                    }
+                }
+
+                Ok(RegistrationCommand::Register {
+                    id: parts[1].to_string(),
+                    uri: parts[2].to_string(),
+                    events,
+                    ttl,
+                })
+            }
+
+            "DEREGISTER" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: DEREGISTER <id>"));
+                }
+                Ok(RegistrationCommand::Deregister { id: parts[1].to_string() })
+            }
+
+            "UPDATE" => {
+                if parts.len() != 3 {
+                    return Err(invalid_input("Usage: UPDATE <id> <protocol>://<address>"));
+                }
+                Ok(RegistrationCommand::Update {
+                    id: parts[1].to_string(),
+                    uri: parts[2].to_string(),
+                })
+            }
+
+            "HEARTBEAT" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: HEARTBEAT <id>"));
+                }
+                Ok(RegistrationCommand::Heartbeat { id: parts[1].to_string() })
+            }
+
+            "SUBSCRIBE" => {
+                if parts.len() != 3 {
+                    return Err(invalid_input("Usage: SUBSCRIBE <id> <event_name>"));
+                }
+                Ok(RegistrationCommand::Subscribe {
+                    id: parts[1].to_string(),
+                    event: parts[2].to_string(),
+                })
+            }
+
+            "UNSUBSCRIBE" => {
+                if parts.len() != 3 {
+                    return Err(invalid_input("Usage: UNSUBSCRIBE <id> <event_name>"));
+                }
+                Ok(RegistrationCommand::Unsubscribe {
+                    id: parts[1].to_string(),
+                    event: parts[2].to_string(),
+                })
+            }
+
+            "LIST" => Ok(RegistrationCommand::List),
+
+            "RECEIPT" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: RECEIPT <message_id>"));
+                }
+                Ok(RegistrationCommand::Receipt { id: parts[1].to_string() })
+            }
+
+            "STATS" => Ok(RegistrationCommand::Stats),
+
+            "DEADLETTERS" => {
+                let limit = match parts.get(1..) {
+                    Some([]) => None,
+                    Some([limit]) => Some(limit.parse::<usize>()
+                        .map_err(|_| invalid_input(format!("Invalid limit: {}", limit)))?),
+                    _ => return Err(invalid_input("Usage: DEADLETTERS [limit]")),
+                };
+                Ok(RegistrationCommand::DeadLetters { limit })
+            }
+
+            "SCHEDULED" => Ok(RegistrationCommand::Scheduled),
+
+            "CANCEL" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: CANCEL <schedule_id>"));
+                }
+                Ok(RegistrationCommand::Cancel { id: parts[1].to_string() })
+            }
+
+            "UNRETAIN" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: UNRETAIN <event_name|*>"));
+                }
+                Ok(RegistrationCommand::Unretain { event: parts[1].to_string() })
+            }
+
+            "PROTO" => {
+                let mode = parts.get(1)
+                    .and_then(|mode| ProtocolMode::from_str(mode))
+                    .filter(|_| parts.len() == 2)
+                    .ok_or_else(|| invalid_input("Usage: PROTO <TEXT|JSON>"))?;
+                Ok(RegistrationCommand::Proto { mode })
+            }
+
+            "AUTH" => {
+                if parts.len() != 2 {
+                    return Err(invalid_input("Usage: AUTH <token>"));
+                }
+                Ok(RegistrationCommand::Auth { token: parts[1].to_string() })
+            }
+
+            "QUIT" => Ok(RegistrationCommand::Quit),
+
+            _ => Err(invalid_input(format!("Unknown command: {}", parts[0]))),
+        }
+    }
+
+    /// Get the producer id a mutating command acts on, or `None` for read-only commands
+    pub fn producer_id(&self) -> Option<&str> {
+        match self {
+            RegistrationCommand::Register { id, .. }
+            | RegistrationCommand::Deregister { id }
+            | RegistrationCommand::Update { id, .. }
+            | RegistrationCommand::Heartbeat { id }
+            | RegistrationCommand::Subscribe { id, .. }
+            | RegistrationCommand::Unsubscribe { id, .. } => Some(id),
+            RegistrationCommand::List
+            | RegistrationCommand::Receipt { .. }
+            | RegistrationCommand::Stats
+            | RegistrationCommand::DeadLetters { .. }
+            | RegistrationCommand::Scheduled
+            | RegistrationCommand::Cancel { .. }
+            | RegistrationCommand::Unretain { .. }
+            | RegistrationCommand::Proto { .. }
+            | RegistrationCommand::Auth { .. }
+            | RegistrationCommand::Quit => None,
+        }
+    }
+
+    /// Check whether the command may block: it resolves a producer address,
+    /// or sends the producer the retained messages of its events
+    pub fn may_block(&self) -> bool {
+        matches!(
+            self,
+            RegistrationCommand::Register { .. }
+                | RegistrationCommand::Update { .. }
+                | RegistrationCommand::Subscribe { .. }
+        )
+    }
+
+    /// Parse a command from a JSON protocol line
+    pub fn from_json(line: &str) -> Result<Self> {
+        serde_json::from_str(line).map_err(|e| std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid request: {}", e),
+        ))
+    }
+}
+
+/// Registered producer as reported by `LIST`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProducerInfo {
+    pub id: String,
+    pub protocol: String,
+    pub address: String,
+    pub events: Vec<String>,
+    /// Seconds left on the producer's lease, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_remaining: Option<u64>,
+}
+
+/// Delivery outcome for one producer as reported by `RECEIPT`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeliveryInfo {
+    pub producer: String,
+    #[serde(flatten)]
+    pub status: DeliveryStatus,
+}
+
+/// Message waiting for its delivery time as reported by `SCHEDULED`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduledInfo {
+    pub id: String,
+    pub event_name: String,
+    /// Delivery time in milliseconds since the Unix epoch
+    pub deliver_at: u64,
+    pub msg: String,
+}
+
+/// Successful result of a registration command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegistrationResponse {
+    Registered {
+        id: String,
+        events: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ttl: Option<u64>,
+    },
+    Deregistered {
+        id: String,
+    },
+    Updated {
+        id: String,
+        uri: String,
+    },
+    LeaseRenewed {
+        id: String,
+        ttl: u64,
+    },
+    Subscribed {
+        id: String,
+        event: String,
+    },
+    Unsubscribed {
+        id: String,
+        event: String,
+    },
+    List {
+        producers: Vec<ProducerInfo>,
+        events: Vec<String>,
+    },
+    Receipt {
+        id: String,
+        deliveries: Vec<DeliveryInfo>,
+    },
+    Stats {
+        producers: usize,
+        events: usize,
+        retained: usize,
+        scheduled: usize,
+        dead_letters: u64,
+        expired: u64,
+        duplicates_suppressed: u64,
+        /// Messages over an ingress rate limit, by the kind of limit
+        rate_limited: RateLimitCounts,
+        /// Ingress listeners of the running server
+        listeners: Vec<ListenerStatus>,
+    },
+    DeadLetters {
+        /// Messages dead-lettered since startup
+        total: u64,
+        /// Messages dropped because they expired
+        expired: u64,
+        letters: Vec<DeadLetter>,
+    },
+    Scheduled {
+        messages: Vec<ScheduledInfo>,
+    },
+    Cancelled {
+        id: String,
+    },
+    Unretained {
+        event: String,
+        cleared: usize,
+    },
+    Proto {
+        mode: ProtocolMode,
+    },
+    Authenticated {
+        prefixes: Vec<String>,
+    },
+    Goodbye,
+}
+
+impl RegistrationResponse {
+    /// Render the response body used after `OK:` in text mode
+    pub fn to_text(&self) -> String {
+        match self {
+            RegistrationResponse::Registered { id, events, ttl } => {
+                let mut msg = if events.is_empty() {
+                    format!("Producer '{}' registered", id)
+                } else {
+                    format!("Producer '{}' registered with events: {:?}", id, events)
+                };
+                if let Some(ttl) = ttl {
+                    msg.push_str(&format!(" (ttl {}s)", ttl));
+                }
+                msg
+            }
+            RegistrationResponse::Deregistered { id } => {
+                format!("Producer '{}' deregistered", id)
+            }
+            RegistrationResponse::Updated { id, uri } => {
+                format!("Producer '{}' updated to {}", id, uri)
+            }
+            RegistrationResponse::LeaseRenewed { id, ttl } => {
+                format!("Producer '{}' lease renewed for {}s", id, ttl)
+            }
+            RegistrationResponse::Subscribed { id, event } => {
+                format!("Producer '{}' subscribed to event '{}'", id, event)
+            }
+            RegistrationResponse::Unsubscribed { id, event } => {
+                format!("Producer '{}' unsubscribed from event '{}'", id, event)
+            }
+            RegistrationResponse::List { producers, events } => {
+                let mut info = format!("Producers: {}\n", producers.len());
+                for producer in producers {
+                    info.push_str(&format!("  {} -> {} (events: {:?})",
+                        producer.id, producer.address, producer.events));
+                    if let Some(remaining) = producer.lease_remaining {
+                        info.push_str(&format!(" (lease: {}s left)", remaining));
+                    }
+                    info.push('\n');
+                }
+                info.push_str(&format!("Events: {:?}", events));
+                info
+            }
+            RegistrationResponse::Receipt { id, deliveries } => {
+                let statuses: Vec<String> = deliveries.iter()
+                    .map(|delivery| format!("{} {}", delivery.producer, delivery.status))
+                    .collect();
+                format!("Message '{}': {}", id, statuses.join(", "))
+            }
+            RegistrationResponse::Stats {
+                producers,
+                events,
+                retained,
+                scheduled,
+                dead_letters,
+                expired,
+                duplicates_suppressed,
+                rate_limited,
+                listeners,
+            } => {
+                let mut info = format!(
+                    "Producers: {}, events: {}, retained: {}, scheduled: {}, dead letters: {} ({} expired), \
+                     duplicates suppressed: {}, rate limited: {} (peer {}, identity {}, event {})",
+                    producers, events, retained, scheduled, dead_letters, expired, duplicates_suppressed,
+                    rate_limited.total(), rate_limited.peer, rate_limited.identity, rate_limited.event,
+                );
+                for listener in listeners {
+                    info.push_str(&format!(
+                        "\nListener {} on {}: {}, {} connection(s), {} message(s)",
+                        listener.name, listener.address, listener.state.as_str(),
+                        listener.stats.connections_accepted, listener.stats.messages_received,
+                    ));
+                }
+                info
+            }
+            RegistrationResponse::DeadLetters { total, expired, letters } => {
+                let mut info = format!("Dead letters: {} ({} expired)", total, expired);
+                for letter in letters {
+                    info.push_str(&format!("\n  {} -> {}: {} ({})",
+                        letter.event_name,
+                        letter.producer_id.as_deref().unwrap_or("-"),
+                        letter.reason,
+                        letter.message_id.as_deref().unwrap_or("no id")));
+                }
+                info
+            }
+            RegistrationResponse::Scheduled { messages } => {
+                let mut info = format!("Scheduled messages: {}", messages.len());
+                for message in messages {
+                    info.push_str(&format!("\n  {} -> {} at {}: {}",
+                        message.id, message.event_name, message.deliver_at, message.msg));
+                }
+                info
+            }
+            RegistrationResponse::Cancelled { id } => {
+                format!("Scheduled message '{}' cancelled", id)
+            }
+            RegistrationResponse::Unretained { event, cleared } if event == "*" => {
+                format!("Cleared {} retained message(s) for all events", cleared)
+            }
+            RegistrationResponse::Unretained { event, cleared } => {
+                format!("Cleared {} retained message(s) for '{}'", cleared, event)
+            }
+            RegistrationResponse::Proto { mode } => {
+                format!("Protocol set to {}", mode.as_str())
+            }
+            RegistrationResponse::Authenticated { prefixes } => {
+                format!("Authenticated for producer prefixes: {:?}", prefixes)
+            }
+            RegistrationResponse::Goodbye => "Goodbye".to_string(),
+        }
+    }
+}
+
+/// Successful JSON reply line
+#[derive(Serialize)]
+struct JsonOk<'a> {
+    ok: bool,
+    #[serde(flatten)]
+    response: &'a RegistrationResponse,
+}
+
+/// Failed JSON reply line
+#[derive(Serialize)]
+struct JsonError<'a> {
+    ok: bool,
+    code: &'static str,
+    message: &'a str,
+}
+
+/// Map an error to the stable code reported in JSON mode
+pub fn error_code(error: &std::io::Error) -> &'static str {
+    match error.kind() {
+        ErrorKind::InvalidData => "INVALID_REQUEST",
+        ErrorKind::InvalidInput => "INVALID_ARGUMENT",
+        ErrorKind::NotFound => "NOT_FOUND",
+        ErrorKind::PermissionDenied => "PERMISSION_DENIED",
+        _ => "INTERNAL",
+    }
+}
+
+/// Format a command result as a single reply line (without newline)
+pub fn format_reply(mode: ProtocolMode, result: &Result<RegistrationResponse>) -> String {
+    match (mode, result) {
+        (ProtocolMode::Text, Ok(response)) => format!("OK:{}", response.to_text()),
+        (ProtocolMode::Text, Err(e)) => format!("ERROR:{}", e),
+        (ProtocolMode::Json, Ok(response)) => {
+            serde_json::to_string(&JsonOk { ok: true, response })
+                .unwrap_or_else(|e| json_error("INTERNAL", &e.to_string()))
+        }
+        (ProtocolMode::Json, Err(e)) => json_error(error_code(e), &e.to_string()),
+    }
+}
+
+fn json_error(code: &'static str, message: &str) -> String {
+    serde_json::to_string(&JsonError { ok: false, code, message })
+        .unwrap_or_else(|_| format!(r#"{{"ok":false,"code":"{}"}}"#, code))
+}
+
+fn invalid_input(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidInput, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_register() {
+        let cmd = RegistrationCommand::from_text("register c1 tcp://127.0.0.1:9000 a b ttl=30").unwrap();
+        assert_eq!(cmd, RegistrationCommand::Register {
+            id: "c1".to_string(),
+            uri: "tcp://127.0.0.1:9000".to_string(),
+            events: vec!["a".to_string(), "b".to_string()],
+            ttl: Some(30),
+        });
+        assert!(RegistrationCommand::from_text("REGISTER c1").is_err());
+        assert!(RegistrationCommand::from_text("REGISTER c1 tcp://x:1 ttl=-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_text_proto() {
+        let cmd = RegistrationCommand::from_text("PROTO json").unwrap();
+        assert_eq!(cmd, RegistrationCommand::Proto { mode: ProtocolMode::Json });
+        assert!(RegistrationCommand::from_text("PROTO xml").is_err());
+        assert!(RegistrationCommand::from_text("PROTO").is_err());
+    }
+
+    #[test]
+    fn test_producer_id_of_mutating_commands() {
+        let cmd = RegistrationCommand::from_text("UNSUBSCRIBE c1 a").unwrap();
+        assert_eq!(cmd.producer_id(), Some("c1"));
+        assert_eq!(RegistrationCommand::from_text("LIST").unwrap().producer_id(), None);
+        assert_eq!(RegistrationCommand::from_text("AUTH secret").unwrap().producer_id(), None);
+    }
+
+    #[test]
+    fn test_parse_json_commands() {
+        let cmd = RegistrationCommand::from_json(
+            r#"{"cmd":"REGISTER","id":"c1","uri":"tcp://127.0.0.1:9000","events":["a"]}"#,
+        ).unwrap();
+        assert_eq!(cmd, RegistrationCommand::Register {
+            id: "c1".to_string(),
+            uri: "tcp://127.0.0.1:9000".to_string(),
+            events: vec!["a".to_string()],
+            ttl: None,
+        });
+
+        let cmd = RegistrationCommand::from_json(r#"{"cmd":"LIST"}"#).unwrap();
+        assert_eq!(cmd, RegistrationCommand::List);
+
+        let err = RegistrationCommand::from_json(r#"{"cmd":"SUBSCRIBE","id":"c1"}"#).unwrap_err();
+        assert_eq!(error_code(&err), "INVALID_REQUEST");
+    }
+
+    #[test]
+    fn test_format_json_reply() {
+        let ok = format_reply(ProtocolMode::Json, &Ok(RegistrationResponse::Subscribed {
+            id: "c1".to_string(),
+            event: "a".to_string(),
+        }));
+        let value: serde_json::Value = serde_json::from_str(&ok).unwrap();
+        assert_eq!(value["ok"], true);
+        assert_eq!(value["type"], "subscribed");
+        assert_eq!(value["event"], "a");
+
+        let err = format_reply(ProtocolMode::Json, &Err(std::io::Error::new(
+            ErrorKind::NotFound,
+            "Producer not found: c1",
+        )));
+        let value: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["code"], "NOT_FOUND");
+        assert_eq!(value["message"], "Producer not found: c1");
+    }
+
+    #[test]
+    fn test_receipt_reply() {
+        let cmd = RegistrationCommand::from_text("RECEIPT m-1").unwrap();
+        assert_eq!(cmd, RegistrationCommand::Receipt { id: "m-1".to_string() });
+        assert_eq!(cmd.producer_id(), None);
+        assert!(RegistrationCommand::from_text("RECEIPT").is_err());
+
+        let response = RegistrationResponse::Receipt {
+            id: "m-1".to_string(),
+            deliveries: vec![
+                DeliveryInfo { producer: "audit".to_string(), status: DeliveryStatus::Delivered },
+                DeliveryInfo {
+                    producer: "metrics".to_string(),
+                    status: DeliveryStatus::Redelivered { attempts: 3 },
+                },
+            ],
+        };
+        assert_eq!(response.to_text(), "Message 'm-1': audit delivered, metrics redelivered (3 attempts)");
+
+        let value: serde_json::Value = serde_json::from_str(&format_reply(ProtocolMode::Json, &Ok(response))).unwrap();
+        assert_eq!(value["type"], "receipt");
+        assert_eq!(value["deliveries"][1]["producer"], "metrics");
+        assert_eq!(value["deliveries"][1]["status"], "redelivered");
+        assert_eq!(value["deliveries"][1]["attempts"], 3);
+    }
+
+    #[test]
+    fn test_list_reply_is_single_json_line() {
+        let reply = format_reply(ProtocolMode::Json, &Ok(RegistrationResponse::List {
+            producers: vec![ProducerInfo {
+                id: "c1".to_string(),
+                protocol: "TCP".to_string(),
+                address: "127.0.0.1:9000".to_string(),
+                events: vec!["a".to_string()],
+                lease_remaining: None,
+            }],
+            events: vec!["a".to_string()],
+        }));
+        assert!(!reply.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(value["producers"][0]["address"], "127.0.0.1:9000");
+    }
+}
@@ -1,35 +1,121 @@
-use std::io::{BufRead, BufReader, Result, Write};
-use std::net::{TcpListener, TcpStream};
+use std::io::{Result, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 
 use crate::transport::{
     get_producer_pool,
+    Client,
     ClientFactory,
+    ProducerUri,
     message::EventMessage,
+    destination_policy::get_destination_policy,
+    protocols::{frame::Frame, TcpHandoffHandler},
+    push,
+    reactor::{get_reactor, CloseReason, ConnectionGroup, ConnectionHandler, Flow},
+    server_handle,
+    server_config::{ConnectionLimits, RegistrationConfig},
+    shutdown,
+    registration_auth::{RegistrationAuth, TokenScope, REGISTRATION_TOKENS_ENV},
+    registration_protocol::{
+        format_reply,
+        DeliveryInfo,
+        DEFAULT_DEAD_LETTER_LIMIT,
+        ProducerInfo,
+        ProtocolMode,
+        ScheduledInfo,
+        RegistrationCommand,
+        RegistrationResponse,
+    },
 };
 
-/// Default registration server port
-const DEFAULT_REGISTRATION_PORT: u16 = 49153;
+/// First line that switches a shared ingress connection to the registration protocol
+pub const REGISTRATION_HANDOFF_KEYWORD: &str = "REGISTRATION";
 
-/// Start the registration server for external consumer registration
-pub fn start_registration_server(port: u16) -> Result<()> {
-    let address = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&address)?;
+/// Per-connection registration state
+#[derive(Clone)]
+struct RegistrationSession {
+    mode: ProtocolMode,
+    auth: Arc<RegistrationAuth>,
+    scope: Option<TokenScope>,
+}
+
+impl RegistrationSession {
+    fn new(auth: Arc<RegistrationAuth>) -> Self {
+        Self {
+            mode: ProtocolMode::Text,
+            auth,
+            scope: None,
+        }
+    }
+}
+
+/// Start the dedicated registration listener described by a `RegistrationConfig`,
+/// with the same connection limits as the ingress listeners
+pub fn start_registration_server_with_config(config: &RegistrationConfig, limits: ConnectionLimits) -> Result<()> {
+    listen_registration(&config.bind_address(), config.auth.clone(), limits)
+}
+
+/// Build the handler that serves registration on connections handed off by the ingress listener
+pub fn registration_handoff_handler(config: &RegistrationConfig) -> Result<TcpHandoffHandler> {
+    warn_if_auth_disabled(&config.auth);
+    
+    let auth = Arc::new(config.auth.clone());
+    let loopback_only = config.loopback_only;
+    
+    Ok(Arc::new(move |mut stream: &TcpStream| {
+        let peer_is_loopback = stream.peer_addr().is_ok_and(|addr| addr.ip().is_loopback());
+        if loopback_only && !peer_is_loopback {
+            writeln!(stream, "ERROR:Registration is only available from loopback")?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "registration is only available from loopback",
+            ));
+        }
+        
+        let session = RegistrationSession::new(Arc::clone(&auth));
+        let connection = RegistrationConnection::start(stream, session, get_producer_pool())?;
+        Ok(Box::new(connection) as Box<dyn ConnectionHandler>)
+    }))
+}
+
+/// Accept registration connections on a dedicated listener
+fn listen_registration(address: &str, auth: RegistrationAuth, limits: ConnectionLimits) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    shutdown::register_listener(listener.local_addr()?);
     
     println!("Registration server listening on {}", address);
+    warn_if_auth_disabled(&auth);
     
     let pool = get_producer_pool();
+    let auth = Arc::new(auth);
+    let reactor = get_reactor()?;
+    let connections = Arc::new(ConnectionGroup::new());
     
     for stream in listener.incoming() {
+        if shutdown::is_shutting_down() {
+            break;
+        }
+        
         match stream {
-            Ok(stream) => {
-                let pool = Arc::clone(&pool);
-                thread::spawn(move || {
-                    if let Err(e) = handle_registration(stream, pool) {
-                        eprintln!("Registration error: {}", e);
-                    }
-                });
+            Ok(mut stream) => {
+                if connections.count() >= limits.max_connections {
+                    eprintln!(
+                        "Refusing registration connection from {}: {} connections already open",
+                        stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default(),
+                        limits.max_connections,
+                    );
+                    let _ = stream.write_all(b"ERROR too many connections\n");
+                    let _ = stream.shutdown(Shutdown::Both);
+                    continue;
+                }
+                
+                let session = RegistrationSession::new(Arc::clone(&auth));
+                let result = RegistrationConnection::start(&stream, session, Arc::clone(&pool))
+                    .and_then(|handler| reactor.register(stream, Box::new(handler), limits, &connections));
+                if let Err(e) = result {
+                    eprintln!("Registration error: {}", e);
+                }
             }
             Err(e) => {
                 eprintln!("Registration connection failed: {}", e);
@@ -40,185 +126,561 @@ pub fn start_registration_server(port: u16) -> Result<()> {
     Ok(())
 }
 
-/// Handle consumer registration connection
-fn handle_registration(mut stream: TcpStream, pool: Arc<crate::transport::ProducerPool>) -> Result<()> {
-    let peer_addr = stream.peer_addr()?;
-    
-    println!("[Registration] New connection from {}", peer_addr);
-    
-    // Send welcome message
-    writeln!(stream, "REGISTRATION_SERVER:1.0")?;
-    writeln!(stream, "Commands: REGISTER <id> <protocol>://<address> [events...]")?;
-    writeln!(stream, "          SUBSCRIBE <id> <event_name>")?;
-    writeln!(stream, "          UNSUBSCRIBE <id> <event_name>")?;
-    writeln!(stream, "          LIST")?;
-    writeln!(stream, "          QUIT")?;
-    stream.flush()?;
-    
-    let mut reader = BufReader::new(stream.try_clone()?);
-    let mut line = String::new();
-    loop {
-        line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
+fn warn_if_auth_disabled(auth: &RegistrationAuth) {
+    if !auth.is_enabled() {
+        println!("Warning: registration authentication disabled (configure tokens or set {} to enable)", REGISTRATION_TOKENS_ENV);
+    }
+}
+
+/// Registration protocol of one consumer connection
+struct RegistrationConnection {
+    session: RegistrationSession,
+    pool: Arc<crate::transport::ProducerPool>,
+    peer_addr: SocketAddr,
+}
+
+impl RegistrationConnection {
+    /// Greet a new connection with the list of commands
+    fn start(
+        mut stream: &TcpStream,
+        session: RegistrationSession,
+        pool: Arc<crate::transport::ProducerPool>,
+    ) -> Result<Self> {
+        let peer_addr = stream.peer_addr()?;
+        // A client that stops reading must not stall a shared worker for long
+        stream.set_write_timeout(Some(push::PUSH_WRITE_TIMEOUT))?;
         
-        if bytes_read == 0 {
-            break; // Connection closed
-        }
+        println!("[Registration] New connection from {}", peer_addr);
+        
+        // Send welcome message
+        writeln!(stream, "REGISTRATION_SERVER:1.0")?;
+        writeln!(stream, "Commands: REGISTER <id> <protocol>://<address> [events...] [ttl=<seconds>]")?;
+        writeln!(stream, "          DEREGISTER <id>")?;
+        writeln!(stream, "          UPDATE <id> <protocol>://<address>")?;
+        writeln!(stream, "          HEARTBEAT <id>")?;
+        writeln!(stream, "          SUBSCRIBE <id> <event_name>")?;
+        writeln!(stream, "          UNSUBSCRIBE <id> <event_name>")?;
+        writeln!(stream, "          LIST")?;
+        writeln!(stream, "          RECEIPT <message_id>")?;
+        writeln!(stream, "          STATS")?;
+        writeln!(stream, "          DEADLETTERS [limit]")?;
+        writeln!(stream, "          SCHEDULED")?;
+        writeln!(stream, "          CANCEL <schedule_id>")?;
+        writeln!(stream, "          UNRETAIN <event_name|*>")?;
+        writeln!(stream, "          PROTO <TEXT|JSON>")?;
+        writeln!(stream, "          AUTH <token>")?;
+        writeln!(stream, "          QUIT")?;
+        stream.flush()?;
+        
+        Ok(Self { session, pool, peer_addr })
+    }
+}
+
+impl ConnectionHandler for RegistrationConnection {
+    fn on_frame(&mut self, frame: Frame, mut stream: &TcpStream) -> Result<Flow> {
+        let line = match frame {
+            Frame::Line(line) => line,
+            Frame::TooLarge(size) => {
+                let error = std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("command of {} bytes is too long", size),
+                );
+                writeln!(stream, "{}", format_reply(self.session.mode, &Err(error)))?;
+                return Ok(Flow::Continue);
+            }
+        };
         
         let command = line.trim();
         if command.is_empty() {
-            continue;
+            return Ok(Flow::Continue);
         }
         
-        let response = process_registration_command(command, &pool);
+        let session = &mut self.session;
+        let parsed = match session.mode {
+            ProtocolMode::Text => RegistrationCommand::from_text(command),
+            ProtocolMode::Json => RegistrationCommand::from_json(command),
+        };
         
-        match response {
-            Ok(msg) => {
-                writeln!(stream, "OK:{}", msg)?;
-            }
-            Err(e) => {
-                writeln!(stream, "ERROR:{}", e)?;
+        let parsed = match parsed {
+            // Resolving and delivering run off the connection workers; the
+            // command doesn't change the session, so it can use a copy
+            Ok(command) if command.may_block() => {
+                let mut session = session.clone();
+                let pool = Arc::clone(&self.pool);
+                let mut stream = stream.try_clone()?;
+                return Ok(Flow::Defer(Box::new(move || {
+                    let response = execute_registration_command(command, &mut session, &pool);
+                    // A failed write shows up as a closed connection on the next read
+                    let _ = writeln!(stream, "{}", format_reply(session.mode, &response)).and_then(|()| stream.flush());
+                })));
             }
-        }
+            parsed => parsed,
+        };
+        let response = parsed.and_then(|cmd| execute_registration_command(cmd, session, &self.pool));
         
+        writeln!(stream, "{}", format_reply(session.mode, &response))?;
         stream.flush()?;
         
-        if command.eq_ignore_ascii_case("QUIT") {
-            break;
+        if matches!(response, Ok(RegistrationResponse::Goodbye)) {
+            return Ok(Flow::Close);
         }
+        Ok(Flow::Continue)
+    }
+
+    fn on_close(&mut self, reason: &CloseReason) {
+        if let CloseReason::Failed(e) = reason {
+            eprintln!("Registration error: {}", e);
+        }
+        println!("[Registration] Connection closed: {}", self.peer_addr);
     }
-    
-    println!("[Registration] Connection closed: {}", peer_addr);
-    Ok(())
 }
 
-/// Process registration command
-fn process_registration_command(
-    command: &str,
+/// Execute a parsed registration command against the producer pool
+fn execute_registration_command(
+    command: RegistrationCommand,
+    session: &mut RegistrationSession,
     pool: &Arc<crate::transport::ProducerPool>,
-) -> Result<String> {
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    
-    if parts.is_empty() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Empty command",
-        ));
+) -> Result<RegistrationResponse> {
+    // Mutating commands require a token scoped to the producer id,
+    // and can't touch the producers of push subscribers
+    if let Some(id) = command.producer_id() {
+        session.auth.authorize(session.scope.as_ref(), id)?;
+        push::check_not_reserved(id)?;
     }
     
-    match parts[0].to_uppercase().as_str() {
-        "REGISTER" => {
-            if parts.len() < 3 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Usage: REGISTER <id> <protocol>://<address> [events...]",
-                ));
-            }
+    match command {
+        RegistrationCommand::Register { id, uri, mut events, ttl } => {
+            let ttl = ttl.map(validate_ttl).transpose()?;
+            let (client, uri_events) = create_client_from_uri(&uri)?;
             
-            let id = parts[1].to_string();
-            let uri = parts[2];
-            
-            // Parse URI: protocol://address
-            if !uri.contains("://") {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Invalid URI format. Expected: protocol://address",
-                ));
-            }
-            
-            let (protocol, address) = uri.split_once("://")
-                .ok_or_else(|| std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Invalid URI format",
-                ))?;
-            
-            // Get event subscriptions (optional)
-            let events: Vec<&str> = parts.iter().skip(3).copied().collect();
-            
-            // Create client based on protocol
-            let client = match protocol.to_uppercase().as_str() {
-                "TCP" => {
-                    ClientFactory::create_tcp_client(address.to_string())
-                }
-                _ => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        format!("Unsupported protocol: {}", protocol),
-                    ));
+            // Events from the URI's `?events=` option add to the explicit list
+            for event in uri_events {
+                if !events.contains(&event) {
+                    events.push(event);
                 }
-            };
+            }
             
             // Add producer with events
             if events.is_empty() {
                 pool.add_producer(id.clone(), client)?;
-                Ok(format!("Producer '{}' registered", id))
             } else {
-                pool.add_producer_with_events(id.clone(), client, &events)?;
-                Ok(format!("Producer '{}' registered with events: {:?}", id, events))
-            }
-        }
-        
-        "SUBSCRIBE" => {
-            if parts.len() != 3 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Usage: SUBSCRIBE <id> <event_name>",
-                ));
+                let event_refs: Vec<&str> = events.iter().map(String::as_str).collect();
+                pool.add_producer_with_events(id.clone(), client, &event_refs)?;
             }
             
-            let id = parts[1];
-            let event = parts[2];
+            // Re-registering replaces any previous lease
+            match ttl {
+                Some(ttl) => pool.set_lease(&id, ttl)?,
+                None => pool.clear_lease(&id),
+            }
             
-            pool.subscribe_producer_to_event(id, event)?;
-            Ok(format!("Producer '{}' subscribed to event '{}'", id, event))
+            Ok(RegistrationResponse::Registered {
+                id,
+                events,
+                ttl: ttl.map(|ttl| ttl.as_secs()),
+            })
         }
         
-        "UNSUBSCRIBE" => {
-            if parts.len() != 3 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Usage: UNSUBSCRIBE <id> <event_name>",
-                ));
-            }
-            
-            let id = parts[1];
-            let event = parts[2];
-            
-            pool.unsubscribe_producer_from_event(id, event)?;
-            Ok(format!("Producer '{}' unsubscribed from event '{}'", id, event))
+        RegistrationCommand::Deregister { id } => {
+            pool.remove_producer(&id)
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Producer not found: {}", id),
+                ))?;
+            Ok(RegistrationResponse::Deregistered { id })
         }
         
-        "LIST" => {
-            let producer_ids = pool.get_producer_ids();
-            let events = pool.get_subscribed_events();
-            
-            let mut info = format!("Producers: {}\n", producer_ids.len());
-            for id in &producer_ids {
-                if let Some(producer) = pool.get_producer(id) {
-                    let subscribed = producer.subscribed_events();
-                    info.push_str(&format!("  {} -> {} (events: {:?})\n", 
-                        id, producer.address(), subscribed));
-                }
+        RegistrationCommand::Update { id, uri } => {
+            let (client, uri_events) = create_client_from_uri(&uri)?;
+            pool.update_producer_client(&id, client)?;
+            for event in uri_events {
+                pool.subscribe_producer_to_event(&id, &event)?;
             }
-            info.push_str(&format!("Events: {:?}", events));
+            Ok(RegistrationResponse::Updated { id, uri })
+        }
+        
+        RegistrationCommand::Heartbeat { id } => {
+            let ttl = pool.renew_lease(&id)?;
+            Ok(RegistrationResponse::LeaseRenewed { id, ttl: ttl.as_secs() })
+        }
+        
+        RegistrationCommand::Subscribe { id, event } => {
+            pool.subscribe_producer_to_event(&id, &event)?;
+            Ok(RegistrationResponse::Subscribed { id, event })
+        }
+        
+        RegistrationCommand::Unsubscribe { id, event } => {
+            pool.unsubscribe_producer_from_event(&id, &event)?;
+            Ok(RegistrationResponse::Unsubscribed { id, event })
+        }
+        
+        RegistrationCommand::List => {
+            let producers = pool.get_producer_ids()
+                .into_iter()
+                .filter_map(|id| {
+                    let producer = pool.get_producer(&id)?;
+                    Some(ProducerInfo {
+                        protocol: producer.protocol_name().to_string(),
+                        address: producer.address().to_string(),
+                        events: producer.subscribed_events(),
+                        lease_remaining: pool.get_lease(&id).map(|lease| lease.remaining().as_secs()),
+                        id,
+                    })
+                })
+                .collect();
             
-            Ok(info)
+            Ok(RegistrationResponse::List {
+                producers,
+                events: pool.get_subscribed_events(),
+            })
         }
         
-        "QUIT" => {
-            Ok("Goodbye".to_string())
+        RegistrationCommand::Receipt { id } => {
+            let deliveries = pool.delivery_receipt(&id)
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No delivery receipt for message: {}", id),
+                ))?
+                .into_iter()
+                .map(|(producer, status)| DeliveryInfo { producer, status })
+                .collect();
+            Ok(RegistrationResponse::Receipt { id, deliveries })
+        }
+        
+        RegistrationCommand::Stats => {
+            Ok(RegistrationResponse::Stats {
+                producers: pool.count(),
+                events: pool.get_subscribed_events().len(),
+                retained: pool.retained_count(),
+                scheduled: pool.scheduler().list().len(),
+                dead_letters: pool.dead_letters().total(),
+                expired: pool.dead_letters().expired(),
+                duplicates_suppressed: pool.dedup().suppressed(),
+                rate_limited: pool.rate_limiter().counts(),
+                listeners: server_handle::running_listeners(),
+            })
         }
         
-        _ => {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                format!("Unknown command: {}", parts[0]),
-            ))
+        RegistrationCommand::DeadLetters { limit } => {
+            // Dead letters hold message bodies from every producer
+            session.auth.authorize_unrestricted(session.scope.as_ref(), "read dead letters")?;
+            let store = pool.dead_letters();
+            Ok(RegistrationResponse::DeadLetters {
+                total: store.total(),
+                expired: store.expired(),
+                letters: store.recent(limit.unwrap_or(DEFAULT_DEAD_LETTER_LIMIT)),
+            })
         }
+        
+        RegistrationCommand::Scheduled => {
+            // Scheduled messages may belong to any producer
+            session.auth.authorize_unrestricted(session.scope.as_ref(), "list scheduled messages")?;
+            let messages = pool.scheduler().list()
+                .into_iter()
+                .map(|scheduled| ScheduledInfo {
+                    id: scheduled.schedule_id,
+                    event_name: scheduled.message.event_name,
+                    deliver_at: scheduled.deliver_at,
+                    msg: scheduled.message.msg,
+                })
+                .collect();
+            Ok(RegistrationResponse::Scheduled { messages })
+        }
+        
+        RegistrationCommand::Cancel { id } => {
+            // Scheduled messages don't belong to any producer
+            session.auth.authorize_unrestricted(session.scope.as_ref(), "cancel scheduled messages")?;
+            pool.scheduler().cancel(&id)
+                .ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No scheduled message: {}", id),
+                ))?;
+            Ok(RegistrationResponse::Cancelled { id })
+        }
+        
+        RegistrationCommand::Unretain { event } => {
+            // Retained messages are shared by every producer
+            session.auth.authorize_unrestricted(session.scope.as_ref(), "clear retained messages")?;
+            let cleared = if event == "*" {
+                pool.clear_all_retained()
+            } else {
+                usize::from(pool.clear_retained(&event))
+            };
+            Ok(RegistrationResponse::Unretained { event, cleared })
+        }
+        
+        RegistrationCommand::Proto { mode } => {
+            // Switch before replying so the acknowledgement uses the new format
+            session.mode = mode;
+            Ok(RegistrationResponse::Proto { mode })
+        }
+        
+        RegistrationCommand::Auth { token } => {
+            let scope = session.auth.authenticate(&token)?;
+            let prefixes = scope.prefixes().to_vec();
+            session.scope = Some(scope);
+            Ok(RegistrationResponse::Authenticated { prefixes })
+        }
+        
+        RegistrationCommand::Quit => {
+            Ok(RegistrationResponse::Goodbye)
+        }
+    }
+}
+
+/// Create a client from a `protocol://address[?events=a,b&timeout=3s]` URI
+///
+/// Returns the client together with the events listed in the URI.
+fn create_client_from_uri(uri: &str) -> Result<(Arc<dyn Client>, Vec<String>)> {
+    let uri = ProducerUri::parse(uri)?;
+    
+    // Refuse destinations outside the configured allowlist before registering them
+    get_destination_policy().check_address(&uri.address)?;
+    
+    let client = ClientFactory::create_from_producer_uri(&uri)?;
+    Ok((client, uri.events))
+}
+
+/// Validate a lease TTL given in whole seconds
+fn validate_ttl(secs: u64) -> Result<Duration> {
+    if secs == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid ttl: 0 (expected a positive number of seconds)",
+        ));
     }
+    Ok(Duration::from_secs(secs))
 }
 
-/// Start registration server on default port
-pub fn start_registration_server_default() -> Result<()> {
-    start_registration_server(DEFAULT_REGISTRATION_PORT)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::ProducerPool;
+
+    /// Process a text registration command, returning the `OK:` response body
+    fn process_registration_command(
+        command: &str,
+        pool: &Arc<ProducerPool>,
+    ) -> Result<String> {
+        let mut session = RegistrationSession::new(Arc::new(RegistrationAuth::disabled()));
+        process_in_session(command, &mut session, pool)
+    }
+
+    fn process_in_session(
+        command: &str,
+        session: &mut RegistrationSession,
+        pool: &Arc<ProducerPool>,
+    ) -> Result<String> {
+        let cmd = RegistrationCommand::from_text(command)?;
+        execute_registration_command(cmd, session, pool).map(|response| response.to_text())
+    }
+
+    #[test]
+    fn test_register_and_deregister() {
+        let pool = Arc::new(ProducerPool::new());
+        
+        process_registration_command("REGISTER consumer1 tcp://127.0.0.1:9000 event1", &pool).unwrap();
+        assert!(pool.has_producer("consumer1"));
+        
+        process_registration_command("DEREGISTER consumer1", &pool).unwrap();
+        assert!(!pool.has_producer("consumer1"));
+        assert!(pool.get_event_subscribers("event1").is_empty());
+        
+        assert!(process_registration_command("DEREGISTER consumer1", &pool).is_err());
+        
+        // The push prefix is reserved for connections that subscribe themselves
+        assert!(process_registration_command("REGISTER push-127.0.0.1:5000 tcp://127.0.0.1:9000", &pool).is_err());
+        assert!(!pool.has_producer("push-127.0.0.1:5000"));
+    }
+
+    #[test]
+    fn test_push_producers_are_not_managed_by_registration() {
+        let pool = Arc::new(ProducerPool::new());
+        let client = Arc::new(crate::transport::clients::TcpClient::new("127.0.0.1:9000".to_string()));
+        pool.add_producer_with_events("push-127.0.0.1:5000".to_string(), client, &["a"]).unwrap();
+        
+        for command in [
+            "DEREGISTER push-127.0.0.1:5000",
+            "UPDATE push-127.0.0.1:5000 tcp://127.0.0.1:9100",
+            "SUBSCRIBE push-127.0.0.1:5000 b",
+            "UNSUBSCRIBE push-127.0.0.1:5000 a",
+        ] {
+            let error = process_registration_command(command, &pool).unwrap_err();
+            assert!(error.to_string().contains("reserved for push subscribers"), "{}: {}", command, error);
+        }
+        
+        let producer = pool.get_producer("push-127.0.0.1:5000").unwrap();
+        assert_eq!(producer.address(), "127.0.0.1:9000");
+        assert_eq!(pool.get_event_subscribers("a"), vec!["push-127.0.0.1:5000".to_string()]);
+        assert!(pool.get_event_subscribers("b").is_empty());
+    }
+
+    #[test]
+    fn test_update_keeps_subscriptions() {
+        let pool = Arc::new(ProducerPool::new());
+        
+        process_registration_command("REGISTER consumer1 tcp://127.0.0.1:9000 event1 event2", &pool).unwrap();
+        process_registration_command("UPDATE consumer1 tcp://127.0.0.1:9100", &pool).unwrap();
+        
+        let producer = pool.get_producer("consumer1").unwrap();
+        assert_eq!(producer.address(), "127.0.0.1:9100");
+        assert!(producer.is_subscribed("event1"));
+        assert!(producer.is_subscribed("event2"));
+        assert_eq!(pool.get_event_subscribers("event2"), vec!["consumer1".to_string()]);
+        
+        assert!(process_registration_command("UPDATE consumer1 udp://127.0.0.1:9100", &pool).is_err());
+        assert!(process_registration_command("UPDATE missing tcp://127.0.0.1:9100", &pool).is_err());
+    }
+
+    #[test]
+    fn test_register_merges_uri_events() {
+        let pool = Arc::new(ProducerPool::new());
+        
+        process_registration_command(
+            "REGISTER consumer1 tcp://127.0.0.1:9000?events=event1,event2&timeout=3s event2 event3",
+            &pool,
+        ).unwrap();
+        
+        let producer = pool.get_producer("consumer1").unwrap();
+        assert_eq!(producer.address(), "127.0.0.1:9000");
+        for event in ["event1", "event2", "event3"] {
+            assert!(producer.is_subscribed(event));
+        }
+        
+        assert!(process_registration_command("REGISTER consumer2 tcp://127.0.0.1:9001?retries=3", &pool).is_err());
+    }
+
+    #[test]
+    fn test_register_with_ttl_and_heartbeat() {
+        let pool = Arc::new(ProducerPool::new());
+        
+        process_registration_command("REGISTER consumer1 tcp://127.0.0.1:9000 event1 ttl=30", &pool).unwrap();
+        assert_eq!(pool.get_lease("consumer1").unwrap().ttl(), Duration::from_secs(30));
+        assert_eq!(pool.get_event_subscribers("event1"), vec!["consumer1".to_string()]);
+        assert!(pool.get_event_subscribers("ttl=30").is_empty());
+        
+        process_registration_command("HEARTBEAT consumer1", &pool).unwrap();
+        
+        assert!(process_registration_command("REGISTER consumer2 tcp://127.0.0.1:9001 ttl=abc", &pool).is_err());
+        assert!(!pool.has_producer("consumer2"));
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        let pool = Arc::new(ProducerPool::new());
+        for id in ["m-1", "m-2"] {
+            pool.dead_letters().push(crate::transport::DeadLetter {
+                message_id: Some(id.to_string()),
+                event_name: "alerts".to_string(),
+                producer_id: None,
+                message: "disk full".to_string(),
+                reason: crate::transport::EXPIRED_REASON.to_string(),
+            });
+        }
+        
+        let reply = process_registration_command("DEADLETTERS 1", &pool).unwrap();
+        assert_eq!(reply, "Dead letters: 2 (2 expired)\n  alerts -> -: expired (m-2)");
+        assert!(process_registration_command("DEADLETTERS many", &pool).is_err());
+        
+        // With tokens configured only an admin token may read them
+        let auth = RegistrationAuth::parse("secret-a=team-a-;admin=*").unwrap();
+        let mut session = RegistrationSession::new(Arc::new(auth));
+        let err = process_in_session("DEADLETTERS", &mut session, &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        process_in_session("AUTH secret-a", &mut session, &pool).unwrap();
+        assert!(process_in_session("DEADLETTERS", &mut session, &pool).is_err());
+        process_in_session("AUTH admin", &mut session, &pool).unwrap();
+        assert!(process_in_session("DEADLETTERS", &mut session, &pool).is_ok());
+    }
+
+    #[test]
+    fn test_scheduled_and_cancel() {
+        let pool = Arc::new(ProducerPool::new());
+        let reminder = EventMessage {
+            id: Some("r-1".to_string()),
+            ..EventMessage::new("standup".to_string(), "reminders".to_string())
+        };
+        pool.scheduler().schedule(reminder, Duration::from_secs(900), None).unwrap();
+        
+        let reply = process_registration_command("SCHEDULED", &pool).unwrap();
+        assert!(reply.starts_with("Scheduled messages: 1\n  r-1 -> reminders at "), "{}", reply);
+        assert!(reply.ends_with(": standup"), "{}", reply);
+        
+        assert_eq!(process_registration_command("CANCEL r-1", &pool).unwrap(), "Scheduled message 'r-1' cancelled");
+        let err = process_registration_command("CANCEL r-1", &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(process_registration_command("SCHEDULED", &pool).unwrap(), "Scheduled messages: 0");
+        
+        // With tokens configured only an admin token may list them
+        let auth = RegistrationAuth::parse("secret-a=team-a-;admin=*").unwrap();
+        let mut session = RegistrationSession::new(Arc::new(auth));
+        process_in_session("AUTH secret-a", &mut session, &pool).unwrap();
+        let err = process_in_session("SCHEDULED", &mut session, &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        process_in_session("AUTH admin", &mut session, &pool).unwrap();
+        assert!(process_in_session("SCHEDULED", &mut session, &pool).is_ok());
+    }
+
+    #[test]
+    fn test_stats() {
+        let pool = Arc::new(ProducerPool::new());
+        pool.retain_message("price", "42");
+        pool.set_dedup_rules(std::collections::HashMap::from([("payments".to_string(), crate::transport::DedupRule {
+            window: Duration::from_secs(60),
+            header: None,
+        })]));
+        let payment = EventMessage {
+            id: Some("p-1".to_string()),
+            ..EventMessage::new("charge".to_string(), "payments".to_string())
+        };
+        pool.dedup().record(&payment, std::time::Instant::now());
+        pool.dedup().is_duplicate(&payment, std::time::Instant::now());
+        
+        assert_eq!(
+            process_registration_command("STATS", &pool).unwrap(),
+            "Producers: 0, events: 0, retained: 1, scheduled: 0, dead letters: 0 (0 expired), \
+             duplicates suppressed: 1, rate limited: 0 (peer 0, identity 0, event 0)",
+        );
+    }
+
+    #[test]
+    fn test_unretain() {
+        let pool = Arc::new(ProducerPool::new());
+        pool.retain_message("price", "42");
+        pool.retain_message("config", "v2");
+        
+        let reply = process_registration_command("UNRETAIN price", &pool).unwrap();
+        assert_eq!(reply, "Cleared 1 retained message(s) for 'price'");
+        assert_eq!(pool.get_retained("config").as_deref(), Some("v2"));
+        
+        let auth = RegistrationAuth::parse("secret-a=team-a-").unwrap();
+        let mut session = RegistrationSession::new(Arc::new(auth));
+        process_in_session("AUTH secret-a", &mut session, &pool).unwrap();
+        let err = process_in_session("UNRETAIN *", &mut session, &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        
+        process_registration_command("UNRETAIN *", &pool).unwrap();
+        assert_eq!(pool.get_retained("config"), None);
+    }
+
+    #[test]
+    fn test_auth_scopes_mutating_commands() {
+        let pool = Arc::new(ProducerPool::new());
+        let auth = RegistrationAuth::parse("secret-a=team-a-").unwrap();
+        let mut session = RegistrationSession::new(Arc::new(auth));
+        
+        // Read-only commands need no token
+        process_in_session("LIST", &mut session, &pool).unwrap();
+        
+        let err = process_in_session("REGISTER team-a-1 tcp://127.0.0.1:9000", &mut session, &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        
+        assert!(process_in_session("AUTH wrong", &mut session, &pool).is_err());
+        process_in_session("AUTH secret-a", &mut session, &pool).unwrap();
+        process_in_session("REGISTER team-a-1 tcp://127.0.0.1:9000 event1", &mut session, &pool).unwrap();
+        
+        let err = process_in_session("REGISTER team-b-1 tcp://127.0.0.1:9001", &mut session, &pool).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(!pool.has_producer("team-b-1"));
+    }
 }
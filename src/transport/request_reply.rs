@@ -0,0 +1,413 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Result, Write};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::transport::message::EventMessage;
+
+/// Default time a request waits for its reply
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between sweeps that time out unanswered requests
+pub const REQUEST_SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default number of requests that may wait for a reply at once
+pub const DEFAULT_MAX_PENDING_REQUESTS: usize = 10_000;
+
+/// Writer shared by everything that replies on one ingress connection
+///
+/// Each line is written under the lock, so replies routed from other
+/// connections never interleave with the connection's own acknowledgements.
+#[derive(Clone)]
+pub struct ConnectionWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl ConnectionWriter {
+    /// Wrap the write half of a connection
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(writer))))
+    }
+
+    /// Write one line and flush it
+    pub fn write_line(&self, line: &str) -> Result<()> {
+        let mut writer = self.0.lock().unwrap();
+        writer.write_all(format!("{}\n", line).as_bytes())?;
+        writer.flush()
+    }
+
+    /// Check whether both writers belong to the same connection
+    pub fn is_same_connection(&self, other: &ConnectionWriter) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Get a key identifying the connection while this writer is alive
+    fn connection_key(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+}
+
+impl Write for ConnectionWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+/// Subscriber a request was handed to, and so the only one allowed to reply
+#[derive(Clone)]
+pub struct Responder {
+    producer_id: String,
+    // Connection of a subscriber that receives pushes on its own connection
+    connection: Option<ConnectionWriter>,
+    // Otherwise, the addresses of the subscriber's registered host
+    peers: Vec<IpAddr>,
+}
+
+impl Responder {
+    /// Bind replies to a subscriber that was given the request on its own connection
+    pub fn connection(producer_id: &str, connection: ConnectionWriter) -> Self {
+        Self { producer_id: producer_id.to_string(), connection: Some(connection), peers: Vec::new() }
+    }
+
+    /// Bind replies to the host of a subscriber's `host:port` address
+    pub fn address(producer_id: &str, address: &str) -> Self {
+        let peers = address.to_socket_addrs()
+            .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+            .unwrap_or_default();
+        Self { producer_id: producer_id.to_string(), connection: None, peers }
+    }
+
+    fn accepts(&self, peer: Option<IpAddr>, connection: Option<&ConnectionWriter>) -> bool {
+        match &self.connection {
+            Some(expected) => connection.is_some_and(|connection| connection.is_same_connection(expected)),
+            None => peer.is_some_and(|peer| self.peers.contains(&peer)),
+        }
+    }
+}
+
+/// Request waiting for a reply on `reply_to`
+struct PendingRequest {
+    // The requester's own correlation id, used in the lines written back to it
+    correlation_id: String,
+    reply_to: String,
+    requester: ConnectionWriter,
+    responder: Option<Responder>,
+    timeout: Duration,
+    deadline: Instant,
+}
+
+impl PendingRequest {
+    // Identifies the request among those of its requester
+    fn outstanding_key(&self) -> (usize, String) {
+        (self.requester.connection_key(), self.correlation_id.clone())
+    }
+}
+
+#[derive(Default)]
+struct RequestTable {
+    // Token -> request
+    by_token: HashMap<String, PendingRequest>,
+    // (requesting connection, correlation id) of every pending request
+    outstanding: HashSet<(usize, String)>,
+}
+
+impl RequestTable {
+    fn remove(&mut self, token: &str) -> Option<PendingRequest> {
+        let request = self.by_token.remove(token)?;
+        self.outstanding.remove(&request.outstanding_key());
+        Some(request)
+    }
+}
+
+/// Requests published with `reply_to` and `correlation_id`, keyed by a token
+/// the server generates for each request
+///
+/// The subscriber gets the token as the request's `correlation_id`, so it
+/// can't be guessed by anyone the request wasn't sent to. A reply is a
+/// message published on the request's `reply_to` event with the token as its
+/// `correlation_id`, sent by the subscriber the request was handed to. It is
+/// written back to the requesting connection as `REPLY <correlation_id> <msg>`,
+/// with the requester's own correlation id, instead of being routed to subscribers.
+pub struct PendingRequests {
+    requests: Mutex<RequestTable>,
+    max_pending: usize,
+}
+
+impl Default for PendingRequests {
+    fn default() -> Self {
+        Self::with_max_pending(DEFAULT_MAX_PENDING_REQUESTS)
+    }
+}
+
+impl PendingRequests {
+    /// Create an empty request table that holds at most `max_pending` requests
+    pub fn with_max_pending(max_pending: usize) -> Self {
+        Self { requests: Mutex::new(RequestTable::default()), max_pending }
+    }
+
+    /// Wait for a reply to a request until `timeout` passes, returning the
+    /// token to send to the subscriber as its correlation id
+    ///
+    /// Fails if the connection already waits on the correlation id, or
+    /// `max_pending` requests are already waiting.
+    pub fn register(
+        &self,
+        correlation_id: &str,
+        reply_to: &str,
+        requester: ConnectionWriter,
+        timeout: Duration,
+    ) -> Result<String> {
+        let request = PendingRequest {
+            correlation_id: correlation_id.to_string(),
+            reply_to: reply_to.to_string(),
+            requester,
+            responder: None,
+            timeout,
+            deadline: Instant::now() + timeout,
+        };
+
+        let mut requests = self.requests.lock().unwrap();
+        if requests.outstanding.contains(&request.outstanding_key()) {
+            return Err(std::io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("correlation_id '{}' is already waiting for a reply", correlation_id),
+            ));
+        }
+        if requests.by_token.len() >= self.max_pending {
+            return Err(std::io::Error::new(
+                ErrorKind::QuotaExceeded,
+                format!("{} requests are already waiting for a reply", self.max_pending),
+            ));
+        }
+
+        let token = Self::generate_token()?;
+        requests.outstanding.insert(request.outstanding_key());
+        requests.by_token.insert(token.clone(), request);
+        Ok(token)
+    }
+
+    /// Bind a request to the subscriber it is about to be handed to
+    ///
+    /// Called before each attempt, so a reply that arrives while the request
+    /// is still being sent is already accepted.
+    pub fn assign(&self, token: &str, responder: Responder) {
+        if let Some(request) = self.requests.lock().unwrap().by_token.get_mut(token) {
+            request.responder = Some(responder);
+        }
+    }
+
+    /// Send a message back to its requester if it answers a pending request
+    ///
+    /// Returns `Ok(false)` for messages that are not replies, which are routed
+    /// normally, and an error for a reply from anyone but the subscriber the
+    /// request was handed to.
+    pub fn complete(
+        &self,
+        message: &EventMessage,
+        peer: Option<IpAddr>,
+        connection: Option<&ConnectionWriter>,
+    ) -> Result<bool> {
+        let Some(token) = message.correlation_id.as_deref() else {
+            return Ok(false);
+        };
+
+        let request = {
+            let mut requests = self.requests.lock().unwrap();
+            let Some(request) = requests.by_token.get(token).filter(|request| request.reply_to == message.event_name) else {
+                return Ok(false);
+            };
+            match &request.responder {
+                Some(responder) if responder.accepts(peer, connection) => requests.remove(token),
+                Some(responder) => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        format!("only '{}' may reply to this request", responder.producer_id),
+                    ));
+                }
+                None => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        "request has not been handed to a subscriber",
+                    ));
+                }
+            }
+        };
+
+        if let Some(request) = request {
+            // Best effort: the requester may have disconnected
+            let _ = request.requester.write_line(&format!("REPLY {} {}", request.correlation_id, message.msg));
+        }
+        Ok(true)
+    }
+
+    /// Give up on a request that could not be handed to any subscriber
+    pub fn fail(&self, token: &str, reason: &str) {
+        if let Some(request) = self.requests.lock().unwrap().remove(token) {
+            let _ = request.requester.write_line(&format!("NOREPLY {} {}", request.correlation_id, reason));
+        }
+    }
+
+    /// Generate a request token that can't be guessed from earlier ones
+    fn generate_token() -> Result<String> {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes).map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(format!("rq-{}", bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()))
+    }
+
+    /// Answer requests whose deadline passed with a timeout, returning the
+    /// requesters' correlation ids
+    pub fn expire(&self, now: Instant) -> Vec<String> {
+        let expired: Vec<PendingRequest> = {
+            let mut requests = self.requests.lock().unwrap();
+            let tokens: Vec<String> = requests.by_token.iter()
+                .filter(|(_, request)| request.deadline <= now)
+                .map(|(token, _)| token.clone())
+                .collect();
+            tokens.into_iter()
+                .filter_map(|token| requests.remove(&token))
+                .collect()
+        };
+
+        expired.into_iter()
+            .map(|request| {
+                let _ = request.requester.write_line(&format!(
+                    "TIMEOUT {} no reply within {:?}", request.correlation_id, request.timeout
+                ));
+                request.correlation_id
+            })
+            .collect()
+    }
+}
+
+/// Start a background thread that times out unanswered requests
+pub fn start_request_reaper(requests: Arc<PendingRequests>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        requests.expire(Instant::now());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connection writer that records what was written
+    fn recording_writer() -> (ConnectionWriter, Arc<Mutex<Vec<u8>>>) {
+        struct Recorder(Arc<Mutex<Vec<u8>>>);
+        impl Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        (ConnectionWriter::new(Recorder(Arc::clone(&output))), output)
+    }
+
+    fn reply(event: &str, correlation_id: &str, msg: &str) -> EventMessage {
+        EventMessage {
+            correlation_id: Some(correlation_id.to_string()),
+            ..EventMessage::new(msg.to_string(), event.to_string())
+        }
+    }
+
+    fn loopback() -> Option<IpAddr> {
+        Some(IpAddr::from([127, 0, 0, 1]))
+    }
+
+    #[test]
+    fn test_reply_routed_to_requester() {
+        let requests = PendingRequests::default();
+        let (requester, output) = recording_writer();
+        let token = requests.register("c-1", "user.reply", requester.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap();
+        assert!(requests.register("c-1", "user.reply", requester, DEFAULT_REQUEST_TIMEOUT).is_err());
+        assert!(token.starts_with("rq-") && token.len() == 35);
+        requests.assign(&token, Responder::address("users", "127.0.0.1:9000"));
+
+        // Only a message on the reply event with the request's token is a reply
+        assert!(!requests.complete(&reply("other", &token, "nope"), loopback(), None).unwrap());
+        assert!(!requests.complete(&reply("user.reply", "c-1", "nope"), loopback(), None).unwrap());
+        assert!(!requests.complete(&EventMessage::new("x".to_string(), "user.reply".to_string()), loopback(), None).unwrap());
+        assert!(requests.complete(&reply("user.reply", &token, "alice"), loopback(), None).unwrap());
+        assert!(!requests.complete(&reply("user.reply", &token, "again"), loopback(), None).unwrap());
+
+        assert_eq!(String::from_utf8(output.lock().unwrap().clone()).unwrap(), "REPLY c-1 alice\n");
+    }
+
+    #[test]
+    fn test_reply_only_accepted_from_the_assigned_subscriber() {
+        let requests = PendingRequests::default();
+        let (requester, output) = recording_writer();
+        let (subscriber, _) = recording_writer();
+        let (stranger, _) = recording_writer();
+
+        let by_address = requests.register("c-1", "r", requester.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap();
+        let by_connection = requests.register("c-2", "r", requester, DEFAULT_REQUEST_TIMEOUT).unwrap();
+        assert_ne!(by_address, by_connection);
+
+        // Not handed to anyone yet
+        let error = requests.complete(&reply("r", &by_address, "early"), loopback(), None).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+        requests.assign(&by_address, Responder::address("users", "127.0.0.1:9000"));
+        let other_host = Some(IpAddr::from([10, 0, 0, 9]));
+        assert!(requests.complete(&reply("r", &by_address, "forged"), other_host, None).is_err());
+        assert!(requests.complete(&reply("r", &by_address, "alice"), loopback(), None).unwrap());
+
+        requests.assign(&by_connection, Responder::connection("push-users", subscriber.clone()));
+        assert!(requests.complete(&reply("r", &by_connection, "forged"), loopback(), Some(&stranger)).is_err());
+        assert!(requests.complete(&reply("r", &by_connection, "bob"), loopback(), Some(&subscriber)).unwrap());
+
+        assert_eq!(String::from_utf8(output.lock().unwrap().clone()).unwrap(), "REPLY c-1 alice\nREPLY c-2 bob\n");
+    }
+
+    #[test]
+    fn test_request_timeout_and_failure() {
+        let requests = PendingRequests::default();
+        let (requester, output) = recording_writer();
+        requests.register("c-1", "r", requester.clone(), Duration::from_millis(250)).unwrap();
+        let failed = requests.register("c-2", "r", requester.clone(), Duration::from_secs(60)).unwrap();
+        let answered = requests.register("c-3", "r", requester, Duration::from_secs(60)).unwrap();
+
+        assert_eq!(requests.expire(Instant::now() + Duration::from_secs(1)), vec!["c-1"]);
+        requests.fail(&failed, "no subscribers");
+        requests.assign(&answered, Responder::address("users", "127.0.0.1:9000"));
+        assert!(requests.complete(&reply("r", &answered, "ok"), loopback(), None).unwrap());
+
+        assert_eq!(
+            String::from_utf8(output.lock().unwrap().clone()).unwrap(),
+            "TIMEOUT c-1 no reply within 250ms\nNOREPLY c-2 no subscribers\nREPLY c-3 ok\n",
+        );
+    }
+
+    #[test]
+    fn test_max_pending() {
+        let requests = PendingRequests::with_max_pending(2);
+        let (requester, _) = recording_writer();
+        let (other, _) = recording_writer();
+        let first = requests.register("c-1", "r", requester.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap();
+        requests.register("c-1", "r", other.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap();
+
+        let error = requests.register("c-2", "r", requester.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::QuotaExceeded);
+
+        // An answered request frees its slot and its correlation id
+        requests.assign(&first, Responder::address("users", "127.0.0.1:9000"));
+        assert!(requests.complete(&reply("r", &first, "ok"), loopback(), None).unwrap());
+        let again = requests.register("c-1", "r", requester.clone(), DEFAULT_REQUEST_TIMEOUT).unwrap();
+        requests.fail(&again, "no subscribers");
+        requests.register("c-1", "r", requester, DEFAULT_REQUEST_TIMEOUT).unwrap();
+        assert!(requests.register("c-3", "r", other, DEFAULT_REQUEST_TIMEOUT).is_err());
+    }
+}
@@ -2,15 +2,18 @@ use std::io::Result;
 use std::sync::Arc;
 use std::thread;
 
+use crate::transport::ack::Acknowledger;
 use crate::transport::protocol_factory::ProtocolFactory;
 use crate::transport::server_config::ServerConfig;
+use crate::transport::server_handle::{ListenerHandle, ServerControl, ServerHandle};
 use crate::transport::transport_interface::MessageConsumer;
 use crate::transport::transport_server::TransportServer;
 
 /// Server that encapsulates transport server initialization and protocol mapping
 pub struct Server {
     config: ServerConfig,
-    transport_server: Option<TransportServer>,
+    /// One transport server per ingress listener, labelled by listener name
+    transport_servers: Vec<(String, TransportServer)>,
 }
 
 impl Server {
@@ -18,7 +21,7 @@ impl Server {
     pub fn new() -> Self {
         Self {
             config: ServerConfig::new(),
-            transport_server: None,
+            transport_servers: Vec::new(),
         }
     }
 
@@ -26,50 +29,63 @@ impl Server {
     pub fn with_config(config: ServerConfig) -> Self {
         Self {
             config,
-            transport_server: None,
+            transport_servers: Vec::new(),
         }
     }
 
-    /// Initialize the server with the configured protocol
+    /// Initialize the server with the configured protocols
     pub fn initialize(&mut self) -> Result<()> {
-        // Create protocol instance based on configuration
-        let protocol = ProtocolFactory::create(self.config.protocol, &self.config.address)?;
+        let mut transport_servers = Vec::new();
         
-        // Create transport server with the protocol
-        let transport_server = TransportServer::new(protocol, self.config.address.clone());
+        for listener in self.config.listeners() {
+            // Create protocol instance based on configuration
+            let protocol = ProtocolFactory::create_for_listener(
+                &listener, &self.config.registration, self.config.connection_limits,
+            )?;
+            
+            // Create transport server with the protocol
+            transport_servers.push((listener.name, TransportServer::new(protocol, listener.address)));
+        }
         
-        self.transport_server = Some(transport_server);
+        self.transport_servers = transport_servers;
         
         Ok(())
     }
 
-    /// Start the server with a message consumer
-    pub fn start(&self, consumer: MessageConsumer) -> Result<()> {
-        let server = self.transport_server.as_ref()
-            .ok_or_else(|| std::io::Error::new(
+    /// Start the server with a consumer that also receives the listener name
+    ///
+    /// Returns once every listener is bound; the handle reports the bound
+    /// addresses and can stop the server.
+    pub fn start_labeled<F>(&self, consumer: F) -> Result<ServerHandle>
+    where
+        F: Fn(&str, String, &mut Acknowledger) + Send + Sync + 'static,
+    {
+        if self.transport_servers.is_empty() {
+            return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "Server not initialized. Call initialize() first."
-            ))?;
-
-        // Start the server in a separate thread to avoid blocking
-        let server_clone = TransportServer::new(
-            Arc::clone(&server.protocol),
-            server.address().to_string()
-        );
+            ));
+        }
         
-        thread::spawn(move || {
-            if let Err(e) = server_clone.start(consumer) {
-                eprintln!("Transport server error: {}", e);
+        let consumer = Arc::new(consumer);
+        let mut listeners = Vec::new();
+        
+        for (name, server) in &self.transport_servers {
+            let shared = Arc::clone(&consumer);
+            let label = name.clone();
+            let listener_consumer: MessageConsumer = Box::new(move |message, ack| shared(&label, message, ack));
+            
+            match start_listener(name, server, listener_consumer) {
+                Ok(listener) => listeners.push(listener),
+                Err(e) => {
+                    // Don't leave earlier listeners running when a later one fails to bind
+                    let _ = ServerHandle::new(listeners).shutdown();
+                    return Err(std::io::Error::new(e.kind(), format!("listener '{}': {}", name, e)));
+                }
             }
-        });
-
-        Ok(())
-    }
-
-    /// Initialize and start the server in one call
-    pub fn initialize_and_start(&mut self, consumer: MessageConsumer) -> Result<()> {
-        self.initialize()?;
-        self.start(consumer)
+        }
+        
+        Ok(ServerHandle::new(listeners))
     }
 
     /// Get the current configuration
@@ -80,18 +96,149 @@ impl Server {
     /// Update the configuration (requires re-initialization)
     pub fn set_config(&mut self, config: ServerConfig) {
         self.config = config;
-        self.transport_server = None; // Invalidate existing server
+        self.transport_servers.clear(); // Invalidate existing servers
     }
 
     /// Check if the server is initialized
     pub fn is_initialized(&self) -> bool {
-        self.transport_server.is_some()
+        !self.transport_servers.is_empty()
     }
 }
 
+/// Start one listener in its own thread and wait for it to bind
+fn start_listener(name: &str, server: &TransportServer, consumer: MessageConsumer) -> Result<ListenerHandle> {
+    // Start the server in a separate thread to avoid blocking
+    let server_clone = TransportServer::new(
+        Arc::clone(&server.protocol),
+        server.address().to_string()
+    );
+    
+    let (control, bound) = ServerControl::new(name);
+    let thread_control = Arc::clone(&control);
+    let thread = thread::spawn(move || {
+        let result = server_clone.start(consumer, Arc::clone(&thread_control));
+        if let Err(e) = &result {
+            // A bind failure is returned from `start`; later errors are only logged
+            let bind_error = std::io::Error::new(e.kind(), e.to_string());
+            if !thread_control.notify_bound(Err(bind_error)) {
+                eprintln!("Transport server error ({}): {}", thread_control.name(), e);
+            }
+        }
+        result
+    });
+
+    ListenerHandle::wait_for_bind(control, bound, thread)
+}
+
 impl Default for Server {
     fn default() -> Self {
         Self::new()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::server_config::{ListenerConfig, ProtocolType};
+    use crate::transport::server_handle::{ListenerStats, ServerState};
+    use std::io::{BufRead, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn start_on(address: &str) -> (Result<crate::transport::ServerHandle>, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = std::sync::Mutex::new(sender);
+        let mut server = Server::with_config(ServerConfig { address: address.to_string(), ..ServerConfig::default() });
+        if let Err(e) = server.initialize() {
+            return (Err(e), receiver);
+        }
+        let handle = server.start_labeled(move |_listener, message, _ack| {
+            let _ = sender.lock().unwrap().send(message);
+        });
+        (handle, receiver)
+    }
+
+    #[test]
+    fn test_start_reports_bound_address_and_stops() {
+        let (handle, received) = start_on("127.0.0.1:0");
+        let handle = handle.unwrap();
+        assert_ne!(handle.local_addr().port(), 0);
+        assert_eq!(handle.listeners()[0].state(), ServerState::Running);
+        
+        let mut client = TcpStream::connect(handle.local_addr()).unwrap();
+        writeln!(client, "event:hello").unwrap();
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), "event:hello");
+        
+        let address = handle.local_addr();
+        handle.shutdown().unwrap();
+        assert!(TcpStream::connect(address).is_err());
+    }
+
+    #[test]
+    fn test_listeners_share_pipeline_with_own_stats() {
+        let mut config = ServerConfig { address: "127.0.0.1:0".to_string(), ..ServerConfig::default() };
+        config.add_listener(ListenerConfig::new("local", ProtocolType::Tcp, "127.0.0.1:0".to_string()));
+        
+        let (sender, received) = mpsc::channel();
+        let sender = std::sync::Mutex::new(sender);
+        let mut server = Server::with_config(config);
+        server.initialize().unwrap();
+        let handle = server.start_labeled(move |listener, message, _ack| {
+            let _ = sender.lock().unwrap().send(format!("{}:{}", listener, message));
+        }).unwrap();
+        
+        let local = &handle.listeners()[1];
+        assert_eq!(local.name(), "local");
+        let mut client = TcpStream::connect(local.local_addr()).unwrap();
+        writeln!(client, "event:one").unwrap();
+        writeln!(client, "event:two").unwrap();
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), "local:event:one");
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), "local:event:two");
+        
+        let mut client = TcpStream::connect(handle.local_addr()).unwrap();
+        writeln!(client, "event:three").unwrap();
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), "ingress:event:three");
+        
+        assert_eq!(local.stats(), ListenerStats { connections_accepted: 1, messages_received: 2 });
+        assert_eq!(handle.listeners()[0].stats().messages_received, 1);
+        let status = handle.status();
+        assert_eq!(status[1].name, "local");
+        assert_eq!(status[1].state, ServerState::Running);
+        assert_eq!(status[1].stats.messages_received, 2);
+        handle.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_ack_mode_replies_on_connection() {
+        let mut server = Server::with_config(ServerConfig { address: "127.0.0.1:0".to_string(), ..ServerConfig::default() });
+        server.initialize().unwrap();
+        let handle = server.start_labeled(|_listener, message, ack| match message.as_str() {
+            "bad" => ack.rejected(None, "unparseable"),
+            _ => ack.parsed(None),
+        }).unwrap();
+        
+        let mut client = TcpStream::connect(handle.local_addr()).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut replies = std::io::BufReader::new(client.try_clone().unwrap()).lines();
+        
+        writeln!(client, "ACKMODE PARSED").unwrap();
+        assert_eq!(replies.next().unwrap().unwrap(), "OK ACKMODE PARSED");
+        writeln!(client, "event:hello").unwrap();
+        assert_eq!(replies.next().unwrap().unwrap(), "ACK 1");
+        writeln!(client, "bad").unwrap();
+        assert_eq!(replies.next().unwrap().unwrap(), "NACK 2 unparseable");
+        writeln!(client, "ACKMODE never").unwrap();
+        assert!(replies.next().unwrap().unwrap().starts_with("ERROR Unknown ack mode"));
+        
+        handle.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_start_returns_bind_error() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (handle, _) = start_on(&taken.local_addr().unwrap().to_string());
+        assert_eq!(handle.err().unwrap().kind(), std::io::ErrorKind::AddrInUse);
+    }
+}
@@ -1,3 +1,14 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::transport::producers::{DedupRule, DEFAULT_REAPER_INTERVAL};
+use crate::transport::producers::fanout::FanOutConfig;
+use crate::transport::producers::scheduler::SchedulerLimits;
+use crate::transport::rate_limit::RateLimitConfig;
+use crate::transport::reactor::{DEFAULT_MESSAGE_THREADS, DEFAULT_WORKER_THREADS};
+use crate::transport::registration_auth::RegistrationAuth;
+use crate::transport::request_reply::{DEFAULT_MAX_PENDING_REQUESTS, DEFAULT_REQUEST_TIMEOUT};
+
 /// Supported transport protocol types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProtocolType {
@@ -32,6 +43,104 @@ impl ProtocolType {
     }
 }
 
+/// Name of the listener described by `ServerConfig::protocol` and `ServerConfig::address`
+pub const PRIMARY_LISTENER_NAME: &str = "ingress";
+
+/// One ingress listener; every listener feeds the same routing pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerConfig {
+    /// Label used in logs and per-listener statistics
+    pub name: String,
+    /// Transport protocol type to use
+    pub protocol: ProtocolType,
+    /// Address to bind to
+    pub address: String,
+}
+
+impl ListenerConfig {
+    /// Create a listener configuration
+    pub fn new(name: &str, protocol: ProtocolType, address: String) -> Self {
+        Self {
+            name: name.to_string(),
+            protocol,
+            address,
+        }
+    }
+}
+
+/// Default cap on concurrent connections per ingress listener
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+
+/// Default size limit of one ingress message, including its newline
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Limits protecting ingress listeners from clients that hold on to resources
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Concurrent connections accepted per listener; further ones are refused
+    pub max_connections: usize,
+    /// How long a connection may wait between messages before it is closed
+    pub idle_timeout: Option<Duration>,
+    /// How long one message may take to arrive once its first byte was read
+    pub read_timeout: Option<Duration>,
+    /// Longest message accepted, in bytes; longer ones are rejected unread
+    pub max_frame_size: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: None,
+            read_timeout: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+/// Registration listener configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistrationConfig {
+    /// Whether the registration server runs at all
+    pub enabled: bool,
+    /// Address the dedicated registration listener binds to
+    pub address: String,
+    /// Only accept registration connections from the local machine
+    pub loopback_only: bool,
+    /// Serve registration on the ingress port instead of a dedicated listener;
+    /// a connection switches to registration by sending `REGISTRATION` as its first line
+    pub shared_with_ingress: bool,
+    /// Tokens allowed to run mutating commands (disabled when empty)
+    pub auth: RegistrationAuth,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            address: "0.0.0.0:49153".to_string(),
+            loopback_only: false,
+            shared_with_ingress: false,
+            auth: RegistrationAuth::disabled(),
+        }
+    }
+}
+
+impl RegistrationConfig {
+    /// Get the address the dedicated listener should bind to,
+    /// replacing the host with 127.0.0.1 when loopback-only
+    pub fn bind_address(&self) -> String {
+        if !self.loopback_only {
+            return self.address.clone();
+        }
+        
+        match self.address.rsplit_once(':') {
+            Some((_, port)) => format!("127.0.0.1:{}", port),
+            None => self.address.clone(),
+        }
+    }
+}
+
 /// Server configuration for initialization
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
@@ -39,6 +148,34 @@ pub struct ServerConfig {
     pub protocol: ProtocolType,
     /// Server address to bind to
     pub address: String,
+    /// Ingress listeners started alongside the primary one
+    pub additional_listeners: Vec<ListenerConfig>,
+    /// Registration listener settings
+    pub registration: RegistrationConfig,
+    /// Interval between sweeps that evict producers with expired leases
+    pub lease_reaper_interval: Duration,
+    /// How long a request published with `reply_to` waits for its reply
+    pub request_timeout: Duration,
+    /// Requests that may wait for their reply at once; more are rejected
+    pub max_pending_requests: usize,
+    /// Default TTL of messages per event, for messages without `expires_at` or `ttl_ms`
+    pub event_ttls: HashMap<String, Duration>,
+    /// Duplicate suppression rules per event
+    pub dedup: HashMap<String, DedupRule>,
+    /// Limits on messages from ingress connections
+    pub rate_limits: RateLimitConfig,
+    /// Connection and message size limits of ingress and registration listeners
+    pub connection_limits: ConnectionLimits,
+    /// Threads reading from the connections of every listener
+    pub worker_threads: usize,
+    /// Threads routing the messages those connections receive
+    pub message_threads: usize,
+    /// Threads and deadline of broadcasts to event subscribers
+    pub fanout: FanOutConfig,
+    /// Bounds on messages waiting for their delivery time
+    pub scheduler: SchedulerLimits,
+    /// Log every received and forwarded message
+    pub log_messages: bool,
 }
 
 impl Default for ServerConfig {
@@ -48,6 +185,20 @@ impl Default for ServerConfig {
             // Use 0.0.0.0 to bind to all interfaces and a higher port (49152+) 
             // to avoid Windows socket access restrictions and conflicts
             address: "0.0.0.0:49152".to_string(),
+            additional_listeners: Vec::new(),
+            registration: RegistrationConfig::default(),
+            lease_reaper_interval: DEFAULT_REAPER_INTERVAL,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_pending_requests: DEFAULT_MAX_PENDING_REQUESTS,
+            event_ttls: HashMap::new(),
+            dedup: HashMap::new(),
+            rate_limits: RateLimitConfig::default(),
+            connection_limits: ConnectionLimits::default(),
+            worker_threads: DEFAULT_WORKER_THREADS,
+            message_threads: DEFAULT_MESSAGE_THREADS,
+            fanout: FanOutConfig::default(),
+            scheduler: SchedulerLimits::default(),
+            log_messages: true,
         }
     }
 }
@@ -58,19 +209,15 @@ impl ServerConfig {
         Self::default()
     }
 
-    /// Create a new server configuration with custom address (defaults to TCP)
-    pub fn with_address(address: String) -> Self {
+    /// Create a new server configuration with custom protocol and address
+    pub fn with_protocol(protocol: ProtocolType, address: String) -> Self {
         Self {
-            protocol: ProtocolType::default(),
+            protocol,
             address,
+            ..Self::default()
         }
     }
 
-    /// Create a new server configuration with custom protocol and address
-    pub fn with_protocol(protocol: ProtocolType, address: String) -> Self {
-        Self { protocol, address }
-    }
-
     /// Set the protocol type
     pub fn set_protocol(&mut self, protocol: ProtocolType) -> &mut Self {
         self.protocol = protocol;
@@ -82,4 +229,40 @@ impl ServerConfig {
         self.address = address;
         self
     }
+
+    /// Add an ingress listener alongside the primary one
+    pub fn add_listener(&mut self, listener: ListenerConfig) -> &mut Self {
+        self.additional_listeners.push(listener);
+        self
+    }
+
+    /// Get every ingress listener, the primary one first
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        let primary = ListenerConfig::new(PRIMARY_LISTENER_NAME, self.protocol, self.address.clone());
+        std::iter::once(primary)
+            .chain(self.additional_listeners.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_defaults() {
+        let config = ServerConfig { address: "0.0.0.0:50000".to_string(), ..ServerConfig::default() };
+        assert!(config.registration.enabled);
+        assert_eq!(config.registration.bind_address(), "0.0.0.0:49153");
+    }
+
+    #[test]
+    fn test_registration_loopback_bind_address() {
+        let registration = RegistrationConfig {
+            address: "0.0.0.0:50001".to_string(),
+            loopback_only: true,
+            ..RegistrationConfig::default()
+        };
+        assert_eq!(registration.bind_address(), "127.0.0.1:50001");
+    }
 }
@@ -0,0 +1,255 @@
+use std::io::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::Serialize;
+
+use crate::transport::shutdown;
+
+/// Handle of the server started by this process, reported by the registration `STATS` command
+static RUNNING_SERVER: Mutex<Option<ServerHandle>> = Mutex::new(None);
+
+/// Lifecycle state of a started server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerState {
+    /// Accepting connections
+    Running,
+    /// Stop requested, the listener is closing
+    Stopping,
+    /// The listener has closed
+    Stopped,
+}
+
+/// Shared between a running protocol listener and its `ListenerHandle`
+///
+/// The protocol reports its bound address through `notify_bound`, checks
+/// `is_stopping` between accepts and records per-listener statistics;
+/// `stop` wakes a listener blocked in accept.
+pub struct ServerControl {
+    name: String,
+    stopping: AtomicBool,
+    bound: Mutex<Option<Sender<Result<SocketAddr>>>>,
+    local_addr: Mutex<Option<SocketAddr>>,
+    connections: AtomicU64,
+    messages: AtomicU64,
+}
+
+impl ServerControl {
+    /// Create a control for the named listener and the receiver that gets the bind result
+    pub fn new(name: &str) -> (Arc<Self>, Receiver<Result<SocketAddr>>) {
+        let (sender, receiver) = mpsc::channel();
+        let control = Arc::new(Self {
+            name: name.to_string(),
+            stopping: AtomicBool::new(false),
+            bound: Mutex::new(Some(sender)),
+            local_addr: Mutex::new(None),
+            connections: AtomicU64::new(0),
+            messages: AtomicU64::new(0),
+        });
+        (control, receiver)
+    }
+
+    /// Get the listener name used in logs
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Report the bind result, returning `false` if it was already reported
+    pub fn notify_bound(&self, result: Result<SocketAddr>) -> bool {
+        let Some(sender) = self.bound.lock().unwrap().take() else {
+            return false;
+        };
+        if let Ok(address) = &result {
+            *self.local_addr.lock().unwrap() = Some(*address);
+        }
+        let _ = sender.send(result);
+        true
+    }
+
+    /// Count an accepted connection
+    pub fn record_connection(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a received message
+    pub fn record_message(&self) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Check if the listener should stop (this server or the whole process)
+    pub fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst) || shutdown::is_shutting_down()
+    }
+
+    /// Ask the listener to stop and wake it from a blocking accept
+    pub fn stop(&self) {
+        if self.stopping.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(address) = *self.local_addr.lock().unwrap() {
+            shutdown::wake_listener(address);
+        }
+    }
+}
+
+impl ServerState {
+    /// Get the lowercase name used in status output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerState::Running => "running",
+            ServerState::Stopping => "stopping",
+            ServerState::Stopped => "stopped",
+        }
+    }
+}
+
+/// Counters for one ingress listener
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ListenerStats {
+    pub connections_accepted: u64,
+    pub messages_received: u64,
+}
+
+/// Name, address, state and counters of one ingress listener
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListenerStatus {
+    pub name: String,
+    pub address: SocketAddr,
+    pub state: ServerState,
+    #[serde(flatten)]
+    pub stats: ListenerStats,
+}
+
+/// Handle to one running ingress listener
+pub struct ListenerHandle {
+    local_addr: SocketAddr,
+    control: Arc<ServerControl>,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl ListenerHandle {
+    /// Wait for the listener thread to bind and wrap it in a handle
+    pub(crate) fn wait_for_bind(
+        control: Arc<ServerControl>,
+        bound: Receiver<Result<SocketAddr>>,
+        thread: JoinHandle<Result<()>>,
+    ) -> Result<Self> {
+        match bound.recv() {
+            Ok(Ok(local_addr)) => Ok(Self { local_addr, control, thread }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(std::io::Error::other("Server exited before binding")),
+        }
+    }
+
+    /// Get the listener name
+    pub fn name(&self) -> &str {
+        self.control.name()
+    }
+
+    /// Get the address the listener is actually bound to (useful with port 0)
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Get the listener's connection and message counters
+    pub fn stats(&self) -> ListenerStats {
+        ListenerStats {
+            connections_accepted: self.control.connections.load(Ordering::Relaxed),
+            messages_received: self.control.messages.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the current lifecycle state
+    pub fn state(&self) -> ServerState {
+        if self.thread.is_finished() {
+            ServerState::Stopped
+        } else if self.control.is_stopping() {
+            ServerState::Stopping
+        } else {
+            ServerState::Running
+        }
+    }
+
+    /// Stop accepting connections and stop reading from open ones
+    ///
+    /// Messages already read are still routed and acknowledged.
+    pub fn stop(&self) {
+        self.control.stop();
+    }
+
+    /// Wait for the listener to finish, returning its result
+    pub fn join(self) -> Result<()> {
+        self.thread.join()
+            .unwrap_or_else(|_| Err(std::io::Error::other("Server thread panicked")))
+    }
+}
+
+/// Handle to a started server and all of its ingress listeners
+///
+/// Dropping the handle leaves the server running; call `stop` to close it.
+pub struct ServerHandle {
+    listeners: Vec<ListenerHandle>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(listeners: Vec<ListenerHandle>) -> Self {
+        Self { listeners }
+    }
+
+    /// Get the address the primary listener is actually bound to (useful with port 0)
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listeners[0].local_addr()
+    }
+
+    /// Get every listener, the primary one first
+    pub fn listeners(&self) -> &[ListenerHandle] {
+        &self.listeners
+    }
+
+    /// Get the name, address, state and counters of every listener
+    pub fn status(&self) -> Vec<ListenerStatus> {
+        self.listeners.iter()
+            .map(|listener| ListenerStatus {
+                name: listener.name().to_string(),
+                address: listener.local_addr(),
+                state: listener.state(),
+                stats: listener.stats(),
+            })
+            .collect()
+    }
+
+    /// Stop accepting connections on every listener and stop reading from open ones
+    pub fn stop(&self) {
+        for listener in &self.listeners {
+            listener.stop();
+        }
+    }
+
+    /// Wait for the listeners to finish in order, stopping at the first error
+    pub fn join(self) -> Result<()> {
+        self.listeners.into_iter().try_for_each(ListenerHandle::join)
+    }
+
+    /// Stop the server and wait for it to finish
+    pub fn shutdown(self) -> Result<()> {
+        self.stop();
+        self.join()
+    }
+}
+
+/// Keep the server's handle so its listeners show up in status output
+pub fn set_running_server(handle: ServerHandle) {
+    *RUNNING_SERVER.lock().unwrap() = Some(handle);
+}
+
+/// Get the status of the running server's listeners, if a server was started
+pub fn running_listeners() -> Vec<ListenerStatus> {
+    RUNNING_SERVER.lock().unwrap()
+        .as_ref()
+        .map(ServerHandle::status)
+        .unwrap_or_default()
+}
@@ -0,0 +1,126 @@
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Default time allowed for in-flight messages to drain on shutdown
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Process-wide shutdown coordination
+///
+/// Listeners stop accepting once shutdown begins, and every message being
+/// routed holds an `InFlightGuard` so shutdown can wait for it to finish.
+struct ShutdownState {
+    stopping: AtomicBool,
+    in_flight: Mutex<usize>,
+    drained: Condvar,
+    listeners: Mutex<Vec<SocketAddr>>,
+}
+
+static SHUTDOWN: OnceLock<ShutdownState> = OnceLock::new();
+
+fn state() -> &'static ShutdownState {
+    SHUTDOWN.get_or_init(|| ShutdownState {
+        stopping: AtomicBool::new(false),
+        in_flight: Mutex::new(0),
+        drained: Condvar::new(),
+        listeners: Mutex::new(Vec::new()),
+    })
+}
+
+/// Marks one message as in flight until dropped
+pub struct InFlightGuard(());
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let state = state();
+        let mut in_flight = state.in_flight.lock().unwrap();
+        *in_flight -= 1;
+        if *in_flight == 0 {
+            state.drained.notify_all();
+        }
+    }
+}
+
+/// Track a message being routed; shutdown waits for the guard to be dropped
+pub fn track_message() -> InFlightGuard {
+    *state().in_flight.lock().unwrap() += 1;
+    InFlightGuard(())
+}
+
+/// Get the number of messages currently in flight
+pub fn in_flight() -> usize {
+    *state().in_flight.lock().unwrap()
+}
+
+/// Check if shutdown has begun
+pub fn is_shutting_down() -> bool {
+    state().stopping.load(Ordering::SeqCst)
+}
+
+/// Register a listener so shutdown can wake it from a blocking accept
+pub fn register_listener(address: SocketAddr) {
+    state().listeners.lock().unwrap().push(address);
+}
+
+/// Stop accepting connections and new messages
+///
+/// Blocked listeners are woken with a local connection so they observe the
+/// flag and close their sockets.
+pub fn begin_shutdown() {
+    let state = state();
+    if state.stopping.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    for address in state.listeners.lock().unwrap().drain(..) {
+        wake_listener(address);
+    }
+}
+
+/// Connect to a listener so a blocking accept returns and sees the stop flag
+pub(crate) fn wake_listener(mut address: SocketAddr) {
+    if address.ip().is_unspecified() {
+        address.set_ip(if address.is_ipv4() {
+            [127, 0, 0, 1].into()
+        } else {
+            std::net::Ipv6Addr::LOCALHOST.into()
+        });
+    }
+    let _ = TcpStream::connect_timeout(&address, Duration::from_millis(200));
+}
+
+/// Wait until no messages are in flight, returning `false` if the deadline passed first
+pub fn wait_for_drain(timeout: Duration) -> bool {
+    let state = state();
+    let deadline = Instant::now() + timeout;
+    let mut in_flight = state.in_flight.lock().unwrap();
+
+    while *in_flight > 0 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        in_flight = state.drained.wait_timeout(in_flight, remaining).unwrap().0;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drain_waits_for_in_flight_messages() {
+        let guard = track_message();
+        assert!(!wait_for_drain(Duration::from_millis(20)));
+
+        let worker = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+        assert!(wait_for_drain(Duration::from_secs(5)));
+        worker.join().unwrap();
+    }
+}
@@ -1,12 +1,22 @@
 use std::io::Result;
+use std::sync::Arc;
+
+use crate::transport::ack::Acknowledger;
+use crate::transport::server_handle::ServerControl;
 
 /// Trait for message consumer callbacks
-pub type MessageConsumer = Box<dyn Fn(String) + Send + Sync>;
+///
+/// The consumer reports parsing and routing progress through the
+/// `Acknowledger`, which replies to the producer when it enabled ack mode.
+pub type MessageConsumer = Box<dyn Fn(String, &mut Acknowledger) + Send + Sync>;
 
 /// Trait defining transport protocol behavior
 pub trait TransportProtocol: Send + Sync {
-    /// Start listening on the specified address
-    fn listen(&self, address: &str, consumer: MessageConsumer) -> Result<()>;
+    /// Listen on the specified address until `control` asks to stop
+    ///
+    /// Implementations report the bound address (or bind error) through
+    /// `control.notify_bound` before accepting connections.
+    fn listen(&self, address: &str, consumer: MessageConsumer, control: Arc<ServerControl>) -> Result<()>;
     
     /// Get the protocol name
     fn protocol_name(&self) -> &str;
@@ -1,6 +1,7 @@
 use std::io::Result;
 use std::sync::Arc;
 
+use crate::transport::server_handle::ServerControl;
 use crate::transport::transport_interface::{MessageConsumer, TransportProtocol};
 
 /// Transport server that manages protocol initialization and message consumption
@@ -15,13 +16,13 @@ impl TransportServer {
         Self { protocol, address }
     }
 
-    /// Start the transport server and begin consuming messages
-    pub fn start(&self, consumer: MessageConsumer) -> Result<()> {
+    /// Start the transport server and consume messages until `control` asks to stop
+    pub fn start(&self, consumer: MessageConsumer, control: Arc<ServerControl>) -> Result<()> {
         println!("Starting {} transport server on {}", 
                  self.protocol.protocol_name(), 
                  self.address);
         
-        self.protocol.listen(&self.address, consumer)
+        self.protocol.listen(&self.address, consumer, control)
     }
 
     /// Get the server address